@@ -1,27 +1,79 @@
-use std::iter::Iterator;
-use std::rc::{Rc, Weak};
-use std::hash::Hash;
-use std::collections::HashSet;
-use std::mem;
-use std::fmt::Debug;
-use std::time::Duration;
-use super::{InnerWheel, Wheel, Resolution, wheel_sizes};
+use core::iter::{Iterator, FromIterator};
+use alloc::rc::{Rc, Weak};
+use core::hash::Hash;
+use collections::HashMap;
+use core::mem;
+use core::fmt::Debug;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+use alloc::collections::BTreeMap;
+use alloc::vec::{Drain, Vec};
+use super::{Entry, InnerWheel, IntoIter, Wheel, Resolution};
+#[cfg(feature = "copy-wheel")]
+use super::{CopyWheel, Slot};
+use super::{StartError, check_duration, wheel_sizes, deadline, is_due, remaining, resolution_duration};
+use super::{advance_slots, next_deadline, remove_slot_entry, slot_deadline, DuplicatePolicy, Rounding, TickPolicy, Ticks};
+use super::{coalesce, exact_deadline, precise_slot, range_wheels, sized_wheels, slots_ahead, span_deadline};
+use super::with_targets;
+#[cfg(feature = "std")]
+use super::{start_instant, time_until};
+use super::{default_resolutions, network_timeout_resolutions, coarse_job_resolutions};
+
+// The fewest stale entries worth sweeping, so that small wheels don't compact on every cancel
+const MIN_COMPACT: usize = 1024;
 
 /// This wheel requires an allocation for each timer as it creates an Rc<T> for its key. This allows
-/// the key to be stored in a global hashset that can be used for O(1) cancel. A `Weak<T>` is stored
+/// the key to be stored in a global hashmap that can be used for O(1) cancel. A `Weak<T>` is stored
 /// in the wheel slot, so that if the timer is cancelled, the memory is de-allocatd. When the expiry
 /// for that slot comes around, an attempt to promote the Weak reference will return `None` and so
 /// it will be ignored when draining the wheel slot. If the timer expires before it is cancelled,
 /// the weak reference can be used to remove the Rc<T> from the HashMap, as well as trigger the user
 /// timeout behavior.
 ///
+/// The hashmap also records the tick at which each key is due to expire. A timer that is restarted
+/// keeps its allocation, and the weak reference left in its old slot is ignored since the timer is
+/// no longer due at that tick.
+///
+/// A weak reference keeps the allocation of its key alive, though not the key itself, until its
+/// slot comes round, which can be a day away in the hour wheel. The wheel counts the entries that
+/// cancels and restarts leave behind, and once they outnumber the running timers it sweeps them
+/// out of every slot, as `compact` does.
+///
+/// Interval timers keep a second strong reference to their key alongside their period, and are
+/// rescheduled as they expire rather than being removed.
+///
 /// The minimum duration of a timer is 1 ms.
-/// The maximum duration of a timer is 1 day.
+/// Timers longer than the span of the wheels wait in an overflow list until they are close enough
+/// to go in a slot.
 pub struct AllocWheel<T: Eq + Hash + Debug + Clone> {
-    resolutions: Vec<Resolution>,
-    keys: HashSet<Rc<T>>,
-    wheels: Vec<InnerWheel<Weak<T>>>,
-    slot_indexes: Vec<usize>,
+    pub(crate) resolutions: Vec<Resolution>,
+    pub(crate) keys: HashMap<Rc<T>, u64>,
+    pub(crate) periods: HashMap<Rc<T>, Duration>,
+    pub(crate) wheels: Vec<InnerWheel<Weak<T>>>,
+    pub(crate) slot_indexes: Vec<usize>,
+    pub(crate) tick: u64,
+    pub(crate) expired: Vec<T>,
+    pub(crate) duplicate_policy: DuplicatePolicy,
+    pub(crate) paused: bool,
+    pub(crate) precise: bool,
+    pub(crate) slack: Duration,
+    pub(crate) max_timers: Option<usize>,
+    pub(crate) rounding: Rounding,
+    pub(crate) tick_policy: TickPolicy,
+    // Time passed to `expire_elapsed` that didn't make up a whole tick
+    pub(crate) leftover: Duration,
+    // An upper bound on the slot and overflow entries of timers that are no longer running
+    pub(crate) stale: usize,
+    // The exact deadlines of timers started in precise mode, while `keys` holds the deadline of
+    // the slot each one is waiting in. These are copies of the keys, since only interval timers
+    // may hold a second reference.
+    pub(crate) targets: HashMap<T, u64>,
+    // Timers due after the last slot of the wheels, by deadline
+    pub(crate) overflow: BTreeMap<u64, Vec<Weak<T>>>,
+    // The instant of tick 0, for `expire_until`, once known
+    #[cfg(feature = "std")]
+    pub(crate) origin: Option<Instant>
 }
 
 impl<T: Eq + Hash + Debug + Clone> AllocWheel<T> {
@@ -34,113 +86,917 @@ impl<T: Eq + Hash + Debug + Clone> AllocWheel<T> {
     /// The maximum value of the wheel is its minimum resolution times the number of slots in that
     /// resolution's wheel. For example if the maximum resolution is 1 second then the max timer
     /// that may be represented is 1 minute, since the second wheel always only contains 60 slots.
-    /// If larger timer durations are desired, the user should add another, lower resolution.
-    /// The absolute maximum timer duration is 1 day.
-    pub fn new(mut resolutions: Vec<Resolution>) -> AllocWheel<T> {
+    /// Longer timers are held in an overflow list until they come within range, which costs a
+    /// lookup in a sorted map on each tick. Adding a lower resolution keeps them in the wheels.
+    pub fn new(resolutions: Vec<Resolution>) -> AllocWheel<T> {
+        AllocWheel::with_capacity(resolutions, 0)
+    }
+
+    /// Create a set of hierarchical inner wheels with room for `capacity` timers before the key
+    /// set needs to grow
+    pub fn with_capacity(mut resolutions: Vec<Resolution>, capacity: usize) -> AllocWheel<T> {
         let sizes = wheel_sizes(&mut resolutions);
+        AllocWheel::from_sizes(resolutions, sizes, capacity)
+    }
+
+    /// Create a set of hierarchical inner wheels, giving the coarsest a number of slots other than
+    /// the one it would have in `new`. See `CopyWheel::with_sizes`.
+    pub fn with_sizes(resolutions: Vec<Resolution>, slots: &[(Resolution, usize)]) -> AllocWheel<T> {
+        let (resolutions, sizes) = sized_wheels(resolutions, slots)
+            .unwrap_or_else(|reason| panic!("{}", reason));
+        AllocWheel::from_sizes(resolutions, sizes, 0)
+    }
+
+    /// Create a set of hierarchical inner wheels that ticks every `tick` and holds timers of up
+    /// to `max`. See `CopyWheel::with_range`.
+    pub fn with_range(tick: Duration, max: Duration) -> AllocWheel<T> {
+        let (resolutions, slots) = range_wheels(tick, max);
+        AllocWheel::with_sizes(resolutions, &slots)
+    }
+
+    // Create the wheels from sorted resolutions and the number of slots in each
+    pub(crate) fn from_sizes(resolutions: Vec<Resolution>,
+                             sizes: Vec<usize>,
+                             capacity: usize) -> AllocWheel<T>
+    {
         let indexes = vec![0; sizes.len()];
         AllocWheel {
-            resolutions: resolutions,
-            keys: HashSet::new(),
+            resolutions,
+            keys: HashMap::with_capacity(capacity),
+            periods: HashMap::new(),
             wheels: sizes.iter().map(|size| InnerWheel::new(*size)).collect(),
-            slot_indexes: indexes
+            slot_indexes: indexes,
+            tick: 0,
+            expired: Vec::new(),
+            duplicate_policy: DuplicatePolicy::default(),
+            paused: false,
+            precise: false,
+            slack: Duration::from_secs(0),
+            max_timers: None,
+            rounding: Rounding::default(),
+            tick_policy: TickPolicy::default(),
+            leftover: Duration::from_secs(0),
+            stale: 0,
+            targets: HashMap::new(),
+            overflow: BTreeMap::new(),
+            #[cfg(feature = "std")]
+            origin: start_instant()
+        }
+    }
+
+    /// A wheel suited to network timeouts such as request deadlines and idle connections.
+    ///
+    /// The wheel must be driven by calling `expire` every 10ms and supports timers of up to an
+    /// hour.
+    pub fn for_network_timeouts() -> AllocWheel<T> {
+        AllocWheel::new(network_timeout_resolutions())
+    }
+
+    /// A wheel suited to coarse grained jobs such as cache sweeps and periodic reports.
+    ///
+    /// The wheel must be driven by calling `expire` every second and supports timers of up to a
+    /// day.
+    pub fn for_coarse_jobs() -> AllocWheel<T> {
+        AllocWheel::new(coarse_job_resolutions())
+    }
+
+    /// The number of running timers
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// What `start` does with a timer that is already running
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicate_policy
+    }
+
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+
+    /// Stop the wheel ticking until `resume` is called. See `CopyWheel::pause`.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Start the wheel ticking again from where it was paused. See `CopyWheel::resume`.
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Start the wheel ticking again from `now`. See `CopyWheel::resume_at`.
+    #[cfg(feature = "std")]
+    pub fn resume_at(&mut self, now: Instant) {
+        self.resume();
+        self.sync_clock(now);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Expire timers started from now on at the tick the first wheel would expire them on. See
+    /// `CopyWheel::set_precise`.
+    pub fn set_precise(&mut self, precise: bool) {
+        self.precise = precise;
+    }
+
+    pub fn is_precise(&self) -> bool {
+        self.precise
+    }
+
+    /// Let timers started from now on expire up to `slack` late, so that timers due close
+    /// together fire on the same tick. See `CopyWheel::set_slack`.
+    pub fn set_slack(&mut self, slack: Duration) {
+        self.slack = slack;
+    }
+
+    pub fn slack(&self) -> Duration {
+        self.slack
+    }
+
+    /// Limit the number of running timers, or lift the limit with `None`. See
+    /// `CopyWheel::set_max_timers`.
+    pub fn set_max_timers(&mut self, max: Option<usize>) {
+        self.max_timers = max;
+    }
+
+    pub fn max_timers(&self) -> Option<usize> {
+        self.max_timers
+    }
+
+    /// Whether the wheel holds its maximum number of timers
+    pub fn is_full(&self) -> bool {
+        self.max_timers.is_some_and(|max| self.keys.len() >= max)
+    }
+
+    /// How timers started from now on are rounded to the slots of their wheel. See
+    /// `CopyWheel::set_rounding`.
+    pub fn set_rounding(&mut self, rounding: Rounding) {
+        self.rounding = rounding;
+    }
+
+    pub fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
+    /// Whether timers started from now on may expire late or early. See
+    /// `CopyWheel::set_tick_policy`.
+    pub fn set_tick_policy(&mut self, policy: TickPolicy) {
+        self.tick_policy = policy;
+    }
+
+    pub fn tick_policy(&self) -> TickPolicy {
+        self.tick_policy
+    }
+
+    /// The number of calls to `expire` until the next timer expires, or `None` if no timers are
+    /// running.
+    ///
+    /// The calls before then return nothing, so an event loop can sleep through them and catch up
+    /// afterwards. The answer comes from scanning the slots ahead rather than every key.
+    pub fn ticks_until_next_expiry(&self) -> Option<u64> {
+        let keys = &self.keys;
+        let next = next_deadline(self.tick, &self.wheels, &self.slot_indexes, |key, deadline| {
+            key.upgrade().is_some_and(|key| keys.get(&key) == Some(&deadline))
+        }).or_else(|| self.next_overflow())?;
+        Some(next - self.tick)
+    }
+
+    /// The time until the next timer expires, or `None` if no timers are running
+    pub fn next_expiry_in(&self) -> Option<Duration> {
+        let ticks = self.ticks_until_next_expiry()?;
+        Some(Ticks(ticks).to_duration(self.tick_duration()))
+    }
+
+    /// Whether a timer is running for `key`.
+    ///
+    /// The keys are held in `Rc`s, which can only be looked up by `&T` rather than any borrowed
+    /// form of `T` as in `CopyWheel`.
+    pub fn contains(&self, key: &T) -> bool {
+        self.keys.contains_key(key)
+    }
+
+    /// The time left until the timer for `key` expires, or `None` if it isn't running.
+    ///
+    /// The time left is rounded up to a whole number of ticks.
+    pub fn remaining(&self, key: &T) -> Option<Duration> {
+        let deadline = self.due(key)?;
+        Some(remaining(self.tick, deadline, resolution_duration(self.resolutions[0])))
+    }
+
+    /// The number of running timers in the wheel for `resolution`. See `CopyWheel::len_at`.
+    pub fn len_at(&self, resolution: Resolution) -> usize {
+        let index = match self.resolutions.iter().position(|r| *r == resolution) {
+            Some(index) => index,
+            None => return 0
+        };
+        // A timer restarted with the same deadline has two entries in its slot
+        let mut running: HashMap<_, ()> = HashMap::new();
+        for (i, slot) in self.wheels[index].slots.iter().enumerate() {
+            let deadline = slot_deadline(self.tick, &self.wheels, &self.slot_indexes, index, i);
+            for key in slot.entries.iter().filter_map(|key| key.upgrade()) {
+                if self.keys.get(&key) == Some(&deadline) {
+                    running.insert(Rc::as_ptr(&key), ());
+                }
+            }
         }
+        running.len()
     }
 
-    fn insert_hours(&mut self, key: Weak<T>, time: Duration) -> Result<(), (Weak<T>, Duration)> {
-        let slot = time.as_secs()/3600;
-        self.insert(key, time, Resolution::Hour, slot as usize + 1)
+    /// Get the entry for a key in order to start, restart or cancel its timer
+    pub fn entry(&mut self, key: T) -> Entry<'_, T, AllocWheel<T>> {
+        let running = self.keys.contains_key(&key);
+        Entry::new(self, key, running)
     }
 
-    fn insert_minutes(&mut self, key: Weak<T>, time: Duration) -> Result<(), (Weak<T>, Duration)> {
-        let slot = time.as_secs()/60;
-        self.insert(key, time, Resolution::Min, slot as usize + 1)
+    /// Start a timer for each key and duration, as `start` does.
+    ///
+    /// Room for the new keys is reserved up front from the iterator's size hint, so loading many
+    /// timers at once doesn't grow the key set repeatedly.
+    pub fn start_all<I: IntoIterator<Item = (T, Duration)>>(&mut self, timers: I) {
+        let timers = timers.into_iter();
+        self.keys.reserve(timers.size_hint().0);
+        for (key, time) in timers {
+            self.start(key, time);
+        }
     }
 
-    fn insert_seconds(&mut self, key: Weak<T>, time: Duration) -> Result<(), (Weak<T>, Duration)> {
-        self.insert(key, time, Resolution::Sec, time.as_secs() as usize + 1)
+    /// Cancel every timer.
+    ///
+    /// The slots keep their capacity, so a cleared wheel can be reused without allocating again.
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.periods.clear();
+        self.targets.clear();
+        self.overflow.clear();
+        for wheel in &mut self.wheels {
+            for slot in &mut wheel.slots {
+                slot.entries.clear();
+            }
+        }
+        self.stale = 0;
     }
 
-    fn insert_hundred_ms(&mut self, key: Weak<T>, time: Duration) -> Result<(), (Weak<T>, Duration)> {
-        let slot = time.subsec_nanos()/(1000*1000*100);
-        self.insert(key, time, Resolution::HundredMs, slot as usize + 1)
+    /// Drop the slot and overflow entries of cancelled and restarted timers, returning how many
+    /// were dropped.
+    ///
+    /// This happens on its own once there are more of them than running timers, so it is only
+    /// needed to free their memory sooner, such as after cancelling a large batch.
+    pub fn compact(&mut self) -> usize {
+        let keys = &self.keys;
+        let mut dropped = 0;
+        for index in 0..self.wheels.len() {
+            for slot in 0..self.wheels[index].slots.len() {
+                let deadline = slot_deadline(self.tick, &self.wheels, &self.slot_indexes, index, slot);
+                let entries = &mut self.wheels[index].slots[slot].entries;
+                let len = entries.len();
+                entries.retain(|key| key.upgrade().is_some_and(|key| keys.get(&key) == Some(&deadline)));
+                dropped += len - entries.len();
+            }
+        }
+        self.overflow.retain(|target, entries| {
+            let len = entries.len();
+            entries.retain(|key| key.upgrade().is_some_and(|key| keys.get(&key) == Some(target)));
+            dropped += len - entries.len();
+            !entries.is_empty()
+        });
+        self.stale = 0;
+        dropped
     }
 
-    fn insert_ten_ms(&mut self, key: Weak<T>, time: Duration) -> Result<(), (Weak<T>, Duration)> {
-        let slot = time.subsec_nanos()/(1000*1000*10);
-        self.insert(key, time, Resolution::TenMs, slot  as usize + 1)
+    // Count entries left behind by timers that stopped running, compacting once they outnumber
+    // the running timers so that the sweeps cost O(1) per cancel on average
+    fn add_stale(&mut self, count: usize) {
+        self.stale += count;
+        if self.stale > self.keys.len().max(MIN_COMPACT) {
+            self.compact();
+        }
     }
 
-    fn insert_ms(&mut self, key: Weak<T>, time: Duration) -> Result<(), (Weak<T>, Duration)> {
-        let slot = time.subsec_nanos()/(1000*1000);
-        self.insert(key, time, Resolution::Ms, slot as usize + 1)
+    /// Cancel every timer, returning each key with the time that was left before it would have
+    /// expired.
+    ///
+    /// This is `into_iter` for a wheel that is still in use, such as when moving the timers to
+    /// another wheel or persisting them on shutdown.
+    pub fn drain(&mut self) -> Vec<(T, Duration)> {
+        let keys = with_targets(mem::take(&mut self.keys), mem::take(&mut self.targets));
+        // Drop the references held for interval timers so the keys can be unwrapped
+        self.clear();
+        IntoIter::allocated(keys, self.tick, resolution_duration(self.resolutions[0])).collect()
+    }
+
+    /// Keep only the timers whose keys `f` returns true for, and cancel the rest
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        let len = self.keys.len();
+        self.keys.retain(|key, _| f(&**key));
+        let keys = &self.keys;
+        self.periods.retain(|key, _| keys.contains_key(key));
+        self.targets.retain(|key, _| keys.contains_key(key));
+        self.add_stale(len - self.keys.len());
+    }
+
+    /// Cancel the timers whose keys `f` returns true for, returning how many were cancelled
+    pub fn cancel_where<F: FnMut(&T) -> bool>(&mut self, mut f: F) -> usize {
+        let len = self.keys.len();
+        self.retain(|key| !f(key));
+        len - self.keys.len()
+    }
+
+    /// Cancel a timer, returning the time that was left before it would have expired, or `None`
+    /// if it wasn't running.
+    ///
+    /// The time left is rounded up to a whole number of ticks.
+    pub fn cancel(&mut self, key: &T) -> Option<Duration> {
+        self.periods.remove(key);
+        let deadline = self.keys.remove(key)?;
+        let deadline = self.targets.remove(key).unwrap_or(deadline);
+        self.add_stale(1);
+        Some(remaining(self.tick, deadline, resolution_duration(self.resolutions[0])))
+    }
+
+    /// Move a running timer to a new deadline, removing its old slot entry. See
+    /// `CopyWheel::reschedule`.
+    pub fn reschedule(&mut self, key: T, time: Duration) -> bool {
+        let (key, old_deadline) = match self.keys.remove_entry(&key) {
+            Some(entry) => entry,
+            None => return false
+        };
+        let ptr = Rc::as_ptr(&key);
+        if !self.schedule(key, Some(old_deadline), time) {
+            return false;
+        }
+        remove_slot_entry(self.tick, &mut self.wheels, &self.slot_indexes, old_deadline,
+                          |entry| entry.as_ptr() == ptr);
+        true
+    }
+
+    /// Push a running timer's deadline out by `extra`. See `CopyWheel::extend_by`.
+    pub fn extend_by(&mut self, key: T, extra: Duration) -> bool {
+        let left = match self.remaining(&key) {
+            Some(left) => left,
+            None => return false
+        };
+        // A timer with one tick left fires on the next call to `expire`, like one started for a
+        // tick, so the time to schedule counts from there
+        let tick = self.tick_duration();
+        let time = left.saturating_sub(tick) + extra;
+        time < tick || self.reschedule(key, time)
+    }
+
+    /// Start a timer that isn't already running, without adjusting the duration to fit.
+    ///
+    /// `start` restarts a running timer, holds durations longer than the wheel span in the
+    /// overflow list and ignores durations shorter than a tick. This reports each of those as an
+    /// error instead and leaves the wheel unchanged.
+    pub fn try_start(&mut self, key: T, time: Duration) -> Result<(), StartError> {
+        if self.keys.contains_key(&key) {
+            return Err(StartError::DuplicateKey);
+        }
+        if self.is_full() {
+            return Err(StartError::Full);
+        }
+        check_duration(&self.resolutions, time)?;
+        if !self.schedule(Rc::new(key), None, time) {
+            // Resolutions with gaps between them can't represent every duration in the span
+            return Err(StartError::TooShort(resolution_duration(self.resolutions[0])));
+        }
+        Ok(())
+    }
+
+    /// Move all the pending timers into a `CopyWheel` with the same resolutions.
+    ///
+    /// The new wheel carries on from the same tick, so each timer expires exactly when it would
+    /// have in this wheel.
+    #[cfg(feature = "copy-wheel")]
+    pub fn into_copy(self) -> CopyWheel<T> {
+        let wheels = self.wheels.into_iter().map(|wheel| InnerWheel {
+            slots: wheel.slots.into_iter().map(|slot| Slot {
+                entries: slot.entries.iter()
+                    .filter_map(|key| key.upgrade())
+                    .map(|key| (*key).clone())
+                    .collect()
+            }).collect()
+        }).collect();
+        let overflow = self.overflow.into_iter().map(|(target, entries)| {
+            let entries = entries.iter()
+                .filter_map(|key| key.upgrade())
+                .map(|key| (*key).clone())
+                .collect();
+            (target, entries)
+        }).collect();
+        // Release the references held for interval timers so each key can be unwrapped
+        let periods = self.periods.into_iter()
+            .map(|(key, period)| ((*key).clone(), period))
+            .collect();
+        let mut keys = HashMap::with_capacity(self.keys.len());
+        for (key, deadline) in self.keys {
+            keys.insert(Rc::try_unwrap(key).unwrap(), deadline);
+        }
+        CopyWheel {
+            resolutions: self.resolutions,
+            keys,
+            periods,
+            wheels,
+            slot_indexes: self.slot_indexes,
+            tick: self.tick,
+            expired: Vec::new(),
+            duplicate_policy: self.duplicate_policy,
+            paused: self.paused,
+            precise: self.precise,
+            slack: self.slack,
+            max_timers: self.max_timers,
+            rounding: self.rounding,
+            tick_policy: self.tick_policy,
+            leftover: self.leftover,
+            targets: self.targets,
+            overflow,
+            #[cfg(feature = "std")]
+            origin: self.origin
+        }
+    }
+
+    /// Expire the timers due at the next tick, like `expire`, but return them as a draining
+    /// iterator over a buffer kept by the wheel. See `CopyWheel::expire_iter`.
+    pub fn expire_iter(&mut self) -> Drain<'_, T> {
+        let mut expired = mem::take(&mut self.expired);
+        self.expire_into(&mut expired);
+        self.expired = expired;
+        self.expired.drain(..)
+    }
+
+    /// Expire the timers due at the next tick, like `expire`, but append them to `expired`. See
+    /// `CopyWheel::expire_into`.
+    pub fn expire_into(&mut self, expired: &mut Vec<T>) {
+        if self.paused {
+            return;
+        }
+        // Take keys out of self temporarily so we don't have to borrow self
+        let mut keys = HashMap::new();
+        mem::swap(&mut keys, &mut self.keys);
+
+        self.tick += 1;
+        let now = self.tick;
+        let start = expired.len();
+        let mut rearm = Vec::new();
+        for (ref mut wheel, ref mut slot_index) in self.wheels.iter_mut().zip(&mut self.slot_indexes) {
+            **slot_index = (**slot_index + 1) % wheel.slots.len();
+            expired.extend(wheel.slots[**slot_index].entries.drain(..)
+                           .filter_map(|key| key.upgrade())
+                           .filter(|key| is_due(&mut keys, &**key, now))
+                           // Only interval timers have another strong reference
+                           .map(|key| Rc::try_unwrap(key).unwrap_or_else(|key| {
+                               let copy = (*key).clone();
+                               rearm.push(key);
+                               copy
+                           })));
+
+            // We haven't wrapped around to the next wheel
+            if **slot_index != 0 {
+                break;
+            }
+        }
+
+        // Make keys part of self again
+        mem::swap(&mut keys, &mut self.keys);
+
+        self.cascade(expired, start);
+        self.refeed(expired, &mut rearm);
+        for key in rearm {
+            // Interval timers that moved down a wheel aren't due yet
+            if self.keys.contains_key(&key) {
+                continue;
+            }
+            if let Some(period) = self.periods.get(&key).cloned() {
+                self.schedule(key, None, period);
+            }
+        }
+    }
+
+    /// Move the wheel on by the ticks that fit in `elapsed`, carrying any time left over into the
+    /// next call. See `CopyWheel::expire_elapsed`.
+    pub fn expire_elapsed(&mut self, elapsed: Duration) -> Vec<T> {
+        let tick = self.tick_duration();
+        let elapsed = self.leftover + elapsed;
+        let ticks = Ticks::from_duration(elapsed, tick);
+        self.leftover = elapsed - ticks.to_duration(tick);
+        self.advance(ticks.0)
+    }
+
+    /// Move the wheel on by `ticks` at once, returning every timer that came due along the way.
+    /// See `CopyWheel::advance`.
+    pub fn advance(&mut self, ticks: u64) -> Vec<T> {
+        if self.paused {
+            return Vec::new();
+        }
+        let end = self.tick + ticks;
+        let mut expired = Vec::new();
+        let mut rearm = Vec::new();
+        {
+            let keys = &mut self.keys;
+            advance_slots(self.tick, ticks, &mut self.wheels, &mut self.slot_indexes, |entries| {
+                entries.retain(|weak| {
+                    let key = match weak.upgrade() {
+                        Some(key) => key,
+                        None => return false
+                    };
+                    match keys.get(&key).cloned() {
+                        Some(deadline) if deadline <= end => {
+                            keys.remove(&key);
+                            // Only interval timers have another strong reference
+                            expired.push(Rc::try_unwrap(key).unwrap_or_else(|key| {
+                                let copy = (*key).clone();
+                                rearm.push(key);
+                                copy
+                            }));
+                            false
+                        },
+                        Some(_) => true,
+                        None => false
+                    }
+                });
+            });
+        }
+        self.tick = end;
+
+        self.cascade(&mut expired, 0);
+        self.refeed(&mut expired, &mut rearm);
+        for key in rearm {
+            if self.keys.contains_key(&key) {
+                continue;
+            }
+            if let Some(period) = self.periods.get(&key).cloned() {
+                self.schedule(key, None, period);
+            }
+        }
+        expired
+    }
+
+    /// The instant of the wheel's last tick, counting from when it was created
+    #[cfg(feature = "std")]
+    pub fn last_tick_at(&self) -> Instant {
+        let origin = self.origin.expect("the wheel needs the time from sync_clock first");
+        origin + Ticks(self.tick).to_duration(self.tick_duration())
+    }
+
+    /// Make `now` the instant of the wheel's last tick. See `CopyWheel::sync_clock`.
+    #[cfg(feature = "std")]
+    pub fn sync_clock(&mut self, now: Instant) {
+        let since = Ticks(self.tick).to_duration(self.tick_duration());
+        if let Some(origin) = now.checked_sub(since) {
+            self.origin = Some(origin);
+        }
+    }
+
+    /// Start a timer that expires at `deadline` rather than after a duration. See
+    /// `CopyWheel::start_at`.
+    #[cfg(feature = "std")]
+    pub fn start_at(&mut self, key: T, deadline: Instant) {
+        let wait = deadline.saturating_duration_since(self.last_tick_at());
+        let time = time_until(wait, self.tick_duration());
+        self.start(key, time);
+    }
+
+    /// Restart a running timer so that it expires at `deadline`. See `CopyWheel::restart_at`.
+    #[cfg(feature = "std")]
+    pub fn restart_at(&mut self, key: T, deadline: Instant) -> bool {
+        let wait = deadline.saturating_duration_since(self.last_tick_at());
+        let time = time_until(wait, self.tick_duration());
+        self.restart(key, time)
+    }
+
+    /// The instant a running timer is due to expire
+    #[cfg(feature = "std")]
+    pub fn expires_at(&self, key: &T) -> Option<Instant> {
+        Some(self.last_tick_at() + self.remaining(key)?)
+    }
+
+    /// Expire every tick that has passed by `now`, counting from when the wheel was created. See
+    /// `CopyWheel::expire_until`.
+    #[cfg(feature = "std")]
+    pub fn expire_until(&mut self, now: Instant) -> Vec<T> {
+        let origin = match self.origin {
+            Some(origin) => origin,
+            None => {
+                self.sync_clock(now);
+                return Vec::new();
+            }
+        };
+        let elapsed = now.saturating_duration_since(origin);
+        let due = Ticks::from_duration(elapsed, self.tick_duration());
+        let ticks = due.saturating_sub(Ticks(self.tick));
+        self.advance(ticks.0)
+    }
+
+    /// Expire every tick that has passed by `now`. See `CopyWheel::expire_at`.
+    #[cfg(feature = "std")]
+    pub fn expire_at(&mut self, now: Instant) -> Vec<T> {
+        self.expire_until(now)
+    }
+
+    /// How long an event loop using `expire_until` can wait at `now` before the next timer is
+    /// due. See `CopyWheel::poll_timeout`.
+    #[cfg(feature = "std")]
+    pub fn poll_timeout(&self, now: Instant) -> Option<Duration> {
+        if self.paused {
+            return None;
+        }
+        let due = self.last_tick_at() + self.next_expiry_in()?;
+        Some(due.saturating_duration_since(now))
+    }
+
+    // The tick a running timer is due at
+    fn due(&self, key: &T) -> Option<u64> {
+        self.targets.get(key).or_else(|| self.keys.get(key)).cloned()
+    }
+
+    // Move the precise timers in `expired[start..]` that aren't due yet down to finer wheels
+    fn cascade(&mut self, expired: &mut Vec<T>, start: usize) {
+        if self.targets.is_empty() {
+            return;
+        }
+        for key in expired.split_off(start) {
+            match self.targets.get(&key).cloned() {
+                Some(target) if target > self.tick => {
+                    // Interval timers keep sharing the allocation held with their period
+                    let key = match self.periods.get_key_value(&key) {
+                        Some((key, _)) => key.clone(),
+                        None => Rc::new(key)
+                    };
+                    self.place(key, target);
+                },
+                Some(_) => {
+                    self.targets.remove(&key);
+                    expired.push(key);
+                },
+                None => expired.push(key)
+            }
+        }
+    }
+
+    // Move the timers in the overflow list that have come within range into the wheels. Any that
+    // came due while `advance` skipped ahead are pushed onto `expired`, with the interval timers
+    // among them also pushed onto `rearm`.
+    fn refeed(&mut self, expired: &mut Vec<T>, rearm: &mut Vec<Rc<T>>) {
+        let limit = span_deadline(self.tick, &self.wheels);
+        while let Some(entry) = self.overflow.first_entry() {
+            if *entry.key() > limit {
+                break;
+            }
+            let (target, entries) = entry.remove_entry();
+            for weak in entries {
+                let key = match weak.upgrade() {
+                    Some(key) => key,
+                    None => continue
+                };
+                // Skip timers that were restarted
+                if self.keys.get(&key) != Some(&target) {
+                    continue;
+                }
+                if target <= self.tick {
+                    self.keys.remove(&key);
+                    self.targets.remove(&*key);
+                    // Only interval timers have another strong reference
+                    expired.push(Rc::try_unwrap(key).unwrap_or_else(|key| {
+                        let copy = (*key).clone();
+                        rearm.push(key);
+                        copy
+                    }));
+                } else if self.targets.contains_key(&*key) || target == self.tick + 1 {
+                    // A timer due on the next tick is too short to schedule again
+                    self.place(key, target);
+                } else {
+                    let time = Ticks(target - self.tick - 1).to_duration(self.tick_duration());
+                    self.schedule(key, None, time);
+                }
+            }
+        }
+    }
+
+    // The deadline of the first running timer in the overflow list
+    fn next_overflow(&self) -> Option<u64> {
+        self.overflow.iter()
+            .find(|(target, entries)| {
+                entries.iter()
+                    .filter_map(|key| key.upgrade())
+                    .any(|key| self.keys.get(&key) == Some(target))
+            })
+            .map(|(target, _)| *target)
+    }
+
+    // Put a precise timer in the slot it waits in until the next move or `target`
+    fn place(&mut self, key: Rc<T>, target: u64) {
+        let (wheel, slot, deadline) =
+            precise_slot(self.tick, &self.wheels, &self.slot_indexes, target);
+        self.wheels[wheel].slots[slot].entries.push(Rc::downgrade(&key));
+        self.targets.insert((*key).clone(), target);
+        self.keys.insert(key, deadline);
+    }
+
+    // Schedule a timer without changing its period, keeping `old_deadline` and returning false if
+    // the duration is too short for the wheel
+    fn schedule(&mut self, key: Rc<T>, old_deadline: Option<u64>, time: Duration) -> bool {
+        let time = coalesce(self.tick, self.tick_duration(), time, self.slack);
+        let target = match (exact_deadline(self.tick, self.tick_duration(), time), old_deadline) {
+            (Some(target), _) => self.tick_policy.deadline(self.tick, target),
+            (None, Some(deadline)) => {
+                self.keys.insert(key, deadline);
+                return false;
+            },
+            (None, None) => return false
+        };
+        if target > span_deadline(self.tick, &self.wheels) {
+            self.overflow.entry(target).or_default().push(Rc::downgrade(&key));
+            if self.precise {
+                self.targets.insert((*key).clone(), target);
+            } else {
+                self.targets.remove(&*key);
+            }
+            self.keys.insert(key, target);
+            return true;
+        }
+        if self.precise {
+            self.place(key, target);
+            return true;
+        }
+        let weak = Rc::downgrade(&key);
+        let mut result = Err((weak, time));
+        for index in (0..self.resolutions.len()).rev() {
+            if let Err((weak, time)) = result {
+                let resolution = self.resolutions[index];
+                let ahead = slots_ahead(resolution, time, index == self.resolutions.len() - 1);
+                let slot = self.tick_policy.slots(self.rounding.slots(ahead, resolution, time));
+                result = self.insert(weak, time, resolution, slot);
+            } else {
+                break;
+            }
+        }
+        match (result, old_deadline) {
+            (Ok(deadline), _) => {
+                self.targets.remove(&*key);
+                self.keys.insert(key, deadline);
+                true
+            },
+            (Err(_), Some(deadline)) => {
+                self.keys.insert(key, deadline);
+                false
+            },
+            (Err(_), None) => false
+        }
+    }
+
+    // Remove a running timer's key so it can be rescheduled, or allocate a new one
+    fn take_key(&mut self, key: T) -> (Rc<T>, Option<u64>) {
+        match self.keys.remove_entry(&key) {
+            Some((key, deadline)) => (key, Some(deadline)),
+            None => (Rc::new(key), None)
+        }
     }
 
     fn insert(&mut self,
               key: Weak<T>,
               time: Duration,
               resolution: Resolution,
-              mut slot: usize) -> Result<(), (Weak<T>, Duration)>
+              mut slot: usize) -> Result<u64, (Weak<T>, Duration)>
     {
-        // The slot will always be at least 2 ahead of the current, since we add one in each of the
-        // insert_xxx methods
-        if slot == 1 { return Err((key, time)); }
-        if let Some(wheel_index) = self.resolutions.iter().rposition(|ref r| **r == resolution) {
+        // A timer too short for the resolution belongs in a finer wheel
+        if slot == 0 { return Err((key, time)); }
+        if let Some(wheel_index) = self.resolutions.iter().rposition(|r| *r == resolution) {
             let max_slot = self.wheels[wheel_index].slots.len();
             if slot > max_slot {
                 slot = max_slot
             }
             let slot_index = (self.slot_indexes[wheel_index] + slot) % max_slot;
             self.wheels[wheel_index].slots[slot_index].entries.push(key);
-            return Ok(());
+            return Ok(deadline(self.tick, &self.wheels[..wheel_index], slot));
         }
         Err((key, time))
     }
 }
 
+#[cfg(feature = "copy-wheel")]
+impl<T: Eq + Hash + Debug + Clone> From<CopyWheel<T>> for AllocWheel<T> {
+    fn from(wheel: CopyWheel<T>) -> AllocWheel<T> {
+        wheel.into_alloc()
+    }
+}
+
+impl<T: Eq + Hash + Debug + Clone> Default for AllocWheel<T> {
+    /// A wheel with 10ms, 100ms, 1s, 1 minute and 1 hour resolutions.
+    ///
+    /// The wheel must be driven by calling `expire` every 10ms and supports timers of up to a day.
+    fn default() -> AllocWheel<T> {
+        AllocWheel::new(default_resolutions())
+    }
+}
+
+impl<T: Eq + Hash + Debug + Clone> Extend<(T, Duration)> for AllocWheel<T> {
+    /// Start a timer for each key and duration
+    fn extend<I: IntoIterator<Item = (T, Duration)>>(&mut self, iter: I) {
+        self.start_all(iter);
+    }
+}
+
+impl<T: Eq + Hash + Debug + Clone> FromIterator<(T, Duration)> for AllocWheel<T> {
+    /// Create a wheel with the default resolutions and start a timer for each key and duration
+    fn from_iter<I: IntoIterator<Item = (T, Duration)>>(iter: I) -> AllocWheel<T> {
+        let mut wheel = AllocWheel::default();
+        wheel.extend(iter);
+        wheel
+    }
+}
+
+impl<T: Eq + Hash + Debug + Clone> IntoIterator for AllocWheel<T> {
+    type Item = (T, Duration);
+    type IntoIter = IntoIter<T>;
+
+    /// Consume the wheel, yielding each pending key with the time remaining until it would expire
+    fn into_iter(self) -> IntoIter<T> {
+        let keys = with_targets(self.keys, self.targets);
+        IntoIter::allocated(keys, self.tick, resolution_duration(self.resolutions[0]))
+    }
+}
+
 impl<T: Eq + Hash + Debug + Clone> Wheel<T> for AllocWheel<T> {
+    type Expired = Vec<T>;
+
     /// Start a timer with the given duration.
+    ///
+    /// Starting a timer that is already running restarts it with the new duration, unless the
+    /// duplicate policy says otherwise.
     fn start(&mut self, key: T, time: Duration) {
-        let key = Rc::new(key);
-        let weak = Rc::downgrade(&key.clone());
-        self.keys.insert(key);
-        let _ = self.insert_hours(weak, time)
-            .or_else(|(weak, time)| self.insert_minutes(weak, time))
-            .or_else(|(weak, time)| self.insert_seconds(weak, time))
-            .or_else(|(weak, time)| self.insert_hundred_ms(weak, time))
-            .or_else(|(weak, time)| self.insert_ten_ms(weak, time))
-            .or_else(|(weak, time)| self.insert_ms(weak, time));
+        // Reuse the allocation of a running timer
+        let (key, old_deadline) = self.take_key(key);
+        if old_deadline.is_none() && self.is_full() {
+            return;
+        }
+        let old_target = self.targets.get(&*key).cloned();
+        if !self.schedule(key.clone(), old_deadline, time) {
+            return;
+        }
+        if let (Some(old), Some(new)) = (old_deadline, self.due(&key)) {
+            if self.duplicate_policy.keeps_running(old_target.unwrap_or(old), new) {
+                // The new slot entry is skipped when it comes around, since it isn't due then
+                match old_target {
+                    Some(target) => self.targets.insert((*key).clone(), target),
+                    None => self.targets.remove(&*key)
+                };
+                self.keys.insert(key, old);
+                self.add_stale(1);
+                return;
+            }
+        }
+        self.periods.remove(&key);
+        if old_deadline.is_some() {
+            self.add_stale(1);
+        }
     }
 
-    /// Cancel a timer.
-    fn stop(&mut self, key: T) {
-        self.keys.remove(&key);
+    fn stop(&mut self, key: T) -> Option<Duration> {
+        self.cancel(&key)
     }
 
     /// Return any expired timer keys
     fn expire(&mut self) -> Vec<T> {
-        // Take keys out of self temporarily so we don't have to borrow self
-        let mut keys = HashSet::new();
-        mem::swap(&mut keys, &mut self.keys);
-
         let mut expired = Vec::new();
-        for (ref mut wheel, ref mut slot_index) in self.wheels.iter_mut().zip(&mut self.slot_indexes) {
-            **slot_index = (**slot_index + 1) % wheel.slots.len();
-            expired.extend(wheel.slots[**slot_index].entries.drain(..)
-                           .filter_map(|key| key.upgrade())
-                           .filter(|key| keys.remove(key))
-                           .map(|key| Rc::try_unwrap(key).unwrap()));
+        self.expire_into(&mut expired);
+        expired
+    }
 
-            // We haven't wrapped around to the next wheel
-            if **slot_index != 0 {
-                break;
+    fn start_with_delay(&mut self, key: T, delay: Duration, period: Duration) {
+        let (key, old_deadline) = self.take_key(key);
+        if old_deadline.is_none() && self.is_full() {
+            return;
+        }
+        if self.schedule(key.clone(), old_deadline, delay) {
+            let period = period.max(self.tick_duration());
+            self.periods.insert(key, period);
+            if old_deadline.is_some() {
+                self.add_stale(1);
             }
+        }
+    }
 
+    fn restart(&mut self, key: T, time: Duration) -> bool {
+        // Keep the existing allocation rather than dropping and recreating it
+        match self.keys.remove_entry(&key) {
+            Some((key, deadline)) => {
+                let restarted = self.schedule(key, Some(deadline), time);
+                if restarted {
+                    self.add_stale(1);
+                }
+                restarted
+            },
+            None => false
         }
+    }
 
-        // Make keys part of self again
-        mem::swap(&mut keys, &mut self.keys);
-        expired
+    fn tick_duration(&self) -> Duration {
+        resolution_duration(self.resolutions[0])
     }
 }
 
@@ -199,12 +1055,556 @@ mod tests {
         verify_expire_contains_only_weak_refs(&mut wheel);
     }
 
+    #[test]
+    fn default_and_presets_expire() {
+        let mut wheels = [AllocWheel::default(), AllocWheel::for_network_timeouts()];
+        for wheel in wheels.iter_mut() {
+            wheel.start("a", Duration::from_millis(30));
+            for _ in 0..3 {
+                assert_eq!(0, wheel.expire().len());
+            }
+            assert_eq!(vec!["a"], wheel.expire());
+        }
+
+        let mut wheel = AllocWheel::for_coarse_jobs();
+        wheel.start("a", Duration::from_secs(5));
+        for _ in 0..5 {
+            assert_eq!(0, wheel.expire().len());
+        }
+        assert_eq!(vec!["a"], wheel.expire());
+    }
+
+    #[test]
+    fn collect_and_extend() {
+        let mut wheel: AllocWheel<&'static str> = vec![("a", Duration::from_millis(10))]
+            .into_iter()
+            .collect();
+        wheel.extend(vec![("b", Duration::from_millis(10)), ("c", Duration::from_millis(20))]);
+        assert_eq!(0, wheel.expire().len());
+        let mut expired = wheel.expire();
+        expired.sort();
+        assert_eq!(vec!["a", "b"], expired);
+        assert_eq!(vec!["c"], wheel.expire());
+    }
+
+    #[test]
+    fn interval_timers_repeat_until_stopped() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start_interval("a", Duration::from_millis(20));
+        wheel.start_with_delay("b", Duration::from_millis(40), Duration::from_secs(1));
+        let mut fired = Vec::new();
+        for tick in 1..13 {
+            for key in wheel.expire() {
+                fired.push((tick, key));
+            }
+        }
+        assert_eq!(vec![(3, "a"), (5, "b"), (6, "a"), (9, "a"), (12, "a")], fired);
+        wheel.stop("a");
+        wheel.start("b", Duration::from_millis(10));
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(vec!["b"], wheel.expire());
+        for _ in 0..200 {
+            assert_eq!(0, wheel.expire().len());
+        }
+    }
+
+    #[test]
+    fn restart_only_affects_running_timers() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        assert!(!wheel.restart("a", Duration::from_millis(10)));
+        wheel.start_interval("b", Duration::from_millis(20));
+        wheel.expire();
+        let before = Rc::as_ptr(wheel.keys.get_key_value(&"b").unwrap().0);
+        assert!(wheel.restart("b", Duration::from_millis(50)));
+        assert_eq!(before, Rc::as_ptr(wheel.keys.get_key_value(&"b").unwrap().0));
+        assert!(wheel.restart("b", Duration::from_millis(10)));
+        assert!(!wheel.restart("b", Duration::from_micros(10)));
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(vec!["b"], wheel.expire());
+        // The period is kept
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(vec!["b"], wheel.expire());
+        assert_eq!(0, wheel.expire().len());
+    }
+
+    #[test]
+    fn ranges_hold_their_longest_timers() {
+        let mut wheel = AllocWheel::with_range(Duration::from_millis(16), Duration::from_secs(50));
+        assert_eq!(Duration::from_millis(16), wheel.tick_duration());
+        assert_eq!(Ok(()), wheel.try_start("frame", Duration::from_millis(16)));
+        assert_eq!(Ok(()), wheel.try_start("idle", Duration::from_secs(50)));
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(vec!["frame"], wheel.expire());
+
+        for &hours in &[1, 30 * 24] {
+            let longest = Duration::from_secs(hours * 3600);
+            let mut wheel = AllocWheel::with_range(Duration::from_millis(16), longest);
+            assert_eq!(Ok(()), wheel.try_start("save", longest));
+            let ticks = Ticks::from_duration(longest, Duration::from_millis(16)).0;
+            assert!(wheel.advance(ticks).is_empty());
+            assert_eq!(vec!["save"], wheel.advance(ticks));
+        }
+
+        let mut wheel = AllocWheel::with_range(Duration::from_secs(1), Duration::from_secs(90));
+        assert_eq!(Ok(()), wheel.try_start("a", Duration::from_secs(90)));
+        assert_eq!(1, wheel.len_at(Resolution::Min));
+    }
+
+    #[test]
+    fn try_start_reports_misuse() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        assert_eq!(Err(StartError::Zero), wheel.try_start("a", Duration::from_secs(0)));
+        assert_eq!(Err(StartError::TooShort(Duration::from_millis(10))),
+                   wheel.try_start("a", Duration::from_millis(5)));
+        // Durations past the span wait in the overflow list
+        assert_eq!(Ok(()), wheel.try_start("long", Duration::from_secs(60)));
+        assert_eq!(Ok(()), wheel.try_start("a", Duration::from_millis(10)));
+        assert_eq!(Err(StartError::DuplicateKey), wheel.try_start("a", Duration::from_secs(1)));
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(vec!["a"], wheel.expire());
+        assert!(wheel.advance(5998).is_empty());
+        assert_eq!(vec!["long"], wheel.advance(1));
+    }
+
+    #[test]
+    fn expire_iter_reuses_its_buffer() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        for key in 0..10u64 {
+            wheel.start(key, Duration::from_millis(10));
+        }
+        assert_eq!(0, wheel.expire_iter().count());
+        assert_eq!((0..10).collect::<Vec<_>>(), wheel.expire_iter().collect::<Vec<_>>());
+        let capacity = wheel.expired.capacity();
+        wheel.start(1, Duration::from_millis(10));
+        assert_eq!(0, wheel.expire_iter().count());
+        assert_eq!(vec![1], wheel.expire_iter().collect::<Vec<_>>());
+        assert_eq!(capacity, wheel.expired.capacity());
+    }
+
+    #[test]
+    fn expire_into_appends() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start_interval("a", Duration::from_millis(10));
+        wheel.start("b", Duration::from_millis(30));
+        let mut expired = vec!["z"];
+        for _ in 0..4 {
+            wheel.expire_into(&mut expired);
+        }
+        assert_eq!(vec!["z", "a", "b", "a"], expired);
+    }
+
+    #[test]
+    fn len_counts_running_timers() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        assert!(wheel.is_empty());
+        wheel.start("a", Duration::from_millis(50));
+        wheel.start("b", Duration::from_secs(5));
+        wheel.start("c", Duration::from_millis(50));
+        wheel.start("c", Duration::from_millis(50));
+        assert_eq!(3, wheel.len());
+        assert_eq!(2, wheel.len_at(Resolution::TenMs));
+        assert_eq!(1, wheel.len_at(Resolution::Sec));
+        assert_eq!(0, wheel.len_at(Resolution::Min));
+        for _ in 0..6 {
+            wheel.expire();
+        }
+        wheel.start("b", Duration::from_millis(30));
+        assert_eq!(1, wheel.len());
+        assert_eq!(1, wheel.len_at(Resolution::TenMs));
+        assert_eq!(0, wheel.len_at(Resolution::Sec));
+    }
+
+    #[test]
+    fn contains_running_timers() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(10));
+        wheel.start("b", Duration::from_millis(10));
+        wheel.stop("b");
+        assert!(wheel.contains(&"a"));
+        assert!(!wheel.contains(&"b"));
+        wheel.expire();
+        wheel.expire();
+        assert!(!wheel.contains(&"a"));
+    }
+
+    #[test]
+    fn remaining_counts_down() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(30));
+        wheel.start("b", Duration::from_secs(2));
+        assert_eq!(Some(Duration::from_millis(40)), wheel.remaining(&"a"));
+        wheel.expire();
+        assert_eq!(Some(Duration::from_millis(30)), wheel.remaining(&"a"));
+        assert_eq!(Some(Duration::from_millis(2990)), wheel.remaining(&"b"));
+        assert_eq!(None, wheel.remaining(&"c"));
+        wheel.stop("a");
+        assert_eq!(None, wheel.remaining(&"a"));
+    }
+
+    #[test]
+    fn clear_cancels_everything() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(10));
+        wheel.start_interval("b", Duration::from_millis(10));
+        wheel.start("c", Duration::from_secs(1));
+        wheel.clear();
+        assert!(wheel.is_empty());
+        assert!(wheel.wheels.iter().all(|w| w.slots.iter().all(|s| s.entries.is_empty())));
+        wheel.start("a", Duration::from_millis(10));
+        for _ in 0..200 {
+            for key in wheel.expire() {
+                assert_eq!("a", key);
+            }
+        }
+    }
+
+    #[test]
+    fn drain_returns_pending_timers() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(20));
+        wheel.start_interval("b", Duration::from_secs(2));
+        wheel.expire();
+        let mut pending = wheel.drain();
+        pending.sort();
+        assert_eq!(vec![("a", Duration::from_millis(20)), ("b", Duration::from_millis(2990))],
+                   pending);
+        assert!(wheel.is_empty());
+        for _ in 0..400 {
+            assert_eq!(0, wheel.expire().len());
+        }
+    }
+
+    #[test]
+    fn cancel_where_matches_keys() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        for key in &["peer1/a", "peer1/b", "peer2/a", "peer2/b"] {
+            wheel.start(*key, Duration::from_millis(10));
+        }
+        wheel.start_interval("peer3/a", Duration::from_millis(10));
+        assert_eq!(2, wheel.cancel_where(|key| key.starts_with("peer1/")));
+        wheel.retain(|key| !key.ends_with("/b"));
+        assert!(wheel.expire().is_empty());
+        let mut expired = wheel.expire();
+        expired.sort();
+        assert_eq!(vec!["peer2/a", "peer3/a"], expired);
+        wheel.retain(|_| false);
+        assert!(wheel.is_empty());
+        assert!(wheel.periods.is_empty());
+    }
+
+    #[test]
+    fn start_all_loads_many_timers() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start_all((0..1000u64).map(|key| (key, Duration::from_millis(10 * (key % 5 + 1)))));
+        assert_eq!(1000, wheel.len());
+        let mut expired = 0;
+        for tick in 1..7 {
+            let keys = wheel.expire();
+            assert!(keys.iter().all(|key| key % 5 + 2 == tick));
+            expired += keys.len();
+        }
+        assert_eq!(1000, expired);
+    }
+
+    #[test]
+    fn duplicate_policies() {
+        use super::super::{DuplicatePolicy, WheelBuilder};
+        let policies = [(DuplicatePolicy::Replace, 2, 4),
+                        (DuplicatePolicy::KeepEarliest, 2, 2),
+                        (DuplicatePolicy::KeepLatest, 4, 4),
+                        (DuplicatePolicy::Reject, 4, 2)];
+        for &(policy, a, b) in policies.iter() {
+            let mut wheel = WheelBuilder::new()
+                .resolutions(vec![Resolution::TenMs, Resolution::Sec])
+                .duplicate_policy(policy)
+                .build_alloc();
+            assert_eq!(policy, wheel.duplicate_policy());
+            wheel.start("a", Duration::from_millis(30));
+            wheel.start("a", Duration::from_millis(10));
+            wheel.start("b", Duration::from_millis(10));
+            wheel.start("b", Duration::from_millis(30));
+            let mut fired = Vec::new();
+            for tick in 1..10 {
+                fired.extend(wheel.expire().into_iter().map(|key| (key, tick)));
+            }
+            fired.sort();
+            assert_eq!(vec![("a", a), ("b", b)], fired);
+        }
+    }
+
+    #[test]
+    fn next_expiry_matches_expire() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec, Resolution::Min]);
+        assert_eq!(None, wheel.ticks_until_next_expiry());
+        wheel.start("a", Duration::from_secs(30));
+        wheel.start("b", Duration::from_millis(500));
+        wheel.start("c", Duration::from_millis(200));
+        wheel.stop("c");
+        assert_eq!(Some(51), wheel.ticks_until_next_expiry());
+        assert_eq!(Some(Duration::from_millis(510)), wheel.next_expiry_in());
+        let mut ticks = 0;
+        while ticks < 10_000 {
+            let next = wheel.ticks_until_next_expiry();
+            ticks += 1;
+            let expired = wheel.expire();
+            assert_eq!(next == Some(1), !expired.is_empty());
+            if expired == vec!["a"] {
+                break;
+            }
+        }
+        assert_eq!(3100, ticks);
+        assert_eq!(None, wheel.ticks_until_next_expiry());
+    }
+
+    #[test]
+    fn advance_matches_ticking() {
+        let new = || {
+            let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec, Resolution::Min]);
+            for &(key, millis) in &[("a", 50), ("b", 900), ("c", 1500), ("d", 70_000), ("e", 80)] {
+                wheel.start(key, Duration::from_millis(millis));
+            }
+            wheel.stop("e");
+            wheel
+        };
+        let mut ticked = new();
+        let mut advanced = new();
+        for &ticks in &[0, 1, 99, 10_000] {
+            let mut expected = Vec::new();
+            for _ in 0..ticks {
+                expected.extend(ticked.expire());
+            }
+            expected.sort();
+            let mut expired = advanced.advance(ticks);
+            expired.sort();
+            assert_eq!(expected, expired);
+            ticked.start("f", Duration::from_millis(20));
+            advanced.start("f", Duration::from_millis(20));
+            assert_eq!(ticked.remaining(&"f"), advanced.remaining(&"f"));
+        }
+
+        advanced.start_interval("g", Duration::from_millis(10));
+        assert_eq!(vec!["g", "f"], advanced.advance(5));
+        assert_eq!(vec!["g"], advanced.advance(2));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn expire_until_follows_the_clock() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        let start = wheel.last_tick_at();
+        wheel.start("a", Duration::from_millis(30));
+        wheel.start("b", Duration::from_millis(100));
+        assert!(wheel.expire_until(start + Duration::from_millis(39)).is_empty());
+        assert_eq!(start + Duration::from_millis(30), wheel.last_tick_at());
+        assert_eq!(vec!["a"], wheel.expire_until(start + Duration::from_millis(45)));
+        wheel.expire();
+        assert!(wheel.expire_until(start + Duration::from_millis(45)).is_empty());
+        assert_eq!(vec!["b"], wheel.expire_until(start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn paused_wheels_keep_their_timers() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(20));
+        wheel.expire();
+        wheel.pause();
+        assert!(wheel.is_paused());
+        wheel.start("b", Duration::from_millis(10));
+        for _ in 0..10 {
+            assert!(wheel.expire().is_empty());
+        }
+        assert!(wheel.advance(10).is_empty());
+        assert_eq!(Some(Duration::from_millis(20)), wheel.remaining(&"a"));
+        wheel.resume();
+        assert!(wheel.expire().is_empty());
+        let mut expired = wheel.expire();
+        expired.sort();
+        assert_eq!(vec!["a", "b"], expired);
+    }
+
+    #[test]
+    fn reschedule_leaves_no_stale_entries() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        let entries = |wheel: &AllocWheel<&str>| -> usize {
+            wheel.wheels.iter().flat_map(|wheel| &wheel.slots).map(|slot| slot.entries.len()).sum()
+        };
+        assert!(!wheel.reschedule("a", Duration::from_millis(10)));
+        wheel.start("a", Duration::from_millis(10));
+        wheel.start("b", Duration::from_millis(10));
+        for millis in &[20, 3000, 50, 50, 30] {
+            assert!(wheel.reschedule("a", Duration::from_millis(*millis)));
+        }
+        assert!(!wheel.reschedule("a", Duration::from_micros(10)));
+        assert_eq!(2, entries(&wheel));
+        wheel.expire();
+        assert_eq!(vec!["b"], wheel.expire());
+        wheel.expire();
+        assert_eq!(vec!["a"], wheel.expire());
+        assert_eq!(0, entries(&wheel));
+    }
+
+    #[test]
+    fn extend_by_pushes_deadlines_out() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        assert!(!wheel.extend_by("a", Duration::from_millis(10)));
+        wheel.start("a", Duration::from_millis(30));
+        wheel.expire();
+        assert!(wheel.extend_by("a", Duration::from_millis(25)));
+        assert_eq!(Some(Duration::from_millis(50)), wheel.remaining(&"a"));
+        assert!(wheel.extend_by("a", Duration::from_millis(5)));
+        for _ in 0..4 {
+            assert!(wheel.expire().is_empty());
+        }
+        assert_eq!(vec!["a"], wheel.expire());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn poll_timeout_waits_for_the_next_timer() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        let start = wheel.last_tick_at();
+        assert_eq!(None, wheel.poll_timeout(start));
+        wheel.start(7usize, Duration::from_millis(30));
+        wheel.start(8usize, Duration::from_secs(2));
+        assert_eq!(Some(Duration::from_millis(35)),
+                   wheel.poll_timeout(start + Duration::from_millis(5)));
+        let now = start + Duration::from_millis(40);
+        assert_eq!(Some(Duration::from_secs(0)), wheel.poll_timeout(now));
+        assert_eq!(vec![7], wheel.expire_until(now));
+        assert_eq!(Some(Duration::from_millis(2960)), wheel.poll_timeout(now));
+    }
+
+    #[test]
+    fn cancel_returns_remaining_time() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(50));
+        wheel.expire();
+        wheel.expire();
+        assert_eq!(Some(Duration::from_millis(40)), wheel.cancel(&"a"));
+        assert_eq!(None, wheel.cancel(&"a"));
+        for _ in 0..6 {
+            assert_eq!(0, wheel.expire().len());
+        }
+    }
+
+    #[test]
+    fn restart_ignores_stale_slot_entry() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(20));
+        wheel.stop("a");
+        wheel.start("a", Duration::from_millis(50));
+        wheel.start("b", Duration::from_millis(50));
+        wheel.start("b", Duration::from_millis(20));
+        for i in 1..7 {
+            let expired = wheel.expire();
+            match i {
+                3 => assert_eq!(vec!["b"], expired),
+                6 => assert_eq!(vec!["a"], expired),
+                _ => assert_eq!(0, expired.len())
+            }
+        }
+    }
+
+    #[test]
+    fn into_iter_yields_remaining_time() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(50));
+        wheel.start("b", Duration::from_secs(3));
+        wheel.start("c", Duration::from_millis(50));
+        wheel.stop("c");
+        wheel.expire();
+        let mut remaining: Vec<_> = wheel.into_iter().collect();
+        remaining.sort();
+        assert_eq!(vec![("a", Duration::from_millis(50)), ("b", Duration::from_millis(3990))],
+                   remaining);
+    }
+
+    #[test]
+    fn precise_timers_catch_up_with_advance() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.set_precise(true);
+        wheel.start("a", Duration::from_millis(1300));
+        wheel.start_interval("b", Duration::from_millis(250));
+        assert_eq!(vec!["b"], wheel.advance(100));
+        assert_eq!(Some(Duration::from_millis(310)), wheel.remaining(&"a"));
+
+        let mut fired = Vec::new();
+        for tick in 101..160 {
+            for key in wheel.expire() {
+                fired.push((key, tick));
+            }
+        }
+        assert_eq!(vec![("b", 126), ("a", 131), ("b", 152)], fired);
+        let wheel = wheel.into_copy();
+        assert_eq!(Some(Duration::from_millis(190)), wheel.remaining(&"b"));
+    }
+
+    fn entries(wheel: &AllocWheel<u64>) -> usize {
+        let slots: usize = wheel.wheels.iter()
+            .flat_map(|wheel| wheel.slots.iter())
+            .map(|slot| slot.entries.len())
+            .sum();
+        slots + wheel.overflow.values().map(Vec::len).sum::<usize>()
+    }
+
+    #[test]
+    fn compaction_drops_stale_entries() {
+        let mut wheel = AllocWheel::new(vec![Resolution::Sec, Resolution::Min, Resolution::Hour]);
+        for key in 0..10 {
+            wheel.start(key, Duration::from_secs(7200));
+        }
+        wheel.start(20, Duration::from_secs(2 * 86_400));
+        assert!(wheel.restart(0, Duration::from_secs(3)));
+        for key in 5..10 {
+            wheel.cancel(&key);
+        }
+        wheel.cancel(&20);
+        assert_eq!(12, entries(&wheel));
+        assert_eq!(7, wheel.compact());
+        assert_eq!(5, entries(&wheel));
+        for _ in 0..4 {
+            wheel.expire();
+        }
+        assert!(!wheel.contains(&0) && wheel.contains(&1));
+
+        // Cancel heavy workloads compact on their own
+        for key in 100..5000 {
+            wheel.start(key, Duration::from_secs(3600));
+            wheel.cancel(&key);
+        }
+        assert!(entries(&wheel) <= 1024 + 4, "{} entries left", entries(&wheel));
+        assert_eq!(4, wheel.len());
+    }
+
+    #[test]
+    fn long_interval_timers_wait_in_the_overflow_list() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.set_precise(true);
+        wheel.start_interval("a", Duration::from_secs(90));
+        assert!(wheel.advance(9000).is_empty());
+        assert_eq!(vec!["a"], wheel.expire());
+        assert_eq!(Some(Duration::from_millis(90_010)), wheel.remaining(&"a"));
+        let wheel = wheel.into_copy();
+        assert_eq!(Some(9001), wheel.ticks_until_next_expiry());
+    }
+
+    #[test]
+    fn overflow_timers_due_on_the_next_tick_still_fire() {
+        let mut wheel = AllocWheel::default();
+        wheel.start("a", Duration::from_secs(25 * 3600));
+        let ticks = wheel.ticks_until_next_expiry().unwrap();
+        assert!(wheel.advance(ticks - 1).is_empty());
+        assert_eq!(vec!["a"], wheel.expire());
+        assert!(wheel.is_empty());
+    }
+
     fn verify_wheel_and_slot_position(wheel: &mut AllocWheel<&'static str>) {
         let (_, _, keys) = values();
         let expected_slots = [6, 4, 2, 6, 6, 6];
         for i in 0..wheel.wheels.len() {
             for j in 0..wheel.wheels[i].slots.len() {
-                let ref entries = wheel.wheels[i].slots[j].entries;
+                let entries = &wheel.wheels[i].slots[j].entries;
                 if j == expected_slots[i] {
                     assert_eq!(1, entries.len());
                     let entry = Weak::upgrade(&entries[0].clone()).unwrap();
@@ -248,7 +1648,7 @@ mod tests {
             if expected_ticks.contains(&i) {
                 assert_eq!(1, expired.len());
                 assert_eq!(keys[match_count], expired[0]);
-                match_count = match_count + 1;
+                match_count += 1;
             } else  {
                 assert_eq!(0, expired.len());
             }
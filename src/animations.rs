@@ -0,0 +1,208 @@
+use std::hash::Hash;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Duration;
+use super::{CopyWheel, Wheel, Resolution};
+
+/// An event reported by `Animations::expire`
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum AnimationEvent<E, A> {
+    /// A stage other than the last completed. `stage` is the index of the completed stage.
+    Stage { entity: E, animation: A, stage: usize },
+    /// The last stage completed and the animation is no longer playing
+    Finished { entity: E, animation: A }
+}
+
+struct Playing {
+    stages: Vec<Duration>,
+    stage: usize,
+    generation: u64
+}
+
+/// Stage and end timers for per-entity animations.
+///
+/// An animation is a sequence of stage durations. A `Stage` event is reported as each stage
+/// completes, followed by `Finished` after the last one. Stages shorter than a tick are rounded up
+/// to a tick. Entity ids are usually small `Copy` values, which makes a `CopyWheel` the natural
+/// backing store.
+///
+/// Every animation belongs to an entity, so `despawn` cancels all of an entity's animations at
+/// once without the caller tracking what is playing.
+pub struct Animations<E: Eq + Hash + Debug + Clone, A: Eq + Hash + Debug + Clone> {
+    playing: HashMap<E, HashMap<A, Playing>>,
+    wheel: CopyWheel<(E, A, u64)>,
+    next_generation: u64
+}
+
+impl<E, A> Animations<E, A>
+    where E: Eq + Hash + Debug + Clone,
+          A: Eq + Hash + Debug + Clone
+{
+    pub fn new(resolutions: Vec<Resolution>) -> Animations<E, A> {
+        Animations {
+            playing: HashMap::new(),
+            wheel: CopyWheel::new(resolutions),
+            next_generation: 0
+        }
+    }
+
+    /// Play an animation made up of the given stages. Playing an animation that is already
+    /// playing on the entity restarts it from the first stage.
+    pub fn play(&mut self, entity: E, animation: A, stages: Vec<Duration>) {
+        assert!(!stages.is_empty());
+        self.cancel(&entity, &animation);
+        let generation = self.arm(entity.clone(), animation.clone(), stages[0]);
+        self.playing.entry(entity).or_default().insert(animation, Playing {
+            stages,
+            stage: 0,
+            generation
+        });
+    }
+
+    /// Play a single stage animation
+    pub fn play_once(&mut self, entity: E, animation: A, duration: Duration) {
+        self.play(entity, animation, vec![duration]);
+    }
+
+    /// Stop an animation. Returns false if it wasn't playing.
+    pub fn cancel(&mut self, entity: &E, animation: &A) -> bool {
+        let (playing, now_idle) = match self.playing.get_mut(entity) {
+            Some(animations) => (animations.remove(animation), animations.is_empty()),
+            None => return false
+        };
+        if now_idle {
+            self.playing.remove(entity);
+        }
+        match playing {
+            Some(playing) => {
+                self.wheel.stop((entity.clone(), animation.clone(), playing.generation));
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Stop every animation playing on an entity. Returns the number of animations stopped.
+    pub fn despawn(&mut self, entity: &E) -> usize {
+        match self.playing.remove(entity) {
+            Some(animations) => {
+                let count = animations.len();
+                for (animation, playing) in animations {
+                    self.wheel.stop((entity.clone(), animation, playing.generation));
+                }
+                count
+            },
+            None => 0
+        }
+    }
+
+    pub fn is_playing(&self, entity: &E, animation: &A) -> bool {
+        self.stage(entity, animation).is_some()
+    }
+
+    /// The index of the stage currently playing
+    pub fn stage(&self, entity: &E, animation: &A) -> Option<usize> {
+        self.playing.get(entity)
+            .and_then(|animations| animations.get(animation))
+            .map(|playing| playing.stage)
+    }
+
+    /// Advance the wheel by one tick and return any completed stages and animations
+    pub fn expire(&mut self) -> Vec<AnimationEvent<E, A>> {
+        let mut events = Vec::new();
+        for (entity, animation, generation) in self.wheel.expire() {
+            let next = match self.playing.get_mut(&entity).and_then(|a| a.get_mut(&animation)) {
+                Some(playing) if playing.generation == generation => {
+                    let completed = playing.stage;
+                    playing.stage += 1;
+                    playing.stages.get(playing.stage).cloned().map(|duration| (completed, duration))
+                },
+                _ => continue
+            };
+            match next {
+                Some((stage, duration)) => {
+                    let generation = self.arm(entity.clone(), animation.clone(), duration);
+                    self.playing.get_mut(&entity).unwrap().get_mut(&animation).unwrap().generation =
+                        generation;
+                    events.push(AnimationEvent::Stage { entity, animation, stage });
+                },
+                None => {
+                    self.cancel(&entity, &animation);
+                    events.push(AnimationEvent::Finished { entity, animation });
+                }
+            }
+        }
+        events
+    }
+
+    fn arm(&mut self, entity: E, animation: A, duration: Duration) -> u64 {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        let duration = duration.max(self.wheel.tick_duration());
+        self.wheel.start((entity, animation, generation), duration);
+        generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    fn run(animations: &mut Animations<u32, &'static str>, ticks: usize)
+        -> Vec<AnimationEvent<u32, &'static str>>
+    {
+        let mut events = Vec::new();
+        for _ in 0..ticks {
+            events.extend(animations.expire());
+        }
+        events
+    }
+
+    #[test]
+    fn stages_then_finish() {
+        let mut animations = Animations::new(vec![Resolution::TenMs, Resolution::Sec]);
+        animations.play(1, "attack", vec![Duration::from_millis(20), Duration::from_millis(30)]);
+        assert_eq!(Some(0), animations.stage(&1, &"attack"));
+        assert_eq!(vec![AnimationEvent::Stage { entity: 1, animation: "attack", stage: 0 }],
+                   run(&mut animations, 3));
+        assert_eq!(Some(1), animations.stage(&1, &"attack"));
+        assert_eq!(vec![AnimationEvent::Finished { entity: 1, animation: "attack" }],
+                   run(&mut animations, 4));
+        assert!(!animations.is_playing(&1, &"attack"));
+    }
+
+    #[test]
+    fn stages_shorter_than_a_tick_still_complete() {
+        let mut animations = Animations::new(vec![Resolution::TenMs, Resolution::Sec]);
+        animations.play(1, "flash", vec![Duration::from_millis(5), Duration::from_millis(5)]);
+        assert_eq!(vec![AnimationEvent::Stage { entity: 1, animation: "flash", stage: 0 },
+                        AnimationEvent::Finished { entity: 1, animation: "flash" }],
+                   run(&mut animations, 4));
+        assert!(!animations.is_playing(&1, &"flash"));
+    }
+
+    #[test]
+    fn despawn_cancels_all_animations() {
+        let mut animations = Animations::new(vec![Resolution::TenMs, Resolution::Sec]);
+        animations.play_once(1, "walk", Duration::from_millis(50));
+        animations.play_once(1, "blink", Duration::from_millis(50));
+        animations.play_once(2, "walk", Duration::from_millis(50));
+        assert_eq!(2, animations.despawn(&1));
+        assert_eq!(0, animations.despawn(&1));
+        assert_eq!(vec![AnimationEvent::Finished { entity: 2, animation: "walk" }],
+                   run(&mut animations, 10));
+    }
+
+    #[test]
+    fn replaying_restarts_animation() {
+        let mut animations = Animations::new(vec![Resolution::TenMs, Resolution::Sec]);
+        animations.play_once(1, "walk", Duration::from_millis(20));
+        run(&mut animations, 2);
+        animations.play_once(1, "walk", Duration::from_millis(20));
+        assert!(run(&mut animations, 2).is_empty());
+        assert_eq!(1, run(&mut animations, 1).len());
+        assert!(!animations.cancel(&1, &"walk"));
+    }
+}
@@ -0,0 +1,445 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+use alloc::vec::{Drain, Vec};
+#[cfg(feature = "alloc-wheel")]
+use super::AllocWheel;
+#[cfg(feature = "copy-wheel")]
+use super::CopyWheel;
+use super::{DuplicatePolicy, Resolution, StartError, Wheel, WheelKind};
+
+/// One of the wheel implementations, chosen at runtime.
+///
+/// This is an alternative to `Box<dyn Wheel<T>>` that avoids the allocation and dynamic dispatch,
+/// and still gives access to the inherent methods of the wheels. Build one from a `WheelKind`
+/// with `WheelBuilder::build_any`, or convert from a wheel with `From`.
+///
+/// Only the hierarchical wheels with the full set of inherent methods are covered. `HashedWheel`
+/// and `IntrusiveWheel` lack most of them, such as `pause`, `try_start` and precise timers, so use
+/// them through `Box<dyn Wheel<T, Expired = Vec<T>>>` instead.
+pub enum AnyWheel<T: Eq + Hash + Debug + Clone> {
+    #[cfg(feature = "copy-wheel")]
+    Copy(CopyWheel<T>),
+    #[cfg(feature = "alloc-wheel")]
+    Alloc(AllocWheel<T>)
+}
+
+impl<T: Eq + Hash + Debug + Clone> AnyWheel<T> {
+    pub fn kind(&self) -> WheelKind {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(_) => WheelKind::Copy,
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(_) => WheelKind::Alloc
+        }
+    }
+
+    /// The number of running timers
+    pub fn len(&self) -> usize {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref wheel) => wheel.len(),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref wheel) => wheel.len()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether a timer is running for `key`
+    pub fn contains(&self, key: &T) -> bool {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref wheel) => wheel.contains(key),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref wheel) => wheel.contains(key)
+        }
+    }
+
+    /// The time left until the timer for `key` expires, or `None` if it isn't running
+    pub fn remaining(&self, key: &T) -> Option<Duration> {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref wheel) => wheel.remaining(key),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref wheel) => wheel.remaining(key)
+        }
+    }
+
+    /// The number of calls to `expire` until the next timer expires. See
+    /// `CopyWheel::ticks_until_next_expiry`.
+    pub fn ticks_until_next_expiry(&self) -> Option<u64> {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref wheel) => wheel.ticks_until_next_expiry(),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref wheel) => wheel.ticks_until_next_expiry()
+        }
+    }
+
+    /// The time until the next timer expires, or `None` if no timers are running
+    pub fn next_expiry_in(&self) -> Option<Duration> {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref wheel) => wheel.next_expiry_in(),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref wheel) => wheel.next_expiry_in()
+        }
+    }
+
+    /// Move the wheel on by `ticks` at once. See `CopyWheel::advance`.
+    pub fn advance(&mut self, ticks: u64) -> Vec<T> {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.advance(ticks),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.advance(ticks)
+        }
+    }
+
+    /// Move the wheel on by the ticks that fit in `elapsed`. See `CopyWheel::expire_elapsed`.
+    pub fn expire_elapsed(&mut self, elapsed: Duration) -> Vec<T> {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.expire_elapsed(elapsed),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.expire_elapsed(elapsed)
+        }
+    }
+
+    /// Expire every tick that has passed by `now`. See `CopyWheel::expire_until`.
+    #[cfg(feature = "std")]
+    pub fn expire_until(&mut self, now: Instant) -> Vec<T> {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.expire_until(now),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.expire_until(now)
+        }
+    }
+
+    /// Expire every tick that has passed by `now`. See `CopyWheel::expire_at`.
+    #[cfg(feature = "std")]
+    pub fn expire_at(&mut self, now: Instant) -> Vec<T> {
+        self.expire_until(now)
+    }
+
+    /// Stop the wheel ticking until `resume` is called. See `CopyWheel::pause`.
+    pub fn pause(&mut self) {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.pause(),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.pause()
+        }
+    }
+
+    /// Start the wheel ticking again from where it was paused. See `CopyWheel::resume`.
+    pub fn resume(&mut self) {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.resume(),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.resume()
+        }
+    }
+
+    /// Start the wheel ticking again from `now`. See `CopyWheel::resume_at`.
+    #[cfg(feature = "std")]
+    pub fn resume_at(&mut self, now: Instant) {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.resume_at(now),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.resume_at(now)
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref wheel) => wheel.is_paused(),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref wheel) => wheel.is_paused()
+        }
+    }
+
+    /// How long an event loop can wait before the next timer is due. See
+    /// `CopyWheel::poll_timeout`.
+    #[cfg(feature = "std")]
+    pub fn poll_timeout(&self, now: Instant) -> Option<Duration> {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref wheel) => wheel.poll_timeout(now),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref wheel) => wheel.poll_timeout(now)
+        }
+    }
+
+    /// Start a timer that expires at `deadline`. See `CopyWheel::start_at`.
+    #[cfg(feature = "std")]
+    pub fn start_at(&mut self, key: T, deadline: Instant) {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.start_at(key, deadline),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.start_at(key, deadline)
+        }
+    }
+
+    /// Restart a running timer so that it expires at `deadline`. See `CopyWheel::restart_at`.
+    #[cfg(feature = "std")]
+    pub fn restart_at(&mut self, key: T, deadline: Instant) -> bool {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.restart_at(key, deadline),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.restart_at(key, deadline)
+        }
+    }
+
+    /// The number of running timers in the wheel for `resolution`. See `CopyWheel::len_at`.
+    pub fn len_at(&self, resolution: Resolution) -> usize {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref wheel) => wheel.len_at(resolution),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref wheel) => wheel.len_at(resolution)
+        }
+    }
+
+    /// Cancel a timer, returning the time that was left before it would have expired, or `None`
+    /// if it wasn't running
+    pub fn cancel(&mut self, key: &T) -> Option<Duration> {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.cancel(key),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.cancel(key)
+        }
+    }
+
+    /// Expire the timers due at the next tick into a buffer kept by the wheel. See
+    /// `CopyWheel::expire_iter`.
+    pub fn expire_iter(&mut self) -> Drain<'_, T> {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.expire_iter(),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.expire_iter()
+        }
+    }
+
+    /// Expire the timers due at the next tick, appending them to `expired`. See
+    /// `CopyWheel::expire_into`.
+    pub fn expire_into(&mut self, expired: &mut Vec<T>) {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.expire_into(expired),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.expire_into(expired)
+        }
+    }
+
+    /// Start a timer for each key and duration. See `CopyWheel::start_all`.
+    pub fn start_all<I: IntoIterator<Item = (T, Duration)>>(&mut self, timers: I) {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.start_all(timers),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.start_all(timers)
+        }
+    }
+
+    /// Cancel every timer
+    pub fn clear(&mut self) {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.clear(),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.clear()
+        }
+    }
+
+    /// Cancel every timer, returning each key with the time that was left. See
+    /// `CopyWheel::drain`.
+    pub fn drain(&mut self) -> Vec<(T, Duration)> {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.drain(),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.drain()
+        }
+    }
+
+    /// Keep only the timers whose keys `f` returns true for, and cancel the rest
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, f: F) {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.retain(f),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.retain(f)
+        }
+    }
+
+    /// Cancel the timers whose keys `f` returns true for, returning how many were cancelled
+    pub fn cancel_where<F: FnMut(&T) -> bool>(&mut self, f: F) -> usize {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.cancel_where(f),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.cancel_where(f)
+        }
+    }
+
+    /// What `start` does with a timer that is already running
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref wheel) => wheel.duplicate_policy(),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref wheel) => wheel.duplicate_policy()
+        }
+    }
+
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.set_duplicate_policy(policy),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.set_duplicate_policy(policy)
+        }
+    }
+
+    /// Move a running timer to a new deadline, removing its old slot entry. See
+    /// `CopyWheel::reschedule`.
+    pub fn reschedule(&mut self, key: T, time: Duration) -> bool {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.reschedule(key, time),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.reschedule(key, time)
+        }
+    }
+
+    /// Push a running timer's deadline out by `extra`. See `CopyWheel::extend_by`.
+    pub fn extend_by(&mut self, key: T, extra: Duration) -> bool {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.extend_by(key, extra),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.extend_by(key, extra)
+        }
+    }
+
+    /// Start a timer that isn't already running, without adjusting the duration to fit. See
+    /// `CopyWheel::try_start`.
+    pub fn try_start(&mut self, key: T, time: Duration) -> Result<(), StartError> {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.try_start(key, time),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.try_start(key, time)
+        }
+    }
+}
+
+#[cfg(feature = "copy-wheel")]
+impl<T: Eq + Hash + Debug + Clone> From<CopyWheel<T>> for AnyWheel<T> {
+    fn from(wheel: CopyWheel<T>) -> AnyWheel<T> {
+        AnyWheel::Copy(wheel)
+    }
+}
+
+#[cfg(feature = "alloc-wheel")]
+impl<T: Eq + Hash + Debug + Clone> From<AllocWheel<T>> for AnyWheel<T> {
+    fn from(wheel: AllocWheel<T>) -> AnyWheel<T> {
+        AnyWheel::Alloc(wheel)
+    }
+}
+
+impl<T: Eq + Hash + Debug + Clone> Wheel<T> for AnyWheel<T> {
+    type Expired = Vec<T>;
+
+    fn start(&mut self, key: T, time: Duration) {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.start(key, time),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.start(key, time)
+        }
+    }
+
+    fn stop(&mut self, key: T) -> Option<Duration> {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.stop(key),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.stop(key)
+        }
+    }
+
+    fn expire(&mut self) -> Vec<T> {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.expire(),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.expire()
+        }
+    }
+
+    fn start_with_delay(&mut self, key: T, delay: Duration, period: Duration) {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.start_with_delay(key, delay, period),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.start_with_delay(key, delay, period)
+        }
+    }
+
+    fn restart(&mut self, key: T, time: Duration) -> bool {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref mut wheel) => wheel.restart(key, time),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref mut wheel) => wheel.restart(key, time)
+        }
+    }
+
+    fn tick_duration(&self) -> Duration {
+        match *self {
+            #[cfg(feature = "copy-wheel")]
+            AnyWheel::Copy(ref wheel) => wheel.tick_duration(),
+            #[cfg(feature = "alloc-wheel")]
+            AnyWheel::Alloc(ref wheel) => wheel.tick_duration()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{Resolution, Wheel, WheelBuilder, WheelKind};
+
+    #[test]
+    fn any_wheels_delegate() {
+        for kind in [WheelKind::Copy, WheelKind::Alloc].iter() {
+            let mut wheel = WheelBuilder::new()
+                .resolutions(vec![Resolution::TenMs, Resolution::Sec])
+                .build_any(*kind);
+            assert_eq!(*kind, wheel.kind());
+            wheel.start("a", Duration::from_millis(10));
+            wheel.start("b", Duration::from_millis(10));
+            assert_eq!(Some(Duration::from_millis(20)), wheel.cancel(&"b"));
+            assert!(wheel.expire().is_empty());
+            assert_eq!(vec!["a"], wheel.expire());
+        }
+        let wheel: AnyWheel<u64> = CopyWheel::new(vec![Resolution::Sec]).into();
+        assert_eq!(WheelKind::Copy, wheel.kind());
+    }
+}
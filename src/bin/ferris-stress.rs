@@ -0,0 +1,206 @@
+//! Run a timer workload against a wheel in real time and report how accurately and how quickly it
+//! was handled.
+//!
+//! ```text
+//! ferris-stress [--kind copy|alloc] [--seconds N] [--rate TIMERS_PER_SEC]
+//!               [--min-ms N] [--max-ms N] [--cancel-ratio F] [--jitter-ms N] [--seed N]
+//! ```
+//!
+//! The wheel ticks every 10ms. Each tick starts new timers with durations spread uniformly between
+//! `--min-ms` and `--max-ms`, cancels a fraction of them before they expire, and sleeps for up to
+//! `--jitter-ms` extra to simulate a loaded event loop. Lateness is measured from the requested
+//! deadline to the tick on which the timer expired, and throughput counts the starts, stops and
+//! expiries done per second of time spent working on ticks.
+
+extern crate ferris;
+
+use std::collections::HashMap;
+use std::env;
+use std::process;
+use std::thread;
+use std::time::{Duration, Instant};
+use ferris::{Resolution, Wheel, WheelBuilder, WheelKind};
+
+struct Config {
+    kind: WheelKind,
+    seconds: u64,
+    rate: u64,
+    min_ms: u64,
+    max_ms: u64,
+    cancel_ratio: f64,
+    jitter_ms: u64,
+    seed: u64
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            kind: WheelKind::Copy,
+            seconds: 10,
+            rate: 10_000,
+            min_ms: 20,
+            max_ms: 5_000,
+            cancel_ratio: 0.5,
+            jitter_ms: 0,
+            seed: 1
+        }
+    }
+}
+
+fn usage(message: &str) -> ! {
+    eprintln!("ferris-stress: {}", message);
+    eprintln!("usage: ferris-stress [--kind copy|alloc] [--seconds N] [--rate TIMERS_PER_SEC] \
+               [--min-ms N] [--max-ms N] [--cancel-ratio F] [--jitter-ms N] [--seed N]");
+    process::exit(2);
+}
+
+fn parse_args() -> Config {
+    let mut config = Config::default();
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let value = args.next().unwrap_or_else(|| usage(&format!("missing value for {}", flag)));
+        let number = || {
+            value.parse::<u64>().unwrap_or_else(|_| usage(&format!("bad value for {}", flag)))
+        };
+        match &flag[..] {
+            "--kind" => config.kind = match &value[..] {
+                "copy" => WheelKind::Copy,
+                "alloc" => WheelKind::Alloc,
+                _ => usage("--kind must be copy or alloc")
+            },
+            "--seconds" => config.seconds = number(),
+            "--rate" => config.rate = number(),
+            "--min-ms" => config.min_ms = number(),
+            "--max-ms" => config.max_ms = number(),
+            "--jitter-ms" => config.jitter_ms = number(),
+            "--seed" => config.seed = number(),
+            "--cancel-ratio" => {
+                config.cancel_ratio = value.parse()
+                    .unwrap_or_else(|_| usage("--cancel-ratio must be a number"));
+            },
+            _ => usage(&format!("unknown flag {}", flag))
+        }
+    }
+    if config.min_ms > config.max_ms || config.max_ms >= 24 * 3600 * 1000 {
+        usage("durations must satisfy min-ms <= max-ms < 1 day");
+    }
+    config
+}
+
+/// xorshift64*, which is plenty for generating a workload
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 { 0 } else { self.next_u64() % bound }
+    }
+
+    fn chance(&mut self, probability: f64) -> bool {
+        ((self.next_u64() >> 11) as f64 / (1u64 << 53) as f64) < probability
+    }
+}
+
+fn main() {
+    let config = parse_args();
+    let tick = Duration::from_millis(10);
+    let mut wheel = WheelBuilder::new()
+        .resolutions(vec![Resolution::TenMs, Resolution::HundredMs, Resolution::Sec,
+                          Resolution::Min, Resolution::Hour])
+        .build_any::<u64>(config.kind);
+    let mut rng = Rng(config.seed.max(1));
+
+    // The deadline requested for each running timer
+    let mut deadlines: HashMap<u64, Instant> = HashMap::new();
+    // The keys to cancel on each tick
+    let mut cancels: HashMap<u64, Vec<u64>> = HashMap::new();
+    let mut next_key = 0u64;
+    let mut started = 0u64;
+    let mut cancelled = 0u64;
+    let mut lateness: Vec<Duration> = Vec::new();
+    let mut early = 0u64;
+    let mut busy = Duration::from_secs(0);
+
+    let per_tick = config.rate as f64 / 100.0;
+    let mut owed = 0.0;
+    let ticks = config.seconds * 100;
+    let start = Instant::now();
+    for n in 1..ticks + 1 {
+        let now = Instant::now();
+
+        owed += per_tick;
+        while owed >= 1.0 {
+            owed -= 1.0;
+            let millis = config.min_ms + rng.below(config.max_ms - config.min_ms + 1);
+            let time = Duration::from_millis(millis);
+            wheel.start(next_key, time);
+            deadlines.insert(next_key, now + time);
+            if rng.chance(config.cancel_ratio) {
+                // Cancel somewhere before the deadline, like a request that got its response
+                let at = n + rng.below(millis / 10);
+                cancels.entry(at).or_default().push(next_key);
+            }
+            next_key += 1;
+            started += 1;
+        }
+
+        for key in cancels.remove(&n).unwrap_or_default() {
+            if deadlines.remove(&key).is_some() {
+                wheel.stop(key);
+                cancelled += 1;
+            }
+        }
+
+        let expired = wheel.expire();
+        let fired = Instant::now();
+        busy += fired - now;
+        for key in expired {
+            if let Some(deadline) = deadlines.remove(&key) {
+                if fired >= deadline {
+                    lateness.push(fired - deadline);
+                } else {
+                    early += 1;
+                    lateness.push(Duration::from_secs(0));
+                }
+            }
+        }
+
+        if config.jitter_ms > 0 {
+            thread::sleep(Duration::from_millis(rng.below(config.jitter_ms + 1)));
+        }
+        let next = start + tick * n as u32;
+        let now = Instant::now();
+        if next > now {
+            thread::sleep(next - now);
+        }
+    }
+    let elapsed = start.elapsed();
+
+    lateness.sort();
+    let percentile = |p: f64| -> Duration {
+        if lateness.is_empty() {
+            return Duration::from_secs(0);
+        }
+        lateness[((lateness.len() - 1) as f64 * p) as usize]
+    };
+    let operations = started + cancelled + ticks;
+    println!("wheel:        {:?}", config.kind);
+    println!("elapsed:      {:?} over {} ticks", elapsed, ticks);
+    println!("started:      {}", started);
+    println!("cancelled:    {}", cancelled);
+    println!("expired:      {} ({} early)", lateness.len(), early);
+    println!("pending:      {}", deadlines.len());
+    println!("lateness p50: {:?}", percentile(0.5));
+    println!("lateness p99: {:?}", percentile(0.99));
+    println!("lateness max: {:?}", percentile(1.0));
+    println!("tick work:    {:?} ({:.1}% of elapsed)",
+             busy, 100.0 * busy.as_secs_f64() / elapsed.as_secs_f64());
+    println!("throughput:   {:.0} operations per second of tick work",
+             operations as f64 / busy.as_secs_f64().max(1e-9));
+}
@@ -0,0 +1,123 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::time::Duration;
+use alloc::vec::Vec;
+use super::{FixedStep, Wheel};
+
+/// Drives a wheel from browser timestamps.
+///
+/// There is no clock to read on `wasm32-unknown-unknown`, so the driver is fed the timestamp passed
+/// to a `requestAnimationFrame` callback, or `performance.now()` from a `setInterval` callback, in
+/// milliseconds. It expires the wheel once for every whole tick since the previous call, using a
+/// `FixedStep` so fractional milliseconds carry over between frames.
+///
+/// The glue on the JavaScript side is left to the application, for example with `wasm-bindgen`:
+///
+/// ```ignore
+/// #[wasm_bindgen]
+/// impl Simulation {
+///     pub fn frame(&mut self, timestamp: f64) {
+///         for key in self.driver.on_frame(&mut self.wheel, timestamp) {
+///             self.handle_timeout(key);
+///         }
+///     }
+/// }
+/// ```
+///
+/// Disable the crate's `std` feature for this target, as in
+/// `default-features = false, features = ["browser", "copy-wheel"]`. With `std` enabled the
+/// wheels record an `Instant` when they are created, and `Instant::now` panics on
+/// `wasm32-unknown-unknown`. Nothing in a `no_std` build reads a clock, so the timestamps given to
+/// the driver are the only time source.
+///
+/// Browsers stop calling `requestAnimationFrame` in background tabs. Use `with_max_ticks` to bound
+/// the catch up work when the tab becomes visible again, or call `reset` to skip it entirely.
+#[derive(Debug, Clone)]
+pub struct BrowserDriver {
+    step: FixedStep,
+    last: Option<f64>
+}
+
+impl BrowserDriver {
+    /// The tick must match the maximum resolution of the wheel being driven
+    pub fn new(tick: Duration) -> BrowserDriver {
+        BrowserDriver {
+            step: FixedStep::new(tick),
+            last: None
+        }
+    }
+
+    /// Run at most `max_ticks` per frame, discarding any time beyond that
+    pub fn with_max_ticks(tick: Duration, max_ticks: u64) -> BrowserDriver {
+        BrowserDriver {
+            step: FixedStep::with_max_ticks(tick, max_ticks),
+            last: None
+        }
+    }
+
+    /// Expire the wheel for the time elapsed since the last frame, given a timestamp in
+    /// milliseconds. The first call only records the timestamp.
+    ///
+    /// Timestamps that go backwards, such as when switching between time sources, count as no
+    /// time elapsed.
+    pub fn on_frame<T, W>(&mut self, wheel: &mut W, timestamp: f64) -> Vec<T>
+        where T: Eq + Hash + Debug + Clone,
+              W: Wheel<T> + ?Sized
+    {
+        let elapsed = match self.last {
+            Some(last) if timestamp <= last => return Vec::new(),
+            Some(last) => millis_to_duration(timestamp - last),
+            None => Duration::from_secs(0)
+        };
+        self.last = Some(timestamp);
+        self.step.update(wheel, elapsed)
+    }
+
+    /// Forget the last timestamp, so that the next frame doesn't expire anything
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    /// The fraction of a tick accumulated towards the next tick, for interpolating rendered state
+    pub fn alpha(&self) -> f64 {
+        self.step.alpha()
+    }
+}
+
+fn millis_to_duration(millis: f64) -> Duration {
+    Duration::from_nanos((millis * 1_000_000.0) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{CopyWheel, Resolution, Wheel};
+
+    #[test]
+    fn frames_expire_elapsed_ticks() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(50));
+        let mut driver = BrowserDriver::new(Duration::from_millis(10));
+        assert!(driver.on_frame(&mut wheel, 1000.0).is_empty());
+        // Frames at 60Hz with fractional timestamps
+        assert!(driver.on_frame(&mut wheel, 1016.667).is_empty());
+        assert!(driver.on_frame(&mut wheel, 1033.333).is_empty());
+        // Going backwards doesn't move time
+        assert!(driver.on_frame(&mut wheel, 1020.0).is_empty());
+        assert_eq!(vec!["a"], driver.on_frame(&mut wheel, 1070.0));
+    }
+
+    #[test]
+    fn reset_skips_hidden_time() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(10));
+        let mut driver = BrowserDriver::with_max_ticks(Duration::from_millis(10), 1);
+        driver.on_frame(&mut wheel, 0.0);
+        driver.reset();
+        assert!(driver.on_frame(&mut wheel, 60_000.0).is_empty());
+        assert!(driver.on_frame(&mut wheel, 60_010.0).is_empty());
+        // Only one tick runs even though 50ms passed
+        assert_eq!(vec!["a"], driver.on_frame(&mut wheel, 60_060.0));
+    }
+}
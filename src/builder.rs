@@ -0,0 +1,291 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::time::Duration;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+#[cfg(feature = "alloc-wheel")]
+use super::AllocWheel;
+#[cfg(feature = "copy-wheel")]
+use super::CopyWheel;
+use super::{AnyWheel, DuplicatePolicy, FerrisError, Resolution, Rounding, TickPolicy, Wheel};
+use super::{check_resolutions, sized_wheels};
+
+/// The wheel implementations that can be selected at runtime with `WheelBuilder::build_boxed` or
+/// `WheelBuilder::build_any`.
+///
+/// Only the variants for wheels enabled by crate features exist. `HashedWheel` and
+/// `IntrusiveWheel` aren't among them, since the builder's settings don't apply to them. Both
+/// implement `Wheel`, so box them alongside built wheels to choose between them at runtime.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum WheelKind {
+    #[cfg(feature = "copy-wheel")]
+    Copy,
+    #[cfg(feature = "alloc-wheel")]
+    Alloc
+}
+
+/// A builder for configuring and constructing wheels.
+///
+/// ```
+/// use ferris::{WheelBuilder, Resolution, CopyWheel};
+///
+/// let wheel: CopyWheel<u64> = WheelBuilder::new()
+///     .resolution(Resolution::Ms)
+///     .resolution(Resolution::Sec)
+///     .capacity(100_000)
+///     .build_copy();
+/// ```
+///
+/// At least one resolution must be added before building.
+#[derive(Debug, Clone, Default)]
+pub struct WheelBuilder {
+    resolutions: Vec<Resolution>,
+    slots: Vec<(Resolution, usize)>,
+    capacity: usize,
+    duplicate_policy: DuplicatePolicy,
+    precise: bool,
+    slack: Duration,
+    max_timers: Option<usize>,
+    rounding: Rounding,
+    tick_policy: TickPolicy
+}
+
+impl WheelBuilder {
+    pub fn new() -> WheelBuilder {
+        WheelBuilder::default()
+    }
+
+    /// Add a wheel at the given resolution to the hierarchy. Order and duplicates don't matter.
+    pub fn resolution(mut self, resolution: Resolution) -> WheelBuilder {
+        self.resolutions.push(resolution);
+        self
+    }
+
+    /// Add wheels at each of the given resolutions to the hierarchy
+    pub fn resolutions<I>(mut self, resolutions: I) -> WheelBuilder
+        where I: IntoIterator<Item = Resolution>
+    {
+        self.resolutions.extend(resolutions);
+        self
+    }
+
+    /// Give the wheel at `resolution` a number of slots other than its default, adding it to the
+    /// hierarchy if needed. Only the coarsest wheel can change. See `CopyWheel::with_sizes`.
+    pub fn slots(mut self, resolution: Resolution, slots: usize) -> WheelBuilder {
+        self.resolutions.push(resolution);
+        self.slots.push((resolution, slots));
+        self
+    }
+
+    /// The number of timers to reserve space for up front
+    pub fn capacity(mut self, capacity: usize) -> WheelBuilder {
+        self.capacity = capacity;
+        self
+    }
+
+    /// What `start` does with a timer that is already running
+    pub fn duplicate_policy(mut self, policy: DuplicatePolicy) -> WheelBuilder {
+        self.duplicate_policy = policy;
+        self
+    }
+
+    /// Whether timers cascade down to finer wheels to expire precisely. See
+    /// `CopyWheel::set_precise`.
+    pub fn precise(mut self, precise: bool) -> WheelBuilder {
+        self.precise = precise;
+        self
+    }
+
+    /// How late timers may expire so that those due close together fire on the same tick. See
+    /// `CopyWheel::set_slack`.
+    pub fn slack(mut self, slack: Duration) -> WheelBuilder {
+        self.slack = slack;
+        self
+    }
+
+    /// The most timers the wheel may hold at once. See `CopyWheel::set_max_timers`.
+    pub fn max_timers(mut self, max: usize) -> WheelBuilder {
+        self.max_timers = Some(max);
+        self
+    }
+
+    /// How durations are rounded to the slots of the wheel they land in. See
+    /// `CopyWheel::set_rounding`.
+    pub fn rounding(mut self, rounding: Rounding) -> WheelBuilder {
+        self.rounding = rounding;
+        self
+    }
+
+    /// Whether timers may expire late or early. See
+    /// `CopyWheel::set_tick_policy`.
+    pub fn tick_policy(mut self, policy: TickPolicy) -> WheelBuilder {
+        self.tick_policy = policy;
+        self
+    }
+
+    #[cfg(feature = "copy-wheel")]
+    pub fn build_copy<T: Eq + Hash + Debug + Clone>(self) -> CopyWheel<T> {
+        let (resolutions, sizes) = self.sized_wheels();
+        let mut wheel = CopyWheel::from_sizes(resolutions, sizes, self.capacity);
+        wheel.set_duplicate_policy(self.duplicate_policy);
+        wheel.set_precise(self.precise);
+        wheel.set_slack(self.slack);
+        wheel.set_max_timers(self.max_timers);
+        wheel.set_rounding(self.rounding);
+        wheel.set_tick_policy(self.tick_policy);
+        wheel
+    }
+
+    #[cfg(feature = "alloc-wheel")]
+    pub fn build_alloc<T: Eq + Hash + Debug + Clone>(self) -> AllocWheel<T> {
+        let (resolutions, sizes) = self.sized_wheels();
+        let mut wheel = AllocWheel::from_sizes(resolutions, sizes, self.capacity);
+        wheel.set_duplicate_policy(self.duplicate_policy);
+        wheel.set_precise(self.precise);
+        wheel.set_slack(self.slack);
+        wheel.set_max_timers(self.max_timers);
+        wheel.set_rounding(self.rounding);
+        wheel.set_tick_policy(self.tick_policy);
+        wheel
+    }
+
+    /// Build a wheel of the given kind behind a trait object, for when the implementation is
+    /// chosen by configuration rather than at compile time
+    pub fn build_boxed<T>(self, kind: WheelKind) -> Box<dyn Wheel<T, Expired = Vec<T>>>
+        where T: Eq + Hash + Debug + Clone + 'static
+    {
+        match kind {
+            #[cfg(feature = "copy-wheel")]
+            WheelKind::Copy => Box::new(self.build_copy()),
+            #[cfg(feature = "alloc-wheel")]
+            WheelKind::Alloc => Box::new(self.build_alloc())
+        }
+    }
+
+    /// Build a wheel of the given kind, returning an error rather than panicking if the
+    /// configuration is invalid
+    pub fn try_build<T>(self, kind: WheelKind) -> Result<AnyWheel<T>, FerrisError>
+        where T: Eq + Hash + Debug + Clone
+    {
+        if self.resolutions.is_empty() {
+            return Err(FerrisError::InvalidConfig("at least one resolution is required"));
+        }
+        let mut resolutions = self.resolutions.clone();
+        resolutions.sort();
+        resolutions.dedup();
+        check_resolutions(&resolutions).map_err(FerrisError::InvalidConfig)?;
+        sized_wheels(resolutions, &self.slots).map_err(FerrisError::InvalidConfig)?;
+        Ok(self.build_any(kind))
+    }
+
+    fn sized_wheels(&self) -> (Vec<Resolution>, Vec<usize>) {
+        sized_wheels(self.resolutions.clone(), &self.slots).unwrap_or_else(|reason| panic!("{}", reason))
+    }
+
+    /// Build a wheel of the given kind as an `AnyWheel`, which dispatches without a trait object
+    pub fn build_any<T: Eq + Hash + Debug + Clone>(self, kind: WheelKind) -> AnyWheel<T> {
+        match kind {
+            #[cfg(feature = "copy-wheel")]
+            WheelKind::Copy => AnyWheel::Copy(self.build_copy()),
+            #[cfg(feature = "alloc-wheel")]
+            WheelKind::Alloc => AnyWheel::Alloc(self.build_alloc())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{Resolution, Wheel};
+
+    #[test]
+    fn built_wheels_expire() {
+        let builder = WheelBuilder::new()
+            .resolution(Resolution::Sec)
+            .resolutions(vec![Resolution::TenMs, Resolution::Sec])
+            .capacity(10);
+        let mut copy = builder.clone().build_copy();
+        let mut alloc = builder.build_alloc();
+        copy.start("a", Duration::from_millis(30));
+        alloc.start("a", Duration::from_millis(30));
+        for _ in 0..3 {
+            copy.expire();
+            alloc.expire();
+        }
+        assert_eq!(vec!["a"], copy.expire());
+        assert_eq!(vec!["a"], alloc.expire());
+    }
+
+    #[test]
+    fn boxed_wheels_expire() {
+        for kind in [WheelKind::Copy, WheelKind::Alloc].iter() {
+            let mut wheel = WheelBuilder::new()
+                .resolutions(vec![Resolution::TenMs, Resolution::Sec])
+                .build_boxed(*kind);
+            wheel.start("a", Duration::from_millis(10));
+            assert!(wheel.expire().is_empty());
+            assert_eq!(vec!["a"], wheel.expire());
+        }
+    }
+
+    #[test]
+    #[cfg(all(feature = "hashed-wheel", feature = "intrusive-wheel"))]
+    fn other_wheels_box_alongside_built_ones() {
+        let built = WheelBuilder::new()
+            .resolutions(vec![Resolution::TenMs, Resolution::Sec])
+            .build_boxed(WheelKind::Copy);
+        let hashed = Box::new(super::super::HashedWheel::new(Resolution::TenMs, 64));
+        let intrusive = Box::new(super::super::IntrusiveWheel::new(vec![Resolution::TenMs, Resolution::Sec]));
+        let wheels: Vec<Box<dyn Wheel<_, Expired = Vec<_>>>> = vec![built, hashed, intrusive];
+        for mut wheel in wheels {
+            wheel.start("a", Duration::from_millis(10));
+            assert!(wheel.expire().is_empty());
+            assert_eq!(vec!["a"], wheel.expire());
+        }
+    }
+
+    #[test]
+    fn try_build_checks_config() {
+        let result = WheelBuilder::new().try_build::<u64>(WheelKind::Copy);
+        assert_eq!(Some(FerrisError::InvalidConfig("at least one resolution is required")),
+                   result.err());
+        let wheel: AnyWheel<u64> = WheelBuilder::new()
+            .resolution(Resolution::Sec)
+            .try_build(WheelKind::Alloc)
+            .unwrap();
+        assert_eq!(WheelKind::Alloc, wheel.kind());
+        let result = WheelBuilder::new()
+            .resolutions(vec![Resolution::Custom(Duration::from_millis(16)), Resolution::Sec])
+            .try_build::<u64>(WheelKind::Copy);
+        assert_eq!(Some(FerrisError::InvalidConfig(
+                       "neighbouring resolutions must divide into a whole number of slots")),
+                   result.err());
+        let result = WheelBuilder::new()
+            .resolutions(vec![Resolution::Ms, Resolution::Min])
+            .try_build::<u64>(WheelKind::Copy);
+        assert_eq!(Some(FerrisError::InvalidConfig(
+                       "a resolution above a built in one finer than a second can't be over a second")),
+                   result.err());
+        let result = WheelBuilder::new()
+            .slots(Resolution::TenMs, 200)
+            .resolution(Resolution::Sec)
+            .try_build::<u64>(WheelKind::Copy);
+        assert_eq!(Some(FerrisError::InvalidConfig(
+                       "only the coarsest wheel can change its number of slots")),
+                   result.err());
+        let mut wheel: AnyWheel<u64> = WheelBuilder::new()
+            .resolution(Resolution::TenMs)
+            .slots(Resolution::Sec, 120)
+            .try_build(WheelKind::Copy)
+            .unwrap();
+        assert_eq!(Ok(()), wheel.try_start(1, Duration::from_secs(110)));
+        assert_eq!(1, wheel.len_at(Resolution::Sec));
+    }
+
+    #[test]
+    #[should_panic]
+    fn build_without_resolutions_panics() {
+        let _: CopyWheel<u64> = WheelBuilder::new().build_copy();
+    }
+}
@@ -0,0 +1,149 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use core::time::Duration;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use collections::HashMap;
+use super::Wheel;
+
+/// A callback run when its timer expires
+pub type Callback = Box<dyn FnOnce()>;
+
+/// A wheel that stores a callback with each timer, for callers that just want to run something
+/// later rather than route expired keys back to their handlers.
+///
+/// The keys are still used to restart and stop timers. `expire` runs the callbacks of the timers
+/// that expired, and `expire_callbacks` returns them instead, so they can be run after the wheel
+/// is released or on another queue.
+///
+/// Callbacks run at most once, so timers started on the inner wheel with `start_interval` or
+/// `start_with_delay` have no callback, and their expiries are ignored.
+pub struct CallbackWheel<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    wheel: W,
+    callbacks: HashMap<T, Callback>,
+    _key: PhantomData<T>
+}
+
+impl<T, W> CallbackWheel<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    pub fn new(wheel: W) -> CallbackWheel<T, W> {
+        CallbackWheel {
+            wheel,
+            callbacks: HashMap::new(),
+            _key: PhantomData
+        }
+    }
+
+    /// Start a timer that runs `callback` when it expires. Starting a running timer again restarts
+    /// it and replaces its callback.
+    ///
+    /// As with the wheels' `start`, durations shorter than a tick are ignored, and the callback is
+    /// dropped without being run.
+    pub fn start(&mut self, key: T, time: Duration, callback: Callback) {
+        if time < self.wheel.tick_duration() {
+            return;
+        }
+        self.wheel.start(key.clone(), time);
+        self.callbacks.insert(key, callback);
+    }
+
+    /// Restart a running timer, keeping its callback. See `Wheel::restart`.
+    pub fn restart(&mut self, key: T, time: Duration) -> bool {
+        self.callbacks.contains_key(&key) && self.wheel.restart(key, time)
+    }
+
+    /// Cancel a timer, returning its callback without running it
+    pub fn stop(&mut self, key: T) -> Option<Callback> {
+        let callback = self.callbacks.remove(&key)?;
+        self.wheel.stop(key);
+        Some(callback)
+    }
+
+    pub fn contains(&self, key: &T) -> bool {
+        self.callbacks.contains_key(key)
+    }
+
+    /// Advance the wheel by one tick and run the callbacks of the timers that expired, returning
+    /// how many ran
+    pub fn expire(&mut self) -> usize {
+        let callbacks = self.expire_callbacks();
+        let count = callbacks.len();
+        for callback in callbacks {
+            callback();
+        }
+        count
+    }
+
+    /// Advance the wheel by one tick and return the callbacks of the timers that expired, in the
+    /// order the wheel expired them
+    pub fn expire_callbacks(&mut self) -> Vec<Callback> {
+        let mut callbacks = Vec::new();
+        for key in self.wheel.expire() {
+            callbacks.extend(self.callbacks.remove(&key));
+        }
+        callbacks
+    }
+
+    /// The number of timers waiting to run their callbacks
+    pub fn len(&self) -> usize {
+        self.callbacks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.callbacks.is_empty()
+    }
+
+    /// How often `expire` must be called
+    pub fn tick_duration(&self) -> Duration {
+        self.wheel.tick_duration()
+    }
+
+    pub fn wheel(&self) -> &W {
+        &self.wheel
+    }
+
+    pub fn into_inner(self) -> W {
+        self.wheel
+    }
+}
+
+#[cfg(all(test, feature = "copy-wheel"))]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::Duration;
+    use super::super::{CopyWheel, Resolution};
+
+    #[test]
+    fn callbacks_run_when_their_timers_expire() {
+        let mut wheel = CallbackWheel::new(CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]));
+        let ran = Rc::new(RefCell::new(Vec::new()));
+        for key in 0..3 {
+            let ran = ran.clone();
+            wheel.start(key, Duration::from_millis(10), Box::new(move || ran.borrow_mut().push(key)));
+        }
+        let stopped = wheel.stop(1).unwrap();
+        assert!(wheel.restart(2, Duration::from_millis(20)));
+        wheel.start(3, Duration::from_micros(1), Box::new(|| panic!("too short to run")));
+        assert!(!wheel.contains(&3));
+
+        assert_eq!(0, wheel.expire());
+        assert_eq!(1, wheel.expire());
+        assert_eq!(vec![0], *ran.borrow());
+        let callbacks = wheel.expire_callbacks();
+        assert_eq!(1, callbacks.len());
+        for callback in callbacks {
+            callback();
+        }
+        stopped();
+        assert_eq!(vec![0, 2, 1], *ran.borrow());
+        assert!(wheel.is_empty());
+    }
+}
@@ -0,0 +1,145 @@
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// A source of monotonic time for the components that drive a wheel from the clock rather than
+/// being ticked by the caller, such as `run_blocking_with` and `Timer::drive_with`.
+///
+/// The wheels themselves only read the system clock once, when they're created, for the instant
+/// they count ticks from. Methods like `CopyWheel::expire_until` take the time as an argument, so
+/// a wheel is driven from a clock with `wheel.expire_until(clock.now())`, after `sync_clock` has
+/// moved its starting instant onto that clock.
+pub trait Clock {
+    fn now(&self) -> Instant;
+
+    /// Block the current thread until `deadline`, returning straight away if it has passed
+    fn sleep_until(&self, deadline: Instant) {
+        let now = self.now();
+        if deadline > now {
+            thread::sleep(deadline - now);
+        }
+    }
+}
+
+/// The clock of the operating system, as read by `Instant::now`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to, for testing timeouts without waiting for them.
+///
+/// Clones share the same time, so a test can keep one and hand another to the code under test.
+/// Sleeping on a mock clock moves it straight to the deadline instead of blocking. A wheel follows
+/// the clock once `sync_clock` has been called with its time, after which each `advance` is
+/// picked up by one `expire_until(clock.now())`, however many ticks it covers.
+#[derive(Debug, Clone)]
+pub struct MockClock {
+    now: Arc<Mutex<Instant>>
+}
+
+impl MockClock {
+    /// Create a mock clock that starts at the current time
+    pub fn new() -> MockClock {
+        MockClock::starting_at(Instant::now())
+    }
+
+    pub fn starting_at(now: Instant) -> MockClock {
+        MockClock {
+            now: Arc::new(Mutex::new(now))
+        }
+    }
+
+    /// Move the clock forward by `by`
+    pub fn advance(&self, by: Duration) {
+        *self.lock() += by;
+    }
+
+    /// Move the clock to `now`, which must not be before its current time
+    pub fn set(&self, now: Instant) {
+        let mut current = self.lock();
+        assert!(now >= *current, "a monotonic clock can't go backwards");
+        *current = now;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Instant> {
+        self.now.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for MockClock {
+    fn default() -> MockClock {
+        MockClock::new()
+    }
+}
+
+impl Clock for MockClock {
+    fn now(&self) -> Instant {
+        *self.lock()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        let mut now = self.lock();
+        if deadline > *now {
+            *now = deadline;
+        }
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    fn sleep_until(&self, deadline: Instant) {
+        (**self).sleep_until(deadline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{CopyWheel, Resolution, Wheel};
+
+    #[test]
+    fn wheels_follow_a_mock_clock() {
+        let clock = MockClock::new();
+        let mut wheel = CopyWheel::new(vec![Resolution::Ms, Resolution::Sec, Resolution::Min]);
+        wheel.set_precise(true);
+        wheel.sync_clock(clock.now());
+        wheel.start("short", Duration::from_secs(5));
+        wheel.start("long", Duration::from_secs(30 * 60));
+
+        clock.advance(Duration::from_secs(6));
+        assert_eq!(vec!["short"], wheel.expire_until(clock.now()));
+        clock.clone().advance(Duration::from_secs(30 * 60));
+        assert_eq!(vec!["long"], wheel.expire_until(clock.now()));
+        assert_eq!(clock.now(), wheel.last_tick_at());
+
+        let deadline = clock.now() + Duration::from_secs(1);
+        clock.sleep_until(deadline);
+        assert_eq!(deadline, clock.now());
+    }
+
+    #[test]
+    fn wheels_resume_on_a_mock_clock() {
+        let clock = MockClock::new();
+        clock.advance(Duration::from_secs(3600));
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.sync_clock(clock.now());
+        wheel.pause();
+        clock.advance(Duration::from_secs(5));
+        wheel.resume_at(clock.now());
+        wheel.start("a", Duration::from_millis(100));
+
+        clock.advance(Duration::from_millis(50));
+        assert!(wheel.expire_until(clock.now()).is_empty());
+        clock.advance(Duration::from_millis(60));
+        assert_eq!(vec!["a"], wheel.expire_until(clock.now()));
+    }
+}
@@ -0,0 +1,9 @@
+//! The hash map used to track keys: std's when available, otherwise a minimal replacement.
+
+#[cfg(feature = "std")]
+pub use std::collections::{hash_map, HashMap};
+
+#[cfg(not(feature = "std"))]
+pub(crate) use table::HashMap;
+#[cfg(not(feature = "std"))]
+pub(crate) use table as hash_map;
@@ -0,0 +1,201 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hash};
+use std::fmt::Debug;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+use super::{CopyWheel, Resolution, Wheel};
+
+/// A wheel that can be shared between threads, with its timers split across separately locked
+/// shards.
+///
+/// Each key belongs to one shard, picked by its hash, and each shard is a `CopyWheel` behind its
+/// own lock. Starting or stopping a timer only locks that timer's shard, and `expire` ticks the
+/// shards one at a time, so worker threads only wait for the ticking thread while it is working on
+/// their shard.
+///
+/// Every method takes `&self`, so the wheel can be shared with an `Arc`. One thread should call
+/// `expire` every tick. Alternatively, each worker can own some of the shards and tick them itself
+/// with `expire_shard`, so that no thread ever waits on a lock held by another's tick. Shards must
+/// then be ticked only that way, once per tick each. A panic on another thread while it holds a
+/// shard's lock doesn't stop the wheel.
+pub struct ConcurrentWheel<T: Eq + Hash + Debug + Clone> {
+    shards: Vec<Mutex<CopyWheel<T>>>,
+    hasher: RandomState,
+    tick_duration: Duration
+}
+
+impl<T: Eq + Hash + Debug + Clone> ConcurrentWheel<T> {
+    /// Create a wheel with one shard for each CPU. See `CopyWheel::new`.
+    pub fn new(resolutions: Vec<Resolution>) -> ConcurrentWheel<T> {
+        let shards = ::std::thread::available_parallelism().map_or(4, |n| n.get());
+        ConcurrentWheel::with_shards(resolutions, shards)
+    }
+
+    /// Create a wheel with `shards` shards, or one if `shards` is zero
+    pub fn with_shards(resolutions: Vec<Resolution>, shards: usize) -> ConcurrentWheel<T> {
+        let shards: Vec<_> = (0..shards.max(1))
+            .map(|_| Mutex::new(CopyWheel::new(resolutions.clone())))
+            .collect();
+        let tick_duration = shards[0].lock().unwrap_or_else(PoisonError::into_inner).tick_duration();
+        ConcurrentWheel {
+            shards,
+            hasher: RandomState::new(),
+            tick_duration
+        }
+    }
+
+    /// Start a timer, restarting it if it's already running
+    pub fn start(&self, key: T, time: Duration) {
+        self.shard(&key).start(key, time);
+    }
+
+    /// Cancel a timer, returning the time it had left
+    pub fn stop(&self, key: &T) -> Option<Duration> {
+        self.shard(key).cancel(key)
+    }
+
+    /// Restart a running timer. See `Wheel::restart`.
+    pub fn restart(&self, key: T, time: Duration) -> bool {
+        self.shard(&key).restart(key, time)
+    }
+
+    pub fn contains(&self, key: &T) -> bool {
+        self.shard(key).contains(key)
+    }
+
+    /// Tick every shard, returning the timers that expired
+    pub fn expire(&self) -> Vec<T> {
+        let mut expired = Vec::new();
+        for shard in &self.shards {
+            lock(shard).expire_into(&mut expired);
+        }
+        expired
+    }
+
+    /// Tick one shard, returning the timers in it that expired. See the type docs for when to use
+    /// this rather than `expire`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `shard` isn't less than `shard_count`.
+    pub fn expire_shard(&self, shard: usize) -> Vec<T> {
+        lock(&self.shards[shard]).expire()
+    }
+
+    /// The shard a key's timer lives in, for routing work on a key to the worker that owns its
+    /// shard
+    pub fn shard_of(&self, key: &T) -> usize {
+        (self.hasher.hash_one(key) % self.shards.len() as u64) as usize
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// The number of running timers, which may be out of date by the time it returns if other
+    /// threads are using the wheel
+    pub fn len(&self) -> usize {
+        self.shards.iter().map(|shard| lock(shard).len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn tick_duration(&self) -> Duration {
+        self.tick_duration
+    }
+
+    fn shard(&self, key: &T) -> MutexGuard<'_, CopyWheel<T>> {
+        lock(&self.shards[self.shard_of(key)])
+    }
+}
+
+impl<T: Eq + Hash + Debug + Clone> Wheel<T> for ConcurrentWheel<T> {
+    type Expired = Vec<T>;
+
+    fn start(&mut self, key: T, time: Duration) {
+        ConcurrentWheel::start(self, key, time);
+    }
+
+    fn stop(&mut self, key: T) -> Option<Duration> {
+        ConcurrentWheel::stop(self, &key)
+    }
+
+    fn expire(&mut self) -> Vec<T> {
+        ConcurrentWheel::expire(self)
+    }
+
+    fn start_with_delay(&mut self, key: T, delay: Duration, period: Duration) {
+        self.shard(&key).start_with_delay(key, delay, period);
+    }
+
+    fn restart(&mut self, key: T, time: Duration) -> bool {
+        ConcurrentWheel::restart(self, key, time)
+    }
+
+    fn tick_duration(&self) -> Duration {
+        self.tick_duration
+    }
+}
+
+fn lock<W>(shard: &Mutex<W>) -> MutexGuard<'_, W> {
+    shard.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    #[test]
+    fn threads_share_the_wheel() {
+        let wheel = Arc::new(ConcurrentWheel::with_shards(vec![Resolution::TenMs, Resolution::Sec], 4));
+        let workers: Vec<_> = (0..4u64).map(|worker| {
+            let wheel = wheel.clone();
+            thread::spawn(move || {
+                for key in worker * 100..worker * 100 + 100 {
+                    wheel.start(key, Duration::from_millis(20));
+                    if key % 2 == 1 {
+                        assert_eq!(Some(Duration::from_millis(30)), wheel.stop(&key));
+                    }
+                }
+            })
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(200, wheel.len());
+        assert!(wheel.contains(&0) && !wheel.contains(&1));
+
+        assert!(wheel.expire().is_empty());
+        assert!(wheel.expire().is_empty());
+        let mut expired = wheel.expire();
+        expired.sort();
+        assert_eq!((0..400).filter(|key| key % 2 == 0).collect::<Vec<_>>(), expired);
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn workers_tick_their_own_shards() {
+        let wheel = Arc::new(ConcurrentWheel::with_shards(vec![Resolution::TenMs, Resolution::Sec], 3));
+        for key in 0..300u64 {
+            wheel.start(key, Duration::from_millis(10));
+        }
+        let workers: Vec<_> = (0..wheel.shard_count()).map(|shard| {
+            let wheel = wheel.clone();
+            thread::spawn(move || {
+                assert!(wheel.expire_shard(shard).is_empty());
+                let expired = wheel.expire_shard(shard);
+                assert!(expired.iter().all(|key| wheel.shard_of(key) == shard));
+                expired.len()
+            })
+        }).collect();
+        let expired: usize = workers.into_iter().map(|worker| worker.join().unwrap()).sum();
+        assert_eq!(300, expired);
+        assert!(wheel.is_empty());
+    }
+}
@@ -1,24 +1,65 @@
-use std::iter::Iterator;
-use std::hash::Hash;
-use std::collections::HashSet;
-use std::mem;
-use std::fmt::Debug;
-use std::time::Duration;
-use super::{InnerWheel, Wheel, Resolution, wheel_sizes};
+use core::iter::{Iterator, FromIterator};
+use core::borrow::Borrow;
+use core::hash::Hash;
+use collections::HashMap;
+use core::mem;
+use core::fmt::Debug;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+use alloc::collections::BTreeMap;
+use alloc::vec::{Drain, Vec};
+use super::{Entry, InnerWheel, IntoIter, Wheel, Resolution};
+#[cfg(feature = "alloc-wheel")]
+use alloc::rc::Rc;
+#[cfg(feature = "alloc-wheel")]
+use super::{AllocWheel, Slot};
+use super::{StartError, check_duration, wheel_sizes, deadline, is_due, remaining, resolution_duration};
+use super::{advance_slots, next_deadline, remove_slot_entry, slot_deadline, DuplicatePolicy, Rounding, TickPolicy, Ticks};
+use super::{coalesce, exact_deadline, precise_slot, range_wheels, sized_wheels, slots_ahead, span_deadline};
+use super::with_targets;
+#[cfg(feature = "std")]
+use super::{start_instant, time_until};
+use super::{default_resolutions, network_timeout_resolutions, coarse_job_resolutions};
 
 /// This wheel maintains a copy of the timer key in both the appropriate inner timer wheel slot and
-/// the global hashset. This does not require an allocation for each timer but may use more memory
-/// than an CopyWheel depending upon the size of the keys. When the expiry for a slot occurs, the
-/// global hashmap is checked for the expiring keys. If they are still there it means they are valid
-/// to expire, otherwise they have already been cancelled.
+/// the global hashmap. This does not require an allocation for each timer but may use more memory
+/// than an AllocWheel depending upon the size of the keys. The hashmap records the tick at which
+/// each key is due to expire. When the expiry for a slot occurs, the global hashmap is checked for
+/// the expiring keys. If they are still there and due at the current tick it means they are valid
+/// to expire, otherwise they have already been cancelled or restarted.
+///
+/// Interval timers also keep their period in a second hashmap, and are rescheduled as they expire.
 ///
 /// The minimum duration of a timer is 1 ms.
-/// The maximum duration of a timer is 1 day.
+/// Timers longer than the span of the wheels wait in an overflow list until they are close enough
+/// to go in a slot.
 pub struct CopyWheel<T: Eq + Hash + Debug + Clone> {
-    resolutions: Vec<Resolution>,
-    keys: HashSet<T>,
-    wheels: Vec<InnerWheel<T>>,
-    slot_indexes: Vec<usize>,
+    pub(crate) resolutions: Vec<Resolution>,
+    pub(crate) keys: HashMap<T, u64>,
+    pub(crate) periods: HashMap<T, Duration>,
+    pub(crate) wheels: Vec<InnerWheel<T>>,
+    pub(crate) slot_indexes: Vec<usize>,
+    pub(crate) tick: u64,
+    pub(crate) expired: Vec<T>,
+    pub(crate) duplicate_policy: DuplicatePolicy,
+    pub(crate) paused: bool,
+    pub(crate) precise: bool,
+    pub(crate) slack: Duration,
+    pub(crate) max_timers: Option<usize>,
+    pub(crate) rounding: Rounding,
+    pub(crate) tick_policy: TickPolicy,
+    // Time passed to `expire_elapsed` that didn't make up a whole tick
+    pub(crate) leftover: Duration,
+    // The exact deadlines of timers started in precise mode, while `keys` holds the deadline of
+    // the slot each one is waiting in
+    pub(crate) targets: HashMap<T, u64>,
+    // Timers due after the last slot of the wheels, by deadline. Like slot entries, those that
+    // were cancelled or restarted are skipped when they come round.
+    pub(crate) overflow: BTreeMap<u64, Vec<T>>,
+    // The instant of tick 0, for `expire_until`, once known
+    #[cfg(feature = "std")]
+    pub(crate) origin: Option<Instant>
 }
 
 impl<T: Eq + Hash + Debug + Clone> CopyWheel<T> {
@@ -31,108 +72,885 @@ impl<T: Eq + Hash + Debug + Clone> CopyWheel<T> {
     /// The maximum value of the wheel is its minimum resolution times the number of slots in that
     /// resolution's wheel. For example if the maximum resolution is 1 second then the max timer
     /// that may be represented is 1 minute, since the second wheel always only contains 60 slots.
-    /// If larger timer durations are desired, the user should add another, lower resolution.
-    /// The absolute maximum timer duration is 1 day.
-    pub fn new(mut resolutions: Vec<Resolution>) -> CopyWheel<T> {
+    /// Longer timers are held in an overflow list until they come within range, which costs a
+    /// lookup in a sorted map on each tick. Adding a lower resolution keeps them in the wheels.
+    pub fn new(resolutions: Vec<Resolution>) -> CopyWheel<T> {
+        CopyWheel::with_capacity(resolutions, 0)
+    }
+
+    /// Create a set of hierarchical inner wheels with room for `capacity` timers before the key
+    /// set needs to grow
+    pub fn with_capacity(mut resolutions: Vec<Resolution>, capacity: usize) -> CopyWheel<T> {
         let sizes = wheel_sizes(&mut resolutions);
+        CopyWheel::from_sizes(resolutions, sizes, capacity)
+    }
+
+    /// Create a set of hierarchical inner wheels, giving some of them a number of slots other
+    /// than the one they would have in `new`.
+    ///
+    /// Only the coarsest wheel can change its number of slots, which changes the span of the
+    /// wheels. For example a 1 second wheel with 120 slots holds timers of up to 2 minutes without
+    /// adding a 1 minute wheel. Panics if the slots are given for any other wheel, or are fewer
+    /// than 2.
+    pub fn with_sizes(resolutions: Vec<Resolution>, slots: &[(Resolution, usize)]) -> CopyWheel<T> {
+        let (resolutions, sizes) = sized_wheels(resolutions, slots)
+            .unwrap_or_else(|reason| panic!("{}", reason));
+        CopyWheel::from_sizes(resolutions, sizes, 0)
+    }
+
+    /// Create a set of hierarchical inner wheels that ticks every `tick` and holds timers of up
+    /// to `max`, choosing the resolutions so that callers don't have to.
+    ///
+    /// The wheel uses the resolution that lasts `tick`, or a custom one if none does, followed by
+    /// the fewest coarser resolutions that reach past `max`. If weeks don't, the coarsest wheel
+    /// gets enough slots to. The wheel must be driven by calling `expire` every `tick`.
+    pub fn with_range(tick: Duration, max: Duration) -> CopyWheel<T> {
+        let (resolutions, slots) = range_wheels(tick, max);
+        CopyWheel::with_sizes(resolutions, &slots)
+    }
+
+    // Create the wheels from sorted resolutions and the number of slots in each
+    pub(crate) fn from_sizes(resolutions: Vec<Resolution>,
+                             sizes: Vec<usize>,
+                             capacity: usize) -> CopyWheel<T>
+    {
         let indexes = vec![0; sizes.len()];
         CopyWheel {
-            resolutions: resolutions,
-            keys: HashSet::new(),
+            resolutions,
+            keys: HashMap::with_capacity(capacity),
+            periods: HashMap::new(),
             wheels: sizes.iter().map(|size| InnerWheel::new(*size)).collect(),
-            slot_indexes: indexes
+            slot_indexes: indexes,
+            tick: 0,
+            expired: Vec::new(),
+            duplicate_policy: DuplicatePolicy::default(),
+            paused: false,
+            precise: false,
+            slack: Duration::from_secs(0),
+            max_timers: None,
+            rounding: Rounding::default(),
+            tick_policy: TickPolicy::default(),
+            leftover: Duration::from_secs(0),
+            targets: HashMap::new(),
+            overflow: BTreeMap::new(),
+            #[cfg(feature = "std")]
+            origin: start_instant()
         }
     }
 
-    fn insert_hours(&mut self, key: T, time: Duration) -> Result<(), (T, Duration)> {
-        let slot = time.as_secs()/3600;
-        self.insert(key, time, Resolution::Hour, slot as usize + 1)
+    /// A wheel suited to network timeouts such as request deadlines and idle connections.
+    ///
+    /// The wheel must be driven by calling `expire` every 10ms and supports timers of up to an
+    /// hour.
+    pub fn for_network_timeouts() -> CopyWheel<T> {
+        CopyWheel::new(network_timeout_resolutions())
+    }
+
+    /// A wheel suited to coarse grained jobs such as cache sweeps and periodic reports.
+    ///
+    /// The wheel must be driven by calling `expire` every second and supports timers of up to a
+    /// day.
+    pub fn for_coarse_jobs() -> CopyWheel<T> {
+        CopyWheel::new(coarse_job_resolutions())
+    }
+
+    /// The number of running timers
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// What `start` does with a timer that is already running
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicate_policy
+    }
+
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+
+    /// Stop the wheel ticking, so `expire` and friends return nothing until `resume` is called.
+    ///
+    /// Timers can still be started and stopped while the wheel is paused, and none of them run
+    /// down, so each keeps the time it had left. This is useful when debugging or when the process
+    /// is suspended.
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    /// Start the wheel ticking again from where it was paused.
+    ///
+    /// Deadlines move out by the length of the pause. To expire the timers as if the pause never
+    /// happened instead, follow this with `advance` for the ticks that were missed. A wheel driven
+    /// by `expire_until` doesn't know how long the pause was, so its first call afterwards catches
+    /// up on the paused ticks. Use `resume_at` to move its deadlines out instead.
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// Start the wheel ticking again, like `resume`, and make `now` the instant of its last tick,
+    /// so that `expire_until` carries on from `now` rather than catching up on the pause.
+    #[cfg(feature = "std")]
+    pub fn resume_at(&mut self, now: Instant) {
+        self.resume();
+        self.sync_clock(now);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
     }
 
-    fn insert_minutes(&mut self, key: T, time: Duration) -> Result<(), (T, Duration)> {
-        let slot = time.as_secs()/60;
-        self.insert(key, time, Resolution::Min, slot  as usize + 1)
+    /// Expire timers started from now on at the tick the first wheel would expire them on, rather
+    /// than rounding them to the resolution of the wheel they are put in.
+    ///
+    /// A precise timer waits in the coarsest wheel it can and moves down into finer wheels as its
+    /// slots come round, the way a cascading timer wheel works. A 1.3s timer on a 10ms and 1s
+    /// wheel then expires after 1.31s rather than between 1 and 2 seconds. The cost is an extra
+    /// copy of the key and a move for each wheel the timer passes through. Timers that are already
+    /// running keep the mode they were started in.
+    pub fn set_precise(&mut self, precise: bool) {
+        self.precise = precise;
+    }
+
+    pub fn is_precise(&self) -> bool {
+        self.precise
     }
 
-    fn insert_seconds(&mut self, key: T, time: Duration) -> Result<(), (T, Duration)> {
-        self.insert(key, time, Resolution::Sec, time.as_secs() as usize + 1)
+    /// Let timers started from now on expire up to `slack` late, so that timers due close
+    /// together fire on the same tick.
+    ///
+    /// Ticks are grouped into windows of `slack`, and each timer expires on the last tick of the
+    /// window its deadline falls in. With a 50ms slack, timers due 10ms and 40ms into a window
+    /// both expire at its end, and a loop that sleeps until the next timer wakes once rather than
+    /// twice. This saves power on laptops and phones at the cost of the precision of each timer.
+    /// Interval timers are rescheduled with the slack in force when they expire. The default of
+    /// zero, or any slack shorter than two ticks, leaves timers alone.
+    pub fn set_slack(&mut self, slack: Duration) {
+        self.slack = slack;
     }
 
-    fn insert_hundred_ms(&mut self, key: T, time: Duration) -> Result<(), (T, Duration)> {
-        let slot = time.subsec_nanos()/(1000*1000*100);
-        self.insert(key, time, Resolution::HundredMs, slot as usize + 1)
+    pub fn slack(&self) -> Duration {
+        self.slack
+    }
+
+    /// Limit the number of running timers, or lift the limit with `None`.
+    ///
+    /// Once the wheel holds `max` timers, `try_start` returns `StartError::Full` and `start`
+    /// ignores new keys, as it ignores durations that are too short, until timers expire or are
+    /// cancelled. Restarting a running timer is always allowed. This bounds the memory a
+    /// misbehaving client can make the wheel use, and `is_full` or `try_start` make hitting the
+    /// bound visible. Lowering the limit below the running count doesn't stop any timers.
+    pub fn set_max_timers(&mut self, max: Option<usize>) {
+        self.max_timers = max;
     }
 
-    fn insert_ten_ms(&mut self, key: T, time: Duration) -> Result<(), (T, Duration)> {
-        let slot = time.subsec_nanos()/(1000*1000*10);
-        self.insert(key, time, Resolution::TenMs, slot as usize + 1)
+    pub fn max_timers(&self) -> Option<usize> {
+        self.max_timers
+    }
+
+    /// Whether the wheel holds its maximum number of timers
+    pub fn is_full(&self) -> bool {
+        self.max_timers.is_some_and(|max| self.keys.len() >= max)
+    }
+
+    /// How timers started from now on are rounded to the slots of their wheel when the wheel
+    /// isn't precise. Timers already running keep their deadlines.
+    pub fn set_rounding(&mut self, rounding: Rounding) {
+        self.rounding = rounding;
+    }
+
+    pub fn rounding(&self) -> Rounding {
+        self.rounding
+    }
+
+    /// Whether timers started from now on may expire late or early. Timers already running keep
+    /// their deadlines. See `TickPolicy`.
+    pub fn set_tick_policy(&mut self, policy: TickPolicy) {
+        self.tick_policy = policy;
+    }
+
+    pub fn tick_policy(&self) -> TickPolicy {
+        self.tick_policy
+    }
+
+    /// The number of calls to `expire` until the next timer expires, or `None` if no timers are
+    /// running.
+    ///
+    /// The calls before then return nothing, so an event loop can sleep through them and catch up
+    /// afterwards. The answer comes from scanning the slots ahead rather than every key.
+    pub fn ticks_until_next_expiry(&self) -> Option<u64> {
+        let keys = &self.keys;
+        let next = next_deadline(self.tick, &self.wheels, &self.slot_indexes, |key, deadline| {
+            keys.get(key) == Some(&deadline)
+        }).or_else(|| self.next_overflow())?;
+        Some(next - self.tick)
     }
 
-    fn insert_ms(&mut self, key: T, time: Duration) -> Result<(), (T, Duration)> {
-        let slot = time.subsec_nanos()/(1000*1000);
-        self.insert(key, time, Resolution::Ms, slot as usize + 1)
+    /// The time until the next timer expires, or `None` if no timers are running
+    pub fn next_expiry_in(&self) -> Option<Duration> {
+        let ticks = self.ticks_until_next_expiry()?;
+        Some(Ticks(ticks).to_duration(self.tick_duration()))
+    }
+
+    /// Whether a timer is running for `key`.
+    ///
+    /// As with `HashMap`, the key may be any borrowed form of the key type, such as a `&str` for
+    /// `String` keys. The same goes for `remaining` and `cancel`.
+    pub fn contains<Q>(&self, key: &Q) -> bool
+        where T: Borrow<Q>,
+              Q: Eq + Hash + ?Sized
+    {
+        self.keys.contains_key(key)
+    }
+
+    /// The time left until the timer for `key` expires, or `None` if it isn't running.
+    ///
+    /// The time left is rounded up to a whole number of ticks.
+    pub fn remaining<Q>(&self, key: &Q) -> Option<Duration>
+        where T: Borrow<Q>,
+              Q: Eq + Hash + ?Sized
+    {
+        let deadline = self.due(key)?;
+        Some(remaining(self.tick, deadline, resolution_duration(self.resolutions[0])))
+    }
+
+    /// The number of running timers in the wheel for `resolution`, or 0 if there is no such wheel.
+    ///
+    /// This walks every slot of that wheel, so it is meant for metrics rather than the hot path.
+    pub fn len_at(&self, resolution: Resolution) -> usize {
+        let index = match self.resolutions.iter().position(|r| *r == resolution) {
+            Some(index) => index,
+            None => return 0
+        };
+        // A timer restarted with the same deadline has two entries in its slot
+        let mut running: HashMap<_, ()> = HashMap::new();
+        for (i, slot) in self.wheels[index].slots.iter().enumerate() {
+            let deadline = slot_deadline(self.tick, &self.wheels, &self.slot_indexes, index, i);
+            for key in slot.entries.iter().filter(|key| self.keys.get(*key) == Some(&deadline)) {
+                running.insert(key, ());
+            }
+        }
+        running.len()
+    }
+
+    /// Get the entry for a key in order to start, restart or cancel its timer
+    pub fn entry(&mut self, key: T) -> Entry<'_, T, CopyWheel<T>> {
+        let running = self.keys.contains_key(&key);
+        Entry::new(self, key, running)
+    }
+
+    /// Start a timer for each key and duration, as `start` does.
+    ///
+    /// Room for the new keys is reserved up front from the iterator's size hint, so loading many
+    /// timers at once doesn't grow the key set repeatedly.
+    pub fn start_all<I: IntoIterator<Item = (T, Duration)>>(&mut self, timers: I) {
+        let timers = timers.into_iter();
+        self.keys.reserve(timers.size_hint().0);
+        for (key, time) in timers {
+            self.start(key, time);
+        }
+    }
+
+    /// Cancel every timer.
+    ///
+    /// The slots keep their capacity, so a cleared wheel can be reused without allocating again.
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.periods.clear();
+        self.targets.clear();
+        self.overflow.clear();
+        for wheel in &mut self.wheels {
+            for slot in &mut wheel.slots {
+                slot.entries.clear();
+            }
+        }
+    }
+
+    /// Cancel every timer, returning each key with the time that was left before it would have
+    /// expired.
+    ///
+    /// This is `into_iter` for a wheel that is still in use, such as when moving the timers to
+    /// another wheel or persisting them on shutdown.
+    pub fn drain(&mut self) -> Vec<(T, Duration)> {
+        let keys = with_targets(mem::take(&mut self.keys), mem::take(&mut self.targets));
+        self.clear();
+        IntoIter::copied(keys, self.tick, resolution_duration(self.resolutions[0])).collect()
+    }
+
+    /// Keep only the timers whose keys `f` returns true for, and cancel the rest
+    pub fn retain<F: FnMut(&T) -> bool>(&mut self, mut f: F) {
+        self.keys.retain(|key, _| f(key));
+        let keys = &self.keys;
+        self.periods.retain(|key, _| keys.contains_key(key));
+        self.targets.retain(|key, _| keys.contains_key(key));
+    }
+
+    /// Cancel the timers whose keys `f` returns true for, returning how many were cancelled
+    pub fn cancel_where<F: FnMut(&T) -> bool>(&mut self, mut f: F) -> usize {
+        let len = self.keys.len();
+        self.retain(|key| !f(key));
+        len - self.keys.len()
+    }
+
+    /// Cancel a timer, returning the time that was left before it would have expired, or `None`
+    /// if it wasn't running.
+    ///
+    /// The time left is rounded up to a whole number of ticks.
+    pub fn cancel<Q>(&mut self, key: &Q) -> Option<Duration>
+        where T: Borrow<Q>,
+              Q: Eq + Hash + ?Sized
+    {
+        self.periods.remove(key);
+        let deadline = self.keys.remove(key)?;
+        let deadline = self.targets.remove(key).unwrap_or(deadline);
+        Some(remaining(self.tick, deadline, resolution_duration(self.resolutions[0])))
+    }
+
+    /// Move a running timer to a new deadline `time` from now, returning false if it isn't
+    /// running or `time` is too short for the wheel.
+    ///
+    /// Unlike `restart`, which leaves the old slot entry behind to be skipped when its slot comes
+    /// round, this removes it, so a timer that is pushed back many times doesn't fill the wheel
+    /// with stale entries. Finding the entry costs a scan of its slot.
+    pub fn reschedule(&mut self, key: T, time: Duration) -> bool {
+        let old_deadline = match self.keys.get(&key) {
+            Some(&deadline) => deadline,
+            None => return false
+        };
+        if !self.schedule(key.clone(), time) {
+            return false;
+        }
+        remove_slot_entry(self.tick, &mut self.wheels, &self.slot_indexes, old_deadline,
+                          |entry| *entry == key);
+        true
+    }
+
+    /// Push a running timer's deadline out by `extra`, returning false if it isn't running.
+    ///
+    /// The new deadline is worked out from the time the timer has left, so a lease can be renewed
+    /// without remembering how long it was started for. `extra` is rounded down to whole ticks,
+    /// and a timer that moves to a coarser wheel is rounded as `start` would round it.
+    pub fn extend_by(&mut self, key: T, extra: Duration) -> bool {
+        let left = match self.remaining(&key) {
+            Some(left) => left,
+            None => return false
+        };
+        // A timer with one tick left fires on the next call to `expire`, like one started for a
+        // tick, so the time to schedule counts from there
+        let tick = self.tick_duration();
+        let time = left.saturating_sub(tick) + extra;
+        time < tick || self.reschedule(key, time)
+    }
+
+    /// Start a timer that isn't already running, without adjusting the duration to fit.
+    ///
+    /// `start` restarts a running timer and ignores durations shorter than a tick. This reports
+    /// each of those as an error instead and leaves the wheel unchanged. Durations longer than the
+    /// wheel span wait in the overflow list, as they do with `start`.
+    pub fn try_start(&mut self, key: T, time: Duration) -> Result<(), StartError> {
+        if self.keys.contains_key(&key) {
+            return Err(StartError::DuplicateKey);
+        }
+        if self.is_full() {
+            return Err(StartError::Full);
+        }
+        check_duration(&self.resolutions, time)?;
+        if !self.schedule(key, time) {
+            // Resolutions with gaps between them can't represent every duration in the span
+            return Err(StartError::TooShort(resolution_duration(self.resolutions[0])));
+        }
+        Ok(())
+    }
+
+    /// Move all the pending timers into an `AllocWheel` with the same resolutions.
+    ///
+    /// The new wheel carries on from the same tick, so each timer expires exactly when it would
+    /// have in this wheel.
+    #[cfg(feature = "alloc-wheel")]
+    pub fn into_alloc(self) -> AllocWheel<T> {
+        let mut keys = HashMap::with_capacity(self.keys.len());
+        for (key, deadline) in self.keys {
+            keys.insert(Rc::new(key), deadline);
+        }
+        // Interval timers share the allocation of their key
+        let periods = self.periods.into_iter()
+            .filter_map(|(key, period)| keys.get_key_value(&key).map(|(key, _)| (key.clone(), period)))
+            .collect();
+        // Entries for cancelled timers have no key to point to, so they are dropped here
+        let wheels = self.wheels.into_iter().map(|wheel| InnerWheel {
+            slots: wheel.slots.into_iter().map(|slot| Slot {
+                entries: slot.entries.iter()
+                    .filter_map(|key| keys.get_key_value(key))
+                    .map(|(key, _)| Rc::downgrade(key))
+                    .collect()
+            }).collect()
+        }).collect();
+        let overflow = self.overflow.into_iter().map(|(target, entries)| {
+            let entries = entries.iter()
+                .filter_map(|key| keys.get_key_value(key))
+                .map(|(key, _)| Rc::downgrade(key))
+                .collect();
+            (target, entries)
+        }).collect();
+        AllocWheel {
+            resolutions: self.resolutions,
+            keys,
+            periods,
+            wheels,
+            slot_indexes: self.slot_indexes,
+            tick: self.tick,
+            expired: Vec::new(),
+            duplicate_policy: self.duplicate_policy,
+            paused: self.paused,
+            precise: self.precise,
+            slack: self.slack,
+            max_timers: self.max_timers,
+            rounding: self.rounding,
+            tick_policy: self.tick_policy,
+            leftover: self.leftover,
+            stale: 0,
+            targets: self.targets,
+            overflow,
+            #[cfg(feature = "std")]
+            origin: self.origin
+        }
+    }
+
+    /// Expire the timers due at the next tick, like `expire`, but return them as a draining
+    /// iterator over a buffer kept by the wheel.
+    ///
+    /// The buffer keeps its capacity between ticks, so once it has grown to fit the busiest tick
+    /// expiring timers no longer allocates. Keys that aren't consumed are dropped along with the
+    /// iterator.
+    pub fn expire_iter(&mut self) -> Drain<'_, T> {
+        let mut expired = mem::take(&mut self.expired);
+        self.expire_into(&mut expired);
+        self.expired = expired;
+        self.expired.drain(..)
+    }
+
+    /// Expire the timers due at the next tick, like `expire`, but append them to `expired`.
+    ///
+    /// Reusing the same buffer on every tick keeps expiring timers free of allocations once the
+    /// buffer has grown to fit the busiest tick.
+    pub fn expire_into(&mut self, expired: &mut Vec<T>) {
+        if self.paused {
+            return;
+        }
+        // Take keys out of self temporarily so we don't have to borrow self
+        let mut keys = HashMap::new();
+        mem::swap(&mut keys, &mut self.keys);
+
+        self.tick += 1;
+        let now = self.tick;
+        let start = expired.len();
+        for (ref mut wheel, ref mut slot_index) in self.wheels.iter_mut().zip(&mut self.slot_indexes) {
+            **slot_index = (**slot_index + 1) % wheel.slots.len();
+            expired.extend(wheel.slots[**slot_index].entries.drain(..)
+                           .filter(|key| is_due(&mut keys, key, now)));
+
+            // We haven't wrapped around to the next wheel
+            if **slot_index != 0 {
+                break;
+            }
+        }
+
+        // Make keys part of self again
+        mem::swap(&mut keys, &mut self.keys);
+
+        self.cascade(expired, start);
+        self.refeed(expired);
+        for key in &expired[start..] {
+            if let Some(period) = self.periods.get(key).cloned() {
+                self.schedule(key.clone(), period);
+            }
+        }
+    }
+
+    /// Move the wheel on by the ticks that fit in `elapsed`, returning every timer that came due
+    /// along the way.
+    ///
+    /// This drives the wheel from a loop with uneven steps, such as a game loop fed the time each
+    /// frame took, without calling `expire` once per tick. Time left over after the last whole
+    /// tick is carried into the next call, so none is lost however the steps fall. The catch up
+    /// works like `advance`.
+    pub fn expire_elapsed(&mut self, elapsed: Duration) -> Vec<T> {
+        let tick = self.tick_duration();
+        let elapsed = self.leftover + elapsed;
+        let ticks = Ticks::from_duration(elapsed, tick);
+        self.leftover = elapsed - ticks.to_duration(tick);
+        self.advance(ticks.0)
+    }
+
+    /// Move the wheel on by `ticks` at once, returning every timer that came due along the way.
+    ///
+    /// This is for catching up after the loop calling `expire` fell behind. Each slot passed is
+    /// visited at most once, rather than once per turn of its wheel, and the keys are returned
+    /// grouped by wheel rather than in deadline order. An interval timer that came due fires once
+    /// and then runs its period from the new tick. Nothing happens while the wheel is paused.
+    pub fn advance(&mut self, ticks: u64) -> Vec<T> {
+        if self.paused {
+            return Vec::new();
+        }
+        let end = self.tick + ticks;
+        let mut expired = Vec::new();
+        {
+            let keys = &mut self.keys;
+            advance_slots(self.tick, ticks, &mut self.wheels, &mut self.slot_indexes, |entries| {
+                entries.retain(|key| match keys.get(key).cloned() {
+                    Some(deadline) if deadline <= end => {
+                        keys.remove(key);
+                        expired.push(key.clone());
+                        false
+                    },
+                    Some(_) => true,
+                    // Cancelled
+                    None => false
+                });
+            });
+        }
+        self.tick = end;
+
+        self.cascade(&mut expired, 0);
+        self.refeed(&mut expired);
+        for key in &expired {
+            if let Some(period) = self.periods.get(key).cloned() {
+                self.schedule(key.clone(), period);
+            }
+        }
+        expired
+    }
+
+    /// The instant of the wheel's last tick, counting from when it was created.
+    ///
+    /// On wasm32-unknown-unknown the wheel can't read the clock when it is created, so this
+    /// panics until `sync_clock` or `expire_until` has given it the time.
+    #[cfg(feature = "std")]
+    pub fn last_tick_at(&self) -> Instant {
+        let origin = self.origin.expect("the wheel needs the time from sync_clock first");
+        origin + Ticks(self.tick).to_duration(self.tick_duration())
+    }
+
+    /// Make `now` the instant of the wheel's last tick, so that `expire_until` and the other
+    /// methods taking an `Instant` follow a `Clock` other than the system's, such as a mock clock.
+    #[cfg(feature = "std")]
+    pub fn sync_clock(&mut self, now: Instant) {
+        let since = Ticks(self.tick).to_duration(self.tick_duration());
+        if let Some(origin) = now.checked_sub(since) {
+            self.origin = Some(origin);
+        }
+    }
+
+    /// Start a timer that expires at `deadline` rather than after a duration, restarting it if
+    /// it's already running.
+    ///
+    /// The deadline is measured from `last_tick_at`, the wheel's own idea of now, rather than the
+    /// clock, so a loop that is running late doesn't push its deadlines out. The timer expires on
+    /// the first tick at or after the deadline, rounded as `start` rounds, and a deadline that has
+    /// passed expires as soon as a timer can.
+    #[cfg(feature = "std")]
+    pub fn start_at(&mut self, key: T, deadline: Instant) {
+        let wait = deadline.saturating_duration_since(self.last_tick_at());
+        let time = time_until(wait, self.tick_duration());
+        self.start(key, time);
+    }
+
+    /// Restart a running timer so that it expires at `deadline`. See `start_at` and
+    /// `Wheel::restart`.
+    #[cfg(feature = "std")]
+    pub fn restart_at(&mut self, key: T, deadline: Instant) -> bool {
+        let wait = deadline.saturating_duration_since(self.last_tick_at());
+        let time = time_until(wait, self.tick_duration());
+        self.restart(key, time)
+    }
+
+    /// The instant a running timer is due to expire
+    #[cfg(feature = "std")]
+    pub fn expires_at(&self, key: &T) -> Option<Instant> {
+        Some(self.last_tick_at() + self.remaining(key)?)
+    }
+
+    /// Expire every tick that has passed by `now`, counting from when the wheel was created, and
+    /// return the timers that came due.
+    ///
+    /// This lets a loop drive the wheel from the clock instead of calling `expire` exactly once per
+    /// tick. Ticks done with `expire` or `advance` count too, so the styles can be mixed, and a
+    /// `now` before the next tick returns nothing. The catch up works like `advance`.
+    #[cfg(feature = "std")]
+    pub fn expire_until(&mut self, now: Instant) -> Vec<T> {
+        let origin = match self.origin {
+            Some(origin) => origin,
+            None => {
+                self.sync_clock(now);
+                return Vec::new();
+            }
+        };
+        let elapsed = now.saturating_duration_since(origin);
+        let due = Ticks::from_duration(elapsed, self.tick_duration());
+        let ticks = due.saturating_sub(Ticks(self.tick));
+        self.advance(ticks.0)
+    }
+
+    /// Expire every tick that has passed by `now` and return the timers that came due, for
+    /// callers that think of it as expiring the wheel at an instant. See `expire_until`.
+    #[cfg(feature = "std")]
+    pub fn expire_at(&mut self, now: Instant) -> Vec<T> {
+        self.expire_until(now)
+    }
+
+    /// How long an event loop using `expire_until` can wait at `now` before the next timer is
+    /// due, or `None` if no timers are running.
+    ///
+    /// Pass this as the timeout to a poll call such as mio's `Poll::poll`, then call
+    /// `expire_until` when it returns. Any key type works with a poll loop, including mio's
+    /// `Token`. The timeout is zero if a timer is already due.
+    #[cfg(feature = "std")]
+    pub fn poll_timeout(&self, now: Instant) -> Option<Duration> {
+        if self.paused {
+            return None;
+        }
+        let due = self.last_tick_at() + self.next_expiry_in()?;
+        Some(due.saturating_duration_since(now))
+    }
+
+    // The tick a running timer is due at
+    fn due<Q>(&self, key: &Q) -> Option<u64>
+        where T: Borrow<Q>,
+              Q: Eq + Hash + ?Sized
+    {
+        self.targets.get(key).or_else(|| self.keys.get(key)).cloned()
+    }
+
+    // Move the precise timers in `expired[start..]` that aren't due yet down to finer wheels
+    fn cascade(&mut self, expired: &mut Vec<T>, start: usize) {
+        if self.targets.is_empty() {
+            return;
+        }
+        for key in expired.split_off(start) {
+            match self.targets.get(&key).cloned() {
+                Some(target) if target > self.tick => self.place(key, target),
+                Some(_) => {
+                    self.targets.remove(&key);
+                    expired.push(key);
+                },
+                None => expired.push(key)
+            }
+        }
+    }
+
+    // Move the timers in the overflow list that have come within range into the wheels. Any that
+    // came due while `advance` skipped ahead are pushed onto `expired`.
+    fn refeed(&mut self, expired: &mut Vec<T>) {
+        let limit = span_deadline(self.tick, &self.wheels);
+        while let Some(entry) = self.overflow.first_entry() {
+            if *entry.key() > limit {
+                break;
+            }
+            let (target, keys) = entry.remove_entry();
+            for key in keys {
+                // Skip timers that were cancelled or restarted
+                if self.keys.get(&key) != Some(&target) {
+                    continue;
+                }
+                if target <= self.tick {
+                    self.keys.remove(&key);
+                    self.targets.remove(&key);
+                    expired.push(key);
+                } else if self.targets.contains_key(&key) || target == self.tick + 1 {
+                    // A timer due on the next tick is too short to schedule again
+                    self.place(key, target);
+                } else {
+                    let time = Ticks(target - self.tick - 1).to_duration(self.tick_duration());
+                    self.schedule(key, time);
+                }
+            }
+        }
+    }
+
+    // The deadline of the first running timer in the overflow list
+    fn next_overflow(&self) -> Option<u64> {
+        self.overflow.iter()
+            .find(|(target, keys)| keys.iter().any(|key| self.keys.get(key) == Some(target)))
+            .map(|(target, _)| *target)
+    }
+
+    // Put a precise timer in the slot it waits in until the next move or `target`
+    fn place(&mut self, key: T, target: u64) {
+        let (wheel, slot, deadline) =
+            precise_slot(self.tick, &self.wheels, &self.slot_indexes, target);
+        self.wheels[wheel].slots[slot].entries.push(key.clone());
+        self.keys.insert(key.clone(), deadline);
+        self.targets.insert(key, target);
+    }
+
+    // Schedule a timer without changing its period, returning false if the duration is too short
+    // for the wheel
+    fn schedule(&mut self, key: T, time: Duration) -> bool {
+        let time = coalesce(self.tick, self.tick_duration(), time, self.slack);
+        let target = match exact_deadline(self.tick, self.tick_duration(), time) {
+            Some(target) => self.tick_policy.deadline(self.tick, target),
+            None => return false
+        };
+        if target > span_deadline(self.tick, &self.wheels) {
+            self.overflow.entry(target).or_default().push(key.clone());
+            if self.precise {
+                self.targets.insert(key.clone(), target);
+            } else {
+                self.targets.remove(&key);
+            }
+            self.keys.insert(key, target);
+            return true;
+        }
+        if self.precise {
+            self.place(key, target);
+            return true;
+        }
+        let mut result = Err((key.clone(), time));
+        for index in (0..self.resolutions.len()).rev() {
+            if let Err((key, time)) = result {
+                let resolution = self.resolutions[index];
+                let ahead = slots_ahead(resolution, time, index == self.resolutions.len() - 1);
+                let slot = self.tick_policy.slots(self.rounding.slots(ahead, resolution, time));
+                result = self.insert(key, time, resolution, slot);
+            } else {
+                break;
+            }
+        }
+        match result {
+            Ok(deadline) => {
+                self.targets.remove(&key);
+                self.keys.insert(key, deadline);
+                true
+            },
+            Err(_) => false
+        }
     }
 
     fn insert(&mut self,
               key: T,
               time: Duration,
               resolution: Resolution,
-              mut slot: usize) -> Result<(), (T, Duration)>
+              mut slot: usize) -> Result<u64, (T, Duration)>
     {
-        // The slot will always be at least 2 ahead of the current, since we add one in each of the
-        // insert_xxx methods
-        if slot == 1 { return Err((key, time)); }
-        if let Some(wheel_index) = self.resolutions.iter().rposition(|ref r| **r == resolution) {
+        // A timer too short for the resolution belongs in a finer wheel
+        if slot == 0 { return Err((key, time)); }
+        if let Some(wheel_index) = self.resolutions.iter().rposition(|r| *r == resolution) {
             let max_slot = self.wheels[wheel_index].slots.len();
             if slot > max_slot {
                 slot = max_slot
             }
             let slot_index = (self.slot_indexes[wheel_index] + slot) % max_slot;
             self.wheels[wheel_index].slots[slot_index].entries.push(key);
-            return Ok(());
+            return Ok(deadline(self.tick, &self.wheels[..wheel_index], slot));
         }
         Err((key, time))
     }
 }
 
+#[cfg(feature = "alloc-wheel")]
+impl<T: Eq + Hash + Debug + Clone> From<AllocWheel<T>> for CopyWheel<T> {
+    fn from(wheel: AllocWheel<T>) -> CopyWheel<T> {
+        wheel.into_copy()
+    }
+}
+
+impl<T: Eq + Hash + Debug + Clone> Default for CopyWheel<T> {
+    /// A wheel with 10ms, 100ms, 1s, 1 minute and 1 hour resolutions.
+    ///
+    /// The wheel must be driven by calling `expire` every 10ms and supports timers of up to a day.
+    fn default() -> CopyWheel<T> {
+        CopyWheel::new(default_resolutions())
+    }
+}
+
+impl<T: Eq + Hash + Debug + Clone> Extend<(T, Duration)> for CopyWheel<T> {
+    /// Start a timer for each key and duration
+    fn extend<I: IntoIterator<Item = (T, Duration)>>(&mut self, iter: I) {
+        self.start_all(iter);
+    }
+}
+
+impl<T: Eq + Hash + Debug + Clone> FromIterator<(T, Duration)> for CopyWheel<T> {
+    /// Create a wheel with the default resolutions and start a timer for each key and duration
+    fn from_iter<I: IntoIterator<Item = (T, Duration)>>(iter: I) -> CopyWheel<T> {
+        let mut wheel = CopyWheel::default();
+        wheel.extend(iter);
+        wheel
+    }
+}
+
+impl<T: Eq + Hash + Debug + Clone> IntoIterator for CopyWheel<T> {
+    type Item = (T, Duration);
+    type IntoIter = IntoIter<T>;
+
+    /// Consume the wheel, yielding each pending key with the time remaining until it would expire
+    fn into_iter(self) -> IntoIter<T> {
+        let keys = with_targets(self.keys, self.targets);
+        IntoIter::copied(keys, self.tick, resolution_duration(self.resolutions[0]))
+    }
+}
+
 impl<T: Eq + Hash + Debug + Clone> Wheel<T> for CopyWheel<T> {
+    type Expired = Vec<T>;
+
     /// Start a timer with the given duration.
+    ///
+    /// Starting a timer that is already running restarts it with the new duration, unless the
+    /// duplicate policy says otherwise.
     fn start(&mut self, key: T, time: Duration) {
-        self.keys.insert(key.clone());
-        let _ = self.insert_hours(key, time)
-            .or_else(|(key, time)| self.insert_minutes(key, time))
-            .or_else(|(key, time)| self.insert_seconds(key, time))
-            .or_else(|(key, time)| self.insert_hundred_ms(key, time))
-            .or_else(|(key, time)| self.insert_ten_ms(key, time))
-            .or_else(|(key, time)| self.insert_ms(key, time));
+        let old_deadline = self.keys.get(&key).cloned();
+        if old_deadline.is_none() && self.is_full() {
+            return;
+        }
+        let old_target = self.targets.get(&key).cloned();
+        if !self.schedule(key.clone(), time) {
+            return;
+        }
+        if let (Some(old), Some(new)) = (old_deadline, self.due(&key)) {
+            if self.duplicate_policy.keeps_running(old_target.unwrap_or(old), new) {
+                // The new slot entry is skipped when it comes around, since it isn't due then
+                self.keys.insert(key.clone(), old);
+                match old_target {
+                    Some(target) => self.targets.insert(key, target),
+                    None => self.targets.remove(&key)
+                };
+                return;
+            }
+        }
+        self.periods.remove(&key);
     }
 
-    /// Cancel a timer.
-    fn stop(&mut self, key: T) {
-        self.keys.remove(&key);
+    fn stop(&mut self, key: T) -> Option<Duration> {
+        self.cancel(&key)
     }
 
     /// Return any expired timer keys
     fn expire(&mut self) -> Vec<T> {
-        // Take keys out of self temporarily so we don't have to borrow self
-        let mut keys = HashSet::new();
-        mem::swap(&mut keys, &mut self.keys);
-
         let mut expired = Vec::new();
-        for (ref mut wheel, ref mut slot_index) in self.wheels.iter_mut().zip(&mut self.slot_indexes) {
-            **slot_index = (**slot_index + 1) % wheel.slots.len();
-            expired.extend(wheel.slots[**slot_index].entries.drain(..)
-                           .filter(|key| keys.remove(key)));
+        self.expire_into(&mut expired);
+        expired
+    }
 
-            // We haven't wrapped around to the next wheel
-            if **slot_index != 0 {
-                break;
-            }
+    fn start_with_delay(&mut self, key: T, delay: Duration, period: Duration) {
+        if !self.keys.contains_key(&key) && self.is_full() {
+            return;
+        }
+        if self.schedule(key.clone(), delay) {
+            let period = period.max(self.tick_duration());
+            self.periods.insert(key, period);
         }
+    }
 
-        // Make keys part of self again
-        mem::swap(&mut keys, &mut self.keys);
-        expired
+    fn restart(&mut self, key: T, time: Duration) -> bool {
+        // A failed schedule leaves the old deadline in place
+        self.keys.contains_key(&key) && self.schedule(key, time)
+    }
+
+    fn tick_duration(&self) -> Duration {
+        resolution_duration(self.resolutions[0])
     }
 }
 
@@ -140,7 +958,7 @@ impl<T: Eq + Hash + Debug + Clone> Wheel<T> for CopyWheel<T> {
 mod tests {
     use super::*;
     use std::time::Duration;
-    use super::super::{Resolution, Wheel};
+    use super::super::{FerrisError, Resolution, Wheel};
 
     fn values() -> (Vec<Resolution>, Vec<Duration>, Vec<&'static str>) {
         let resolutions = vec![
@@ -190,12 +1008,773 @@ mod tests {
         verify_expire_contains_only_weak_refs(&mut wheel);
     }
 
+    #[test]
+    fn default_and_presets_expire() {
+        let mut wheels = [CopyWheel::default(), CopyWheel::for_network_timeouts()];
+        for wheel in wheels.iter_mut() {
+            wheel.start("a", Duration::from_millis(30));
+            for _ in 0..3 {
+                assert_eq!(0, wheel.expire().len());
+            }
+            assert_eq!(vec!["a"], wheel.expire());
+        }
+
+        let mut wheel = CopyWheel::for_coarse_jobs();
+        wheel.start("a", Duration::from_secs(5));
+        for _ in 0..5 {
+            assert_eq!(0, wheel.expire().len());
+        }
+        assert_eq!(vec!["a"], wheel.expire());
+    }
+
+    #[test]
+    fn collect_and_extend() {
+        let mut wheel: CopyWheel<&'static str> = vec![("a", Duration::from_millis(10))]
+            .into_iter()
+            .collect();
+        wheel.extend(vec![("b", Duration::from_millis(10)), ("c", Duration::from_millis(20))]);
+        assert_eq!(0, wheel.expire().len());
+        let mut expired = wheel.expire();
+        expired.sort();
+        assert_eq!(vec!["a", "b"], expired);
+        assert_eq!(vec!["c"], wheel.expire());
+    }
+
+    #[test]
+    fn interval_timers_repeat_until_stopped() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start_interval("a", Duration::from_millis(20));
+        wheel.start_with_delay("b", Duration::from_millis(40), Duration::from_secs(1));
+        let mut fired = Vec::new();
+        for tick in 1..13 {
+            for key in wheel.expire() {
+                fired.push((tick, key));
+            }
+        }
+        assert_eq!(vec![(3, "a"), (5, "b"), (6, "a"), (9, "a"), (12, "a")], fired);
+        wheel.stop("a");
+        wheel.start("b", Duration::from_millis(10));
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(vec!["b"], wheel.expire());
+        for _ in 0..200 {
+            assert_eq!(0, wheel.expire().len());
+        }
+    }
+
+    #[test]
+    fn restart_only_affects_running_timers() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        assert!(!wheel.restart("a", Duration::from_millis(10)));
+        wheel.start_interval("b", Duration::from_millis(20));
+        wheel.expire();
+        assert!(wheel.restart("b", Duration::from_millis(10)));
+        assert!(!wheel.restart("b", Duration::from_micros(10)));
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(vec!["b"], wheel.expire());
+        // The period is kept
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(vec!["b"], wheel.expire());
+        assert_eq!(0, wheel.expire().len());
+    }
+
+    #[test]
+    fn try_start_reports_misuse() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        assert_eq!(Err(StartError::Zero), wheel.try_start("a", Duration::from_secs(0)));
+        assert_eq!(Err(StartError::TooShort(Duration::from_millis(10))),
+                   wheel.try_start("a", Duration::from_millis(5)));
+        // Durations past the span wait in the overflow list
+        assert_eq!(Ok(()), wheel.try_start("long", Duration::from_secs(60)));
+        assert_eq!(Ok(()), wheel.try_start("a", Duration::from_millis(10)));
+        assert_eq!(Err(StartError::DuplicateKey), wheel.try_start("a", Duration::from_secs(1)));
+        let start = |wheel: &mut CopyWheel<_>| -> Result<(), FerrisError> {
+            wheel.try_start("a", Duration::from_secs(1))?;
+            Ok(())
+        };
+        assert_eq!(Err(FerrisError::AlreadyScheduled), start(&mut wheel));
+        assert_eq!(FerrisError::ZeroDuration, FerrisError::from(StartError::Zero));
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(vec!["a"], wheel.expire());
+        assert!(wheel.advance(5998).is_empty());
+        assert_eq!(vec!["long"], wheel.advance(1));
+    }
+
+    #[test]
+    fn try_stop_and_try_restart_report_unknown_keys() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        assert_eq!(Err(FerrisError::NotFound), wheel.try_stop("a"));
+        assert_eq!(Err(FerrisError::NotFound), wheel.try_restart("a", Duration::from_secs(1)));
+        wheel.start("a", Duration::from_secs(1));
+        assert_eq!(Err(FerrisError::ZeroDuration), wheel.try_restart("a", Duration::from_secs(0)));
+        assert_eq!(Err(FerrisError::DurationOutOfRange(Duration::from_millis(10))),
+                   wheel.try_restart("a", Duration::from_millis(5)));
+        assert_eq!(Ok(()), wheel.try_restart("a", Duration::from_millis(20)));
+        assert!(wheel.try_stop("a").is_ok());
+        assert_eq!(Err(FerrisError::NotFound), wheel.try_stop("a"));
+    }
+
+    #[test]
+    fn expire_iter_reuses_its_buffer() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        for key in 0..10u64 {
+            wheel.start(key, Duration::from_millis(10));
+        }
+        assert_eq!(0, wheel.expire_iter().count());
+        assert_eq!((0..10).collect::<Vec<_>>(), wheel.expire_iter().collect::<Vec<_>>());
+        let capacity = wheel.expired.capacity();
+        wheel.start(1, Duration::from_millis(10));
+        assert_eq!(0, wheel.expire_iter().count());
+        assert_eq!(vec![1], wheel.expire_iter().collect::<Vec<_>>());
+        assert_eq!(capacity, wheel.expired.capacity());
+    }
+
+    #[test]
+    fn expire_into_appends() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start_interval("a", Duration::from_millis(10));
+        wheel.start("b", Duration::from_millis(30));
+        let mut expired = vec!["z"];
+        for _ in 0..4 {
+            wheel.expire_into(&mut expired);
+        }
+        assert_eq!(vec!["z", "a", "b", "a"], expired);
+    }
+
+    #[test]
+    fn len_counts_running_timers() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        assert!(wheel.is_empty());
+        wheel.start("a", Duration::from_millis(50));
+        wheel.start("b", Duration::from_secs(5));
+        wheel.start("c", Duration::from_millis(50));
+        wheel.start("c", Duration::from_millis(50));
+        assert_eq!(3, wheel.len());
+        assert_eq!(2, wheel.len_at(Resolution::TenMs));
+        assert_eq!(1, wheel.len_at(Resolution::Sec));
+        assert_eq!(0, wheel.len_at(Resolution::Min));
+        for _ in 0..6 {
+            wheel.expire();
+        }
+        wheel.start("b", Duration::from_millis(30));
+        assert_eq!(1, wheel.len());
+        assert_eq!(1, wheel.len_at(Resolution::TenMs));
+        assert_eq!(0, wheel.len_at(Resolution::Sec));
+    }
+
+    #[test]
+    fn contains_running_timers() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(10));
+        wheel.start("b", Duration::from_millis(10));
+        wheel.stop("b");
+        assert!(wheel.contains(&"a"));
+        assert!(!wheel.contains(&"b"));
+        wheel.expire();
+        wheel.expire();
+        assert!(!wheel.contains(&"a"));
+    }
+
+    #[test]
+    fn remaining_counts_down() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(30));
+        wheel.start("b", Duration::from_secs(2));
+        assert_eq!(Some(Duration::from_millis(40)), wheel.remaining(&"a"));
+        wheel.expire();
+        assert_eq!(Some(Duration::from_millis(30)), wheel.remaining(&"a"));
+        assert_eq!(Some(Duration::from_millis(2990)), wheel.remaining(&"b"));
+        assert_eq!(None, wheel.remaining(&"c"));
+        wheel.stop("a");
+        assert_eq!(None, wheel.remaining(&"a"));
+    }
+
+    #[test]
+    fn clear_cancels_everything() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(10));
+        wheel.start_interval("b", Duration::from_millis(10));
+        wheel.start("c", Duration::from_secs(1));
+        wheel.clear();
+        assert!(wheel.is_empty());
+        assert!(wheel.wheels.iter().all(|w| w.slots.iter().all(|s| s.entries.is_empty())));
+        wheel.start("a", Duration::from_millis(10));
+        for _ in 0..200 {
+            for key in wheel.expire() {
+                assert_eq!("a", key);
+            }
+        }
+    }
+
+    #[test]
+    fn drain_returns_pending_timers() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(20));
+        wheel.start_interval("b", Duration::from_secs(2));
+        wheel.expire();
+        let mut pending = wheel.drain();
+        pending.sort();
+        assert_eq!(vec![("a", Duration::from_millis(20)), ("b", Duration::from_millis(2990))],
+                   pending);
+        assert!(wheel.is_empty());
+        for _ in 0..400 {
+            assert_eq!(0, wheel.expire().len());
+        }
+    }
+
+    #[test]
+    fn cancel_where_matches_keys() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        for key in &["peer1/a", "peer1/b", "peer2/a", "peer2/b"] {
+            wheel.start(*key, Duration::from_millis(10));
+        }
+        wheel.start_interval("peer3/a", Duration::from_millis(10));
+        assert_eq!(2, wheel.cancel_where(|key| key.starts_with("peer1/")));
+        wheel.retain(|key| !key.ends_with("/b"));
+        assert!(wheel.expire().is_empty());
+        let mut expired = wheel.expire();
+        expired.sort();
+        assert_eq!(vec!["peer2/a", "peer3/a"], expired);
+        wheel.retain(|_| false);
+        assert!(wheel.is_empty());
+        assert!(wheel.periods.is_empty());
+    }
+
+    #[test]
+    fn start_all_loads_many_timers() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start_all((0..1000u64).map(|key| (key, Duration::from_millis(10 * (key % 5 + 1)))));
+        assert_eq!(1000, wheel.len());
+        let mut expired = 0;
+        for tick in 1..7 {
+            let keys = wheel.expire();
+            assert!(keys.iter().all(|key| key % 5 + 2 == tick));
+            expired += keys.len();
+        }
+        assert_eq!(1000, expired);
+    }
+
+    #[test]
+    fn lookups_take_borrowed_keys() {
+        let mut wheel: CopyWheel<String> = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a".to_string(), Duration::from_millis(30));
+        assert!(wheel.contains("a"));
+        assert_eq!(Some(Duration::from_millis(40)), wheel.remaining("a"));
+        assert_eq!(Some(Duration::from_millis(40)), wheel.cancel("a"));
+        assert!(!wheel.contains("a"));
+    }
+
+    #[test]
+    fn duplicate_policies() {
+        use super::super::{DuplicatePolicy, WheelBuilder};
+        let policies = [(DuplicatePolicy::Replace, 2, 4),
+                        (DuplicatePolicy::KeepEarliest, 2, 2),
+                        (DuplicatePolicy::KeepLatest, 4, 4),
+                        (DuplicatePolicy::Reject, 4, 2)];
+        for &(policy, a, b) in policies.iter() {
+            let mut wheel = WheelBuilder::new()
+                .resolutions(vec![Resolution::TenMs, Resolution::Sec])
+                .duplicate_policy(policy)
+                .build_copy();
+            assert_eq!(policy, wheel.duplicate_policy());
+            wheel.start("a", Duration::from_millis(30));
+            wheel.start("a", Duration::from_millis(10));
+            wheel.start("b", Duration::from_millis(10));
+            wheel.start("b", Duration::from_millis(30));
+            let mut fired = Vec::new();
+            for tick in 1..10 {
+                fired.extend(wheel.expire().into_iter().map(|key| (key, tick)));
+            }
+            fired.sort();
+            assert_eq!(vec![("a", a), ("b", b)], fired);
+        }
+    }
+
+    #[test]
+    fn next_expiry_matches_expire() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec, Resolution::Min]);
+        assert_eq!(None, wheel.ticks_until_next_expiry());
+        wheel.start("a", Duration::from_secs(30));
+        wheel.start("b", Duration::from_millis(500));
+        wheel.start("c", Duration::from_millis(200));
+        wheel.stop("c");
+        assert_eq!(Some(51), wheel.ticks_until_next_expiry());
+        assert_eq!(Some(Duration::from_millis(510)), wheel.next_expiry_in());
+        let mut ticks = 0;
+        while ticks < 10_000 {
+            let next = wheel.ticks_until_next_expiry();
+            ticks += 1;
+            let expired = wheel.expire();
+            assert_eq!(next == Some(1), !expired.is_empty());
+            if expired == vec!["a"] {
+                break;
+            }
+        }
+        assert_eq!(3100, ticks);
+        assert_eq!(None, wheel.ticks_until_next_expiry());
+    }
+
+    #[test]
+    fn microsecond_resolutions() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenUs, Resolution::HundredUs,
+                                            Resolution::Ms, Resolution::Sec]);
+        assert_eq!(Duration::from_micros(10), wheel.tick_duration());
+        wheel.start("probe", Duration::from_micros(35));
+        wheel.start("pace", Duration::from_micros(250));
+        wheel.start("rto", Duration::from_millis(2));
+        let mut expired = Vec::new();
+        for tick in 1..400 {
+            expired.extend(wheel.expire().into_iter().map(|key| (tick, key)));
+        }
+        assert_eq!(vec![(4, "probe"), (30, "pace"), (300, "rto")], expired);
+    }
+
+    #[test]
+    fn custom_resolutions() {
+        let frame = Duration::from_millis(16);
+        let mut wheel = CopyWheel::new(vec![Resolution::Custom(frame * 60), Resolution::Custom(frame)]);
+        assert_eq!(frame, wheel.tick_duration());
+        wheel.start("blink", frame * 3);
+        wheel.start("respawn", frame * 60 * 5);
+        let mut expired = Vec::new();
+        for tick in 1..400 {
+            expired.extend(wheel.expire().into_iter().map(|key| (tick, key)));
+        }
+        assert_eq!(vec![(4, "blink"), (360, "respawn")], expired);
+    }
+
+    #[test]
+    #[should_panic(expected = "can't be over a second")]
+    fn custom_resolutions_must_line_up() {
+        let _: CopyWheel<u64> = CopyWheel::new(vec![Resolution::Ms, Resolution::Custom(Duration::from_secs(2))]);
+    }
+
+    #[test]
+    fn day_and_week_resolutions() {
+        let mut wheel = CopyWheel::new(vec![Resolution::Hour, Resolution::Day, Resolution::Week]);
+        let day = Duration::from_secs(24 * 3600);
+        wheel.start("renewal", day * 3 + Duration::from_secs(5 * 3600));
+        wheel.start("billing", day * 10);
+        assert!(wheel.advance(95).is_empty());
+        assert_eq!(vec!["renewal"], wheel.advance(1));
+        assert!(wheel.advance(239).is_empty());
+        assert_eq!(vec!["billing"], wheel.advance(1));
+    }
+
+    #[test]
+    fn gaps_between_resolutions_are_covered() {
+        let day = 24 * 3600;
+        for &(ref resolutions, secs, tick) in &[(vec![Resolution::Hour, Resolution::Week], 14 * day, 3600),
+                                                (vec![Resolution::Min, Resolution::Day], 2 * day, 60)] {
+            let ticks = secs / tick;
+            let mut wheel = CopyWheel::new(resolutions.clone());
+            wheel.start("a", Duration::from_secs(secs));
+            assert!(wheel.advance(ticks).is_empty());
+            let mut precise = CopyWheel::new(resolutions.clone());
+            precise.set_precise(true);
+            precise.start("a", Duration::from_secs(secs));
+            assert!(precise.advance(ticks).is_empty());
+            assert_eq!(vec!["a"], precise.expire());
+        }
+    }
+
+    #[test]
+    fn sized_wheels_extend_the_span() {
+        let mut wheel = CopyWheel::with_sizes(vec![Resolution::TenMs, Resolution::Sec],
+                                              &[(Resolution::Sec, 120)]);
+        wheel.start("a", Duration::from_secs(90));
+        assert!(wheel.overflow.is_empty());
+        assert!(wheel.advance(9099).is_empty());
+        assert_eq!(vec!["a"], wheel.advance(1));
+    }
+
+    #[test]
+    fn rounding_modes() {
+        let expiry = |rounding, millis| {
+            let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+            wheel.set_rounding(rounding);
+            wheel.start("a", Duration::from_millis(millis));
+            (1..400).find(|_| !wheel.expire().is_empty())
+        };
+        assert_eq!(Some(200), expiry(Rounding::Up, 1300));
+        assert_eq!(Some(100), expiry(Rounding::Down, 1300));
+        assert_eq!(Some(100), expiry(Rounding::Nearest, 1300));
+        assert_eq!(Some(200), expiry(Rounding::Nearest, 1700));
+        assert_eq!(Some(1), expiry(Rounding::Down, 15));
+        assert_eq!(Some(2), expiry(Rounding::Nearest, 15));
+    }
+
+    #[test]
+    fn tick_policies() {
+        let expiry = |policy, millis| {
+            let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+            wheel.set_precise(true);
+            wheel.set_tick_policy(policy);
+            wheel.start("a", Duration::from_millis(millis));
+            (1..400).find(|_| !wheel.expire().is_empty())
+        };
+        assert_eq!(Some(4), expiry(TickPolicy::NoEarlier, 30));
+        assert_eq!(Some(3), expiry(TickPolicy::NoLater, 30));
+        assert_eq!(Some(1), expiry(TickPolicy::NoLater, 15));
+        assert_eq!(Some(131), expiry(TickPolicy::NoEarlier, 1300));
+        assert_eq!(Some(130), expiry(TickPolicy::NoLater, 1300));
+
+        // Wheels that aren't precise drop the extra slot in whichever wheel the timer lands in
+        let expiry = |policy, millis| {
+            let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+            wheel.set_tick_policy(policy);
+            wheel.start("a", Duration::from_millis(millis));
+            (1..400).find(|_| !wheel.expire().is_empty())
+        };
+        assert_eq!(Some(4), expiry(TickPolicy::NoEarlier, 30));
+        assert_eq!(Some(3), expiry(TickPolicy::NoLater, 30));
+        assert_eq!(Some(200), expiry(TickPolicy::NoEarlier, 1300));
+        assert_eq!(Some(100), expiry(TickPolicy::NoLater, 1300));
+    }
+
+    #[test]
+    fn elapsed_time_carries_over() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(50));
+        // Six 16ms frames make up 9 ticks, with 6ms left over
+        let mut expired = Vec::new();
+        for frame in 1..7 {
+            expired.extend(wheel.expire_elapsed(Duration::from_millis(16)).into_iter().map(|key| (frame, key)));
+        }
+        assert_eq!(vec![(4, "a")], expired);
+        assert_eq!(9, wheel.tick);
+        assert_eq!(Duration::from_millis(6), wheel.leftover);
+    }
+
+    #[test]
+    fn advance_matches_ticking() {
+        let new = || {
+            let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec, Resolution::Min]);
+            for &(key, millis) in &[("a", 50), ("b", 900), ("c", 1500), ("d", 70_000), ("e", 80)] {
+                wheel.start(key, Duration::from_millis(millis));
+            }
+            wheel.stop("e");
+            wheel
+        };
+        let mut ticked = new();
+        let mut advanced = new();
+        for &ticks in &[0, 1, 99, 10_000] {
+            let mut expected = Vec::new();
+            for _ in 0..ticks {
+                expected.extend(ticked.expire());
+            }
+            expected.sort();
+            let mut expired = advanced.advance(ticks);
+            expired.sort();
+            assert_eq!(expected, expired);
+            ticked.start("f", Duration::from_millis(20));
+            advanced.start("f", Duration::from_millis(20));
+            assert_eq!(ticked.remaining(&"f"), advanced.remaining(&"f"));
+        }
+
+        advanced.start_interval("g", Duration::from_millis(10));
+        assert_eq!(vec!["g", "f"], advanced.advance(5));
+        assert_eq!(vec!["g"], advanced.advance(2));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn expire_until_follows_the_clock() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        let start = wheel.last_tick_at();
+        wheel.start("a", Duration::from_millis(30));
+        wheel.start("b", Duration::from_millis(100));
+        assert!(wheel.expire_until(start + Duration::from_millis(39)).is_empty());
+        assert_eq!(start + Duration::from_millis(30), wheel.last_tick_at());
+        assert_eq!(vec!["a"], wheel.expire_until(start + Duration::from_millis(45)));
+        wheel.expire();
+        assert!(wheel.expire_until(start + Duration::from_millis(45)).is_empty());
+        assert_eq!(vec!["b"], wheel.expire_until(start + Duration::from_secs(1)));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn expire_at_catches_up_late_calls() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        let start = wheel.last_tick_at();
+        wheel.start("a", Duration::from_millis(20));
+        wheel.start("b", Duration::from_millis(50));
+        // Called several ticks late, both timers come due at once
+        let mut expired = wheel.expire_at(start + Duration::from_millis(75));
+        expired.sort();
+        assert_eq!(vec!["a", "b"], expired);
+        assert_eq!(start + Duration::from_millis(70), wheel.last_tick_at());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn wheels_without_a_clock_start_from_the_first_expire_until() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        // As created on wasm32-unknown-unknown, where the clock can't be read
+        wheel.origin = None;
+        wheel.start("a", Duration::from_millis(30));
+        let start = std::time::Instant::now();
+        assert!(wheel.expire_until(start).is_empty());
+        assert_eq!(start, wheel.last_tick_at());
+        assert_eq!(vec!["a"], wheel.expire_until(start + Duration::from_millis(40)));
+    }
+
+    #[test]
+    fn paused_wheels_keep_their_timers() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(20));
+        wheel.expire();
+        wheel.pause();
+        assert!(wheel.is_paused());
+        wheel.start("b", Duration::from_millis(10));
+        for _ in 0..10 {
+            assert!(wheel.expire().is_empty());
+        }
+        assert!(wheel.advance(10).is_empty());
+        assert_eq!(Some(Duration::from_millis(20)), wheel.remaining(&"a"));
+        wheel.resume();
+        assert!(wheel.expire().is_empty());
+        let mut expired = wheel.expire();
+        expired.sort();
+        assert_eq!(vec!["a", "b"], expired);
+    }
+
+    #[test]
+    fn reschedule_leaves_no_stale_entries() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        let entries = |wheel: &CopyWheel<&str>| -> usize {
+            wheel.wheels.iter().flat_map(|wheel| &wheel.slots).map(|slot| slot.entries.len()).sum()
+        };
+        assert!(!wheel.reschedule("a", Duration::from_millis(10)));
+        wheel.start("a", Duration::from_millis(10));
+        wheel.start("b", Duration::from_millis(10));
+        for millis in &[20, 3000, 50, 50, 30] {
+            assert!(wheel.reschedule("a", Duration::from_millis(*millis)));
+        }
+        assert!(!wheel.reschedule("a", Duration::from_micros(10)));
+        assert_eq!(2, entries(&wheel));
+        wheel.expire();
+        assert_eq!(vec!["b"], wheel.expire());
+        wheel.expire();
+        assert_eq!(vec!["a"], wheel.expire());
+        assert_eq!(0, entries(&wheel));
+    }
+
+    #[test]
+    fn extend_by_pushes_deadlines_out() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        assert!(!wheel.extend_by("a", Duration::from_millis(10)));
+        wheel.start("a", Duration::from_millis(30));
+        wheel.expire();
+        assert!(wheel.extend_by("a", Duration::from_millis(25)));
+        assert_eq!(Some(Duration::from_millis(50)), wheel.remaining(&"a"));
+        assert!(wheel.extend_by("a", Duration::from_millis(5)));
+        for _ in 0..4 {
+            assert!(wheel.expire().is_empty());
+        }
+        assert_eq!(vec!["a"], wheel.expire());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn poll_timeout_waits_for_the_next_timer() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        let start = wheel.last_tick_at();
+        assert_eq!(None, wheel.poll_timeout(start));
+        wheel.start(7usize, Duration::from_millis(30));
+        wheel.start(8usize, Duration::from_secs(2));
+        assert_eq!(Some(Duration::from_millis(35)),
+                   wheel.poll_timeout(start + Duration::from_millis(5)));
+        let now = start + Duration::from_millis(40);
+        assert_eq!(Some(Duration::from_secs(0)), wheel.poll_timeout(now));
+        assert_eq!(vec![7], wheel.expire_until(now));
+        assert_eq!(Some(Duration::from_millis(2960)), wheel.poll_timeout(now));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn deadlines_round_up_to_the_next_tick() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        let start = wheel.last_tick_at();
+        wheel.start_at("a", start + Duration::from_millis(25));
+        wheel.start_at("late", start - Duration::from_millis(100));
+        wheel.start_at("b", start + Duration::from_secs(2));
+        assert_eq!(Some(start + Duration::from_millis(30)), wheel.expires_at(&"a"));
+        assert!(wheel.restart_at("b", start + Duration::from_millis(40)));
+        assert!(!wheel.restart_at("c", start));
+
+        assert!(wheel.expire().is_empty());
+        assert_eq!(vec!["late"], wheel.expire());
+        assert_eq!(vec!["a"], wheel.expire());
+        assert_eq!(vec!["b"], wheel.expire());
+    }
+
+    #[test]
+    fn slack_groups_timers_into_windows() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.set_slack(Duration::from_millis(50));
+        wheel.start("a", Duration::from_millis(10));
+        wheel.start("b", Duration::from_millis(30));
+        wheel.start("c", Duration::from_millis(60));
+        wheel.start_interval("d", Duration::from_millis(20));
+        let mut fired = Vec::new();
+        for tick in 1..16 {
+            let mut expired = wheel.expire();
+            expired.sort();
+            if !expired.is_empty() {
+                fired.push((tick, expired));
+            }
+        }
+        assert_eq!(vec![(5, vec!["a", "b", "d"]), (10, vec!["c", "d"]), (15, vec!["d"])], fired);
+    }
+
+    #[test]
+    fn full_wheels_turn_new_timers_away() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.set_max_timers(Some(2));
+        wheel.start("a", Duration::from_millis(10));
+        assert_eq!(Ok(()), wheel.try_start("b", Duration::from_millis(20)));
+        assert!(wheel.is_full());
+        assert_eq!(Err(StartError::Full), wheel.try_start("c", Duration::from_millis(10)));
+        wheel.start("c", Duration::from_millis(10));
+        wheel.start_interval("d", Duration::from_millis(10));
+        assert!(!wheel.contains(&"c") && !wheel.contains(&"d"));
+        // Running timers can still be restarted
+        wheel.start("a", Duration::from_millis(20));
+        assert_eq!(2, wheel.len());
+
+        wheel.cancel(&"b");
+        assert!(!wheel.is_full());
+        wheel.start("c", Duration::from_millis(10));
+        assert!(wheel.contains(&"c"));
+    }
+
+    #[test]
+    fn cancel_returns_remaining_time() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(50));
+        wheel.expire();
+        wheel.expire();
+        assert_eq!(Some(Duration::from_millis(40)), wheel.cancel("a"));
+        assert_eq!(None, wheel.cancel("a"));
+        for _ in 0..6 {
+            assert_eq!(0, wheel.expire().len());
+        }
+    }
+
+    #[test]
+    fn conversions_preserve_deadlines() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(50));
+        wheel.start("b", Duration::from_millis(20));
+        wheel.start("c", Duration::from_millis(20));
+        wheel.stop("c");
+        wheel.expire();
+        let mut wheel = wheel.into_alloc();
+        wheel.expire();
+        assert_eq!(vec!["b"], wheel.expire());
+        let mut wheel = CopyWheel::from(wheel);
+        wheel.expire();
+        wheel.expire();
+        assert_eq!(vec!["a"], wheel.expire());
+    }
+
+    #[test]
+    fn restart_ignores_stale_slot_entry() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(20));
+        wheel.stop("a");
+        wheel.start("a", Duration::from_millis(50));
+        wheel.start("b", Duration::from_millis(50));
+        wheel.start("b", Duration::from_millis(20));
+        for i in 1..7 {
+            let expired = wheel.expire();
+            match i {
+                3 => assert_eq!(vec!["b"], expired),
+                6 => assert_eq!(vec!["a"], expired),
+                _ => assert_eq!(0, expired.len())
+            }
+        }
+    }
+
+    #[test]
+    fn into_iter_yields_remaining_time() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(50));
+        wheel.start("b", Duration::from_secs(3));
+        wheel.start("c", Duration::from_millis(50));
+        wheel.stop("c");
+        wheel.expire();
+        let mut remaining: Vec<_> = wheel.into_iter().collect();
+        remaining.sort();
+        assert_eq!(vec![("a", Duration::from_millis(50)), ("b", Duration::from_millis(3990))],
+                   remaining);
+    }
+
+    #[test]
+    fn precise_timers_cascade_to_their_tick() {
+        let resolutions = vec![Resolution::TenMs, Resolution::HundredMs, Resolution::Sec];
+        let mut wheel = CopyWheel::new(resolutions);
+        wheel.set_precise(true);
+        wheel.start("a", Duration::from_millis(1300));
+        wheel.start("b", Duration::from_millis(250));
+        wheel.start_interval("c", Duration::from_millis(1050));
+        wheel.expire();
+        wheel.start("d", Duration::from_millis(90));
+        assert_eq!(Some(Duration::from_millis(1300)), wheel.remaining(&"a"));
+
+        let mut fired = Vec::new();
+        for tick in 2..300 {
+            for key in wheel.expire() {
+                fired.push((key, tick));
+            }
+        }
+        assert_eq!(vec![("d", 11), ("b", 26), ("c", 106), ("a", 131), ("c", 212)], fired);
+
+        // Timers started after turning the mode off are rounded as usual
+        wheel.set_precise(false);
+        wheel.start("e", Duration::from_millis(1300));
+        assert_eq!(Some(Duration::from_millis(1010)), wheel.remaining(&"e"));
+        assert_eq!(Some(Duration::from_millis(190)), wheel.remaining(&"c"));
+    }
+
+    #[test]
+    fn long_timers_wait_in_the_overflow_list() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_secs(90));
+        wheel.start("b", Duration::from_secs(3600));
+        wheel.start("c", Duration::from_secs(120));
+        wheel.stop("c");
+        assert_eq!(Some(Duration::from_millis(90_010)), wheel.remaining(&"a"));
+        assert_eq!(Some(9001), wheel.ticks_until_next_expiry());
+
+        // Once in range the timer is rounded to the second wheel like any other
+        let mut ticks = 1;
+        while wheel.expire().is_empty() {
+            ticks += 1;
+        }
+        assert_eq!(9100, ticks);
+        // Catching up past a timer still in the overflow list expires it
+        assert_eq!(vec!["b"], wheel.advance(360_000));
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn overflow_timers_due_on_the_next_tick_still_fire() {
+        let mut wheel = CopyWheel::default();
+        wheel.start("a", Duration::from_secs(25 * 3600));
+        let ticks = wheel.ticks_until_next_expiry().unwrap();
+        assert!(wheel.advance(ticks - 1).is_empty());
+        assert_eq!(vec!["a"], wheel.expire());
+        assert!(wheel.is_empty());
+    }
+
     fn verify_wheel_and_slot_position(wheel: &mut CopyWheel<&'static str>) {
         let (_, _, keys) = values();
         let expected_slots = [6, 4, 2, 6, 6, 6];
         for i in 0..wheel.wheels.len() {
             for j in 0..wheel.wheels[i].slots.len() {
-                let ref entries = wheel.wheels[i].slots[j].entries;
+                let entries = &wheel.wheels[i].slots[j].entries;
                 if j == expected_slots[i] {
                     assert_eq!(1, entries.len());
                     assert_eq!(keys[i], entries[0]);
@@ -238,7 +1817,7 @@ mod tests {
             if expected_ticks.contains(&i) {
                 assert_eq!(1, expired.len());
                 assert_eq!(keys[match_count], expired[0]);
-                match_count = match_count + 1;
+                match_count += 1;
             } else  {
                 assert_eq!(0, expired.len());
             }
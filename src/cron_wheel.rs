@@ -0,0 +1,403 @@
+use std::hash::Hash;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use super::{CopyWheel, FerrisError, Wheel, Resolution, Ticks};
+use super::time_until;
+
+/// When a `CronWheel` job runs, as the minutes, hours, days of the month, months and days of the
+/// week it matches, in UTC.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct CronSchedule {
+    // Bit n is set if the field matches n
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Whether the day of the month or week was restricted, since a job with both runs on either
+    any_day: bool,
+    any_weekday: bool
+}
+
+impl CronSchedule {
+    /// Parse a five field cron expression: minute, hour, day of the month, month and day of the
+    /// week, where Sunday is 0 or 7.
+    ///
+    /// Each field is `*` or a comma separated list of numbers and ranges such as `1-5`, any of
+    /// which may have a step, as in `*/15` or `0-30/10`. Names such as `MON` aren't supported.
+    pub fn parse(expression: &str) -> Result<CronSchedule, FerrisError> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(FerrisError::InvalidConfig("a cron expression has five fields"));
+        }
+        let mut weekdays = parse_field(fields[4], 0, 7)?;
+        // Sunday may be written as 7
+        if weekdays & 1 << 7 != 0 {
+            weekdays = (weekdays | 1) & !(1 << 7);
+        }
+        Ok(CronSchedule {
+            minutes: parse_field(fields[0], 0, 59)?,
+            hours: parse_field(fields[1], 0, 23)?,
+            days: parse_field(fields[2], 1, 31)?,
+            months: parse_field(fields[3], 1, 12)?,
+            weekdays,
+            any_day: fields[2].starts_with('*'),
+            any_weekday: fields[4].starts_with('*')
+        })
+    }
+
+    /// A schedule that runs every day at `hour:minute` UTC
+    ///
+    /// # Panics
+    ///
+    /// Panics if `hour` is over 23 or `minute` over 59.
+    pub fn daily(hour: u32, minute: u32) -> CronSchedule {
+        assert!(hour < 24 && minute < 60, "{}:{} is not a time of day", hour, minute);
+        let every_minute = CronSchedule::parse("* * * * *").expect("a valid expression");
+        CronSchedule {
+            minutes: 1 << minute,
+            hours: 1 << hour,
+            ..every_minute
+        }
+    }
+
+    /// The first time the schedule matches after `time`, or `None` if it never matches, such as
+    /// on the 30th of February
+    pub fn next_after(&self, time: SystemTime) -> Option<SystemTime> {
+        let secs = time.duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0);
+        let start = (secs / 60 + 1) * 60;
+        let first_day = start / 86_400;
+        // A schedule for the 29th of February may not match for eight years
+        for day in first_day..first_day + 366 * 9 {
+            if !self.matches_day(day) {
+                continue;
+            }
+            for hour in (0..24).filter(|hour| self.hours & 1 << hour != 0) {
+                for minute in (0..60).filter(|minute| self.minutes & 1 << minute != 0) {
+                    let at = day * 86_400 + hour * 3600 + minute * 60;
+                    if at >= start {
+                        return Some(UNIX_EPOCH + Duration::from_secs(at));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, day: u64) -> bool {
+        let (month, date) = month_and_date(day);
+        if self.months & 1 << month == 0 {
+            return false;
+        }
+        // The epoch was a Thursday
+        let on_date = self.days & 1 << date != 0;
+        let on_weekday = self.weekdays & 1 << ((day + 4) % 7) != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => on_date || on_weekday,
+            _ => on_date && on_weekday
+        }
+    }
+}
+
+// Parse a cron field into a bit set of the values from `min` to `max` it matches
+fn parse_field(field: &str, min: u64, max: u64) -> Result<u64, FerrisError> {
+    let invalid = FerrisError::InvalidConfig("a cron field is out of range or malformed");
+    let mut bits = 0;
+    for part in field.split(',') {
+        let (range, step) = match part.find('/') {
+            Some(slash) => {
+                let step: u64 = part[slash + 1..].parse().map_err(|_| invalid)?;
+                (&part[..slash], step)
+            },
+            None => (part, 1)
+        };
+        let (low, high) = if range == "*" {
+            (min, max)
+        } else {
+            match range.find('-') {
+                Some(dash) => {
+                    let low = range[..dash].parse().map_err(|_| invalid)?;
+                    (low, range[dash + 1..].parse().map_err(|_| invalid)?)
+                },
+                // A single value with a step runs to the end of the field, as in `5/15`
+                None => {
+                    let value = range.parse().map_err(|_| invalid)?;
+                    (value, if step > 1 { max } else { value })
+                }
+            }
+        };
+        if step == 0 || low < min || high > max || low > high {
+            return Err(invalid);
+        }
+        for value in (low..high + 1).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+    Ok(bits)
+}
+
+// Return the month, from 1, and the day of the month of a day counted from the epoch
+//
+// This is the civil from days algorithm of Howard Hinnant's chrono date library, with the year
+// left out.
+fn month_and_date(day: u64) -> (u64, u64) {
+    let doe = (day + 719_468) % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let date = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    (month, date)
+}
+
+struct Job {
+    schedule: Option<CronSchedule>,
+    next: SystemTime,
+    // Whether the job runs at a time on the clock rather than after a duration
+    wall: bool
+}
+
+/// A wheel of jobs that run on a calendar schedule, alongside one shot timers that run after a
+/// duration.
+///
+/// Each scheduled job is armed on a `CopyWheel` for its next occurrence and re-armed for the one
+/// after each time it fires. The wheel runs in precise mode, so a job fires on the first tick at
+/// or after its occurrence, even from the hour wheel. Occurrences are counted from the wheel's own
+/// clock, the time it was created plus a tick for each call to `expire`, so the wheel must be
+/// driven by calling `expire` at the maximum resolution. A jump in the system clock doesn't move
+/// jobs that are already armed until `sync_clock` is called.
+pub struct CronWheel<K: Eq + Hash + Debug + Clone> {
+    start: SystemTime,
+    ticks: u64,
+    jobs: HashMap<K, Job>,
+    wheel: CopyWheel<K>
+}
+
+impl<K: Eq + Hash + Debug + Clone> CronWheel<K> {
+    /// Create a wheel whose clock starts now. Jobs more than a few hours out usually want
+    /// `Resolution::Hour` in the hierarchy, so they don't wait in the overflow list.
+    pub fn new(resolutions: Vec<Resolution>) -> CronWheel<K> {
+        CronWheel::starting_at(resolutions, SystemTime::now())
+    }
+
+    /// Create a wheel whose clock starts at `start`, for tests and simulations
+    pub fn starting_at(resolutions: Vec<Resolution>, start: SystemTime) -> CronWheel<K> {
+        let mut wheel = CopyWheel::new(resolutions);
+        wheel.set_precise(true);
+        CronWheel {
+            start,
+            ticks: 0,
+            jobs: HashMap::new(),
+            wheel
+        }
+    }
+
+    /// Run `key` at each occurrence of `schedule`, replacing any job or timer it had. Returns the
+    /// first occurrence, or `None` if the schedule never matches and nothing was armed.
+    pub fn schedule(&mut self, key: K, schedule: CronSchedule) -> Option<SystemTime> {
+        self.cancel(&key);
+        let next = schedule.next_after(self.now())?;
+        self.arm(key, Some(schedule), next, true);
+        Some(next)
+    }
+
+    /// Run `key` once at `time` on the clock, such as a Unix timestamp as
+    /// `UNIX_EPOCH + Duration::from_secs(timestamp)`, replacing any job or timer it had. A time
+    /// that has passed runs on the next tick.
+    pub fn schedule_at(&mut self, key: K, time: SystemTime) {
+        self.cancel(&key);
+        self.arm(key, None, time, true);
+    }
+
+    /// Run `key` once after `time`, replacing any job or timer it had
+    pub fn start(&mut self, key: K, time: Duration) {
+        self.cancel(&key);
+        let next = self.now() + time.max(self.wheel.tick_duration());
+        self.arm(key, None, next, false);
+    }
+
+    /// Move the wheel's clock to `now` if it has drifted from it by more than `tolerance`,
+    /// returning whether it did.
+    ///
+    /// Call this with `SystemTime::now()` every so often, or when the system reports that its
+    /// clock was changed, so that jobs due at a time on the clock still run at that time after a
+    /// jump. Each of them is re-armed for the time it was due, and any whose time was skipped
+    /// runs on the next tick. After a jump back, scheduled jobs are re-armed for their first
+    /// occurrence after `now` instead, so they don't wait out the time the clock lost. Timers
+    /// started for a duration keep the time they had left.
+    pub fn sync_clock(&mut self, now: SystemTime, tolerance: Duration) -> bool {
+        let old = self.now();
+        let skew = now.duration_since(old).unwrap_or_else(|behind| behind.duration());
+        if skew <= tolerance {
+            return false;
+        }
+        self.start = now;
+        self.ticks = 0;
+        let tick = self.wheel.tick_duration();
+        for (key, job) in self.jobs.iter_mut() {
+            if job.wall {
+                if now < old {
+                    if let Some(next) = job.schedule.and_then(|schedule| schedule.next_after(now)) {
+                        job.next = next;
+                    }
+                }
+                let wait = job.next.duration_since(now).unwrap_or_default();
+                self.wheel.start(key.clone(), time_until(wait, tick));
+            } else if now > old {
+                job.next += skew;
+            } else {
+                job.next -= skew;
+            }
+        }
+        true
+    }
+
+    /// Stop a job or timer. Returns false if the key had neither.
+    pub fn cancel(&mut self, key: &K) -> bool {
+        if self.jobs.remove(key).is_none() {
+            return false;
+        }
+        self.wheel.cancel(key);
+        true
+    }
+
+    /// When a key next fires
+    pub fn next_occurrence(&self, key: &K) -> Option<SystemTime> {
+        self.jobs.get(key).map(|job| job.next)
+    }
+
+    /// The wheel's idea of the current time: when it was created plus the ticks since
+    pub fn now(&self) -> SystemTime {
+        self.start + Ticks(self.ticks).to_duration(self.wheel.tick_duration())
+    }
+
+    /// The number of jobs and timers
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+
+    /// How often `expire` must be called
+    pub fn tick_duration(&self) -> Duration {
+        self.wheel.tick_duration()
+    }
+
+    /// Advance the wheel by one tick and return the keys that fired, re-arming scheduled jobs
+    /// for their next occurrence
+    pub fn expire(&mut self) -> Vec<K> {
+        self.ticks += 1;
+        let now = self.now();
+        let fired = self.wheel.expire();
+        for key in &fired {
+            let schedule = self.jobs.remove(key).and_then(|job| job.schedule);
+            if let Some(schedule) = schedule {
+                if let Some(next) = schedule.next_after(now) {
+                    self.arm(key.clone(), Some(schedule), next, true);
+                }
+            }
+        }
+        fired
+    }
+
+    fn arm(&mut self, key: K, schedule: Option<CronSchedule>, next: SystemTime, wall: bool) {
+        let wait = next.duration_since(self.now()).unwrap_or_default();
+        self.wheel.start(key.clone(), time_until(wait, self.wheel.tick_duration()));
+        self.jobs.insert(key, Job {
+            schedule,
+            next,
+            wall
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+    use super::super::Resolution;
+
+    // Midnight UTC on Saturday the 1st of June 2024
+    const JUNE_1_2024: u64 = 1_717_200_000;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn schedules_find_their_next_occurrence() {
+        let june = at(JUNE_1_2024);
+        let weekdays = CronSchedule::parse("30 9 * * 1-5").unwrap();
+        assert_eq!(Some(at(JUNE_1_2024 + 2 * 86_400 + 9 * 3600 + 1800)), weekdays.next_after(june));
+        let quarter = CronSchedule::parse("*/15 * * * *").unwrap();
+        assert_eq!(Some(at(JUNE_1_2024 + 900)), quarter.next_after(at(JUNE_1_2024 + 1)));
+        // Either the 15th or a Wednesday when both are given
+        let either = CronSchedule::parse("0 0 15 * 3").unwrap();
+        assert_eq!(Some(at(JUNE_1_2024 + 4 * 86_400)), either.next_after(june));
+        let leap = CronSchedule::parse("0 0 29 2 *").unwrap();
+        assert_eq!(Some(at(1_835_395_200)), leap.next_after(june));
+        assert_eq!(Some(CronSchedule::daily(9, 30)), CronSchedule::parse("30 9 * * *").ok());
+        assert_eq!(None, CronSchedule::parse("0 0 30 2 *").unwrap().next_after(june));
+        assert!(CronSchedule::parse("60 * * * *").is_err());
+        assert!(CronSchedule::parse("* * *").is_err());
+        assert!(CronSchedule::parse("*/0 * * * *").is_err());
+    }
+
+    #[test]
+    fn jobs_are_rearmed_after_they_fire() {
+        let mut wheel = CronWheel::starting_at(vec![Resolution::Sec, Resolution::Min, Resolution::Hour],
+                                               at(JUNE_1_2024 - 2));
+        let midnight = wheel.schedule("rotate", CronSchedule::daily(0, 0));
+        assert_eq!(Some(at(JUNE_1_2024)), midnight);
+        wheel.start("once", Duration::from_secs(1));
+        assert!(wheel.expire().is_empty());
+        let mut fired = wheel.expire();
+        fired.sort();
+        assert_eq!(vec!["once", "rotate"], fired);
+        assert_eq!(Some(at(JUNE_1_2024 + 86_400)), wheel.next_occurrence(&"rotate"));
+        assert_eq!(1, wheel.len());
+        let mut ticks = 0;
+        while wheel.expire().is_empty() {
+            ticks += 1;
+        }
+        assert_eq!(86_399, ticks);
+    }
+
+    #[test]
+    fn clock_jumps_rearm_jobs_due_on_the_clock() {
+        let mut wheel = CronWheel::starting_at(vec![Resolution::Sec, Resolution::Min], at(JUNE_1_2024));
+        wheel.schedule_at("backup", at(JUNE_1_2024 + 60));
+        wheel.start("poll", Duration::from_secs(30));
+        assert!(!wheel.sync_clock(at(JUNE_1_2024), Duration::from_secs(1)));
+
+        // The clock jumps 50 seconds ahead
+        assert!(wheel.sync_clock(at(JUNE_1_2024 + 50), Duration::from_secs(1)));
+        assert_eq!(Some(at(JUNE_1_2024 + 80)), wheel.next_occurrence(&"poll"));
+        let mut fired = Vec::new();
+        for tick in 1..40 {
+            fired.extend(wheel.expire().into_iter().map(|key| (tick, key)));
+        }
+        assert_eq!(vec![(10, "backup"), (30, "poll")], fired);
+    }
+
+    #[test]
+    fn clock_jumps_back_recompute_scheduled_jobs() {
+        let mut wheel = CronWheel::starting_at(vec![Resolution::Sec, Resolution::Min, Resolution::Hour],
+                                               at(JUNE_1_2024 + 3600));
+        wheel.schedule("report", CronSchedule::daily(0, 30));
+        assert_eq!(Some(at(JUNE_1_2024 + 86_400 + 1800)), wheel.next_occurrence(&"report"));
+
+        // The clock jumps back two hours, to 23:00 the day before
+        assert!(wheel.sync_clock(at(JUNE_1_2024 - 3600), Duration::from_secs(1)));
+        assert_eq!(Some(at(JUNE_1_2024 + 1800)), wheel.next_occurrence(&"report"));
+        let mut ticks = 1;
+        while wheel.expire().is_empty() {
+            ticks += 1;
+        }
+        assert_eq!(5400, ticks);
+        assert_eq!(Some(at(JUNE_1_2024 + 86_400 + 1800)), wheel.next_occurrence(&"report"));
+    }
+}
@@ -0,0 +1,194 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::time::Duration;
+use alloc::vec::Vec;
+use collections::HashMap;
+use super::{CopyWheel, Resolution, Wheel};
+
+/// Emits a key once calls for it have stopped arriving for a window, such as saving a document
+/// once the user stops typing.
+///
+/// Every call to `debounce` restarts the key's timer, and `expire` returns the keys whose timers
+/// ran out. The debouncer must be driven by calling `expire` at the maximum resolution. Windows
+/// shorter than a tick are rounded up to a tick.
+pub struct Debouncer<K: Eq + Hash + Debug + Clone> {
+    wheel: CopyWheel<K>
+}
+
+impl<K: Eq + Hash + Debug + Clone> Debouncer<K> {
+    /// Create a debouncer whose windows are tracked by a wheel with the given resolutions
+    pub fn new(resolutions: Vec<Resolution>) -> Debouncer<K> {
+        let mut wheel = CopyWheel::new(resolutions);
+        wheel.set_precise(true);
+        Debouncer {
+            wheel
+        }
+    }
+
+    /// Record a call for `key`, delaying its emission until `window` passes without another
+    pub fn debounce(&mut self, key: K, window: Duration) {
+        let window = window.max(self.wheel.tick_duration());
+        self.wheel.start(key, window);
+    }
+
+    /// Drop a pending key without emitting it. Returns false if it wasn't pending.
+    pub fn cancel(&mut self, key: &K) -> bool {
+        self.wheel.cancel(key).is_some()
+    }
+
+    /// Whether a key is waiting for its window to pass
+    pub fn is_pending(&self, key: &K) -> bool {
+        self.wheel.contains(key)
+    }
+
+    /// The number of pending keys
+    pub fn len(&self) -> usize {
+        self.wheel.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wheel.is_empty()
+    }
+
+    /// How often `expire` must be called
+    pub fn tick_duration(&self) -> Duration {
+        self.wheel.tick_duration()
+    }
+
+    /// Advance the wheel by one tick and return the keys whose calls have settled
+    pub fn expire(&mut self) -> Vec<K> {
+        self.wheel.expire()
+    }
+}
+
+struct Window {
+    length: Duration,
+    // Whether a call was held back during the window
+    trailing: bool
+}
+
+/// Emits a key at most once per window, such as handling scroll or resize events.
+///
+/// The first call for a key is let through and opens a window, and calls during the window are
+/// held back. If any were, the key is emitted once more when the window closes, which opens a
+/// new window, so the last call is never lost. The throttler must be driven by calling `expire`
+/// at the maximum resolution. Windows shorter than a tick are rounded up to a tick.
+pub struct Throttler<K: Eq + Hash + Debug + Clone> {
+    windows: HashMap<K, Window>,
+    wheel: CopyWheel<K>
+}
+
+impl<K: Eq + Hash + Debug + Clone> Throttler<K> {
+    /// Create a throttler whose windows are tracked by a wheel with the given resolutions
+    pub fn new(resolutions: Vec<Resolution>) -> Throttler<K> {
+        let mut wheel = CopyWheel::new(resolutions);
+        wheel.set_precise(true);
+        Throttler {
+            windows: HashMap::new(),
+            wheel
+        }
+    }
+
+    /// Record a call for `key`, returning true if it goes through now, or false if it is held
+    /// back until the current window closes. `window` only applies when a new window opens.
+    pub fn throttle(&mut self, key: K, window: Duration) -> bool {
+        if let Some(open) = self.windows.get_mut(&key) {
+            open.trailing = true;
+            return false;
+        }
+        let length = window.max(self.wheel.tick_duration());
+        self.wheel.start(key.clone(), length);
+        self.windows.insert(key, Window {
+            length,
+            trailing: false
+        });
+        true
+    }
+
+    /// Close a key's window, dropping any call held back. Returns false if it had no window open.
+    pub fn reset(&mut self, key: &K) -> bool {
+        if self.windows.remove(key).is_none() {
+            return false;
+        }
+        self.wheel.cancel(key);
+        true
+    }
+
+    /// The number of keys with an open window
+    pub fn len(&self) -> usize {
+        self.windows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.windows.is_empty()
+    }
+
+    /// How often `expire` must be called
+    pub fn tick_duration(&self) -> Duration {
+        self.wheel.tick_duration()
+    }
+
+    /// Advance the wheel by one tick and return the keys whose held back calls go through now
+    pub fn expire(&mut self) -> Vec<K> {
+        let mut emitted = Vec::new();
+        for key in self.wheel.expire() {
+            let length = match self.windows.get_mut(&key) {
+                Some(open) if open.trailing => {
+                    open.trailing = false;
+                    open.length
+                },
+                _ => {
+                    self.windows.remove(&key);
+                    continue;
+                }
+            };
+            self.wheel.start(key.clone(), length);
+            emitted.push(key);
+        }
+        emitted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    #[test]
+    fn debounced_keys_wait_for_calls_to_stop() {
+        let mut debouncer = Debouncer::new(vec![Resolution::TenMs, Resolution::Sec]);
+        let mut emitted = Vec::new();
+        for tick in 1..12 {
+            if tick < 5 {
+                debouncer.debounce("search", Duration::from_millis(30));
+            }
+            if tick == 1 {
+                debouncer.debounce("save", Duration::from_millis(20));
+                debouncer.debounce("draft", Duration::from_millis(20));
+                assert!(debouncer.cancel(&"draft"));
+            }
+            emitted.extend(debouncer.expire().into_iter().map(|key| (tick, key)));
+        }
+        assert_eq!(vec![(3, "save"), (7, "search")], emitted);
+        assert!(debouncer.is_empty());
+    }
+
+    #[test]
+    fn throttled_keys_go_through_once_per_window() {
+        let mut throttler = Throttler::new(vec![Resolution::TenMs, Resolution::Sec]);
+        assert!(throttler.throttle("scroll", Duration::from_millis(30)));
+        assert!(!throttler.throttle("scroll", Duration::from_millis(30)));
+        assert!(!throttler.throttle("scroll", Duration::from_millis(30)));
+        let mut emitted = Vec::new();
+        for tick in 1..12 {
+            emitted.extend(throttler.expire().into_iter().map(|key| (tick, key)));
+        }
+        // The held back calls go through once, and the window that opens closes quietly
+        assert_eq!(vec![(4, "scroll")], emitted);
+        assert!(throttler.is_empty());
+        assert!(throttler.throttle("scroll", Duration::from_millis(30)));
+        assert!(throttler.reset(&"scroll"));
+        assert!(throttler.throttle("scroll", Duration::from_millis(30)));
+    }
+}
@@ -0,0 +1,97 @@
+use std::hash::Hash;
+use std::fmt::Debug;
+use super::{Clock, SystemClock, Ticks, Wheel};
+
+/// Drive a wheel from the current thread, calling `sink` with the wheel and the keys that expired
+/// after every tick. Return `false` from `sink` to stop.
+///
+/// Each tick is scheduled from the time the loop started rather than from the end of the last
+/// one, so time spent in `sink` or oversleeping doesn't accumulate as drift. If the loop falls
+/// behind, it expires the missed ticks back to back until it has caught up.
+pub fn run_blocking<T, W, F>(wheel: &mut W, sink: F)
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T> + ?Sized,
+          F: FnMut(&mut W, W::Expired) -> bool
+{
+    run_blocking_with(SystemClock, wheel, sink)
+}
+
+/// Drive a wheel like `run_blocking`, reading the time from `clock` and sleeping with it
+pub fn run_blocking_with<C, T, W, F>(clock: C, wheel: &mut W, mut sink: F)
+    where C: Clock,
+          T: Eq + Hash + Debug + Clone,
+          W: Wheel<T> + ?Sized,
+          F: FnMut(&mut W, W::Expired) -> bool
+{
+    let tick = wheel.tick_duration();
+    let start = clock.now();
+    let mut ticks = 0;
+    loop {
+        ticks += 1;
+        clock.sleep_until(start + Ticks(ticks).to_duration(tick));
+        let expired = wheel.expire();
+        if !sink(wheel, expired) {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::time::{Duration, Instant};
+    use super::super::{Clock, CopyWheel, Resolution, Wheel};
+
+    // A clock that jumps straight to each deadline instead of sleeping
+    struct Jumping(Cell<Instant>);
+
+    impl Clock for Jumping {
+        fn now(&self) -> Instant {
+            self.0.get()
+        }
+
+        fn sleep_until(&self, deadline: Instant) {
+            if deadline > self.0.get() {
+                self.0.set(deadline);
+            }
+        }
+    }
+
+    #[test]
+    fn runs_until_sink_stops() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        assert_eq!(Duration::from_millis(10), wheel.tick_duration());
+        wheel.start("a", Duration::from_millis(30));
+        let start = Instant::now();
+        let mut ticks = 0;
+        let mut all = Vec::new();
+        run_blocking(&mut wheel, |wheel, expired| {
+            ticks += 1;
+            if ticks == 1 {
+                wheel.start("b", Duration::from_millis(10));
+            }
+            all.extend(expired);
+            !all.contains(&"a")
+        });
+        assert_eq!(4, ticks);
+        assert_eq!(vec!["b", "a"], all);
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
+    #[test]
+    fn runs_on_the_given_clock() {
+        let mut wheel = CopyWheel::new(vec![Resolution::Sec, Resolution::Min]);
+        wheel.start("a", Duration::from_secs(9));
+        let start = Instant::now();
+        let clock = Jumping(Cell::new(start));
+        let mut ticks = 0;
+        run_blocking_with(&clock, &mut wheel, |_, expired| {
+            ticks += 1;
+            expired.is_empty()
+        });
+        assert_eq!(10, ticks);
+        assert_eq!(start + Duration::from_secs(10), clock.now());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,156 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::time::Duration;
+use super::Wheel;
+
+/// A view into a single timer in a wheel, which may be running or not.
+///
+/// Returned by the `entry` method of the wheels.
+pub enum Entry<'a, T: 'a, W: 'a + ?Sized> {
+    Occupied(OccupiedEntry<'a, T, W>),
+    Vacant(VacantEntry<'a, T, W>)
+}
+
+/// An entry for a running timer
+pub struct OccupiedEntry<'a, T: 'a, W: 'a + ?Sized> {
+    wheel: &'a mut W,
+    key: T
+}
+
+/// An entry for a key without a running timer
+pub struct VacantEntry<'a, T: 'a, W: 'a + ?Sized> {
+    wheel: &'a mut W,
+    key: T
+}
+
+impl<'a, T, W> Entry<'a, T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T> + ?Sized
+{
+    pub(crate) fn new(wheel: &'a mut W, key: T, running: bool) -> Entry<'a, T, W> {
+        if running {
+            Entry::Occupied(OccupiedEntry { wheel, key })
+        } else {
+            Entry::Vacant(VacantEntry { wheel, key })
+        }
+    }
+
+    pub fn key(&self) -> &T {
+        match *self {
+            Entry::Occupied(ref entry) => entry.key(),
+            Entry::Vacant(ref entry) => entry.key()
+        }
+    }
+
+    /// Start the timer if it isn't already running
+    pub fn or_start(self, time: Duration) {
+        if let Entry::Vacant(entry) = self {
+            entry.start(time);
+        }
+    }
+
+    /// Restart the timer with a new duration if it is running
+    pub fn and_reset(self, time: Duration) -> Entry<'a, T, W> {
+        match self {
+            Entry::Occupied(mut entry) => {
+                entry.reset(time);
+                Entry::Occupied(entry)
+            },
+            entry => entry
+        }
+    }
+
+    /// Cancel the timer if it is running. Returns true if it was running.
+    pub fn cancel(self) -> bool {
+        match self {
+            Entry::Occupied(entry) => {
+                entry.cancel();
+                true
+            },
+            Entry::Vacant(_) => false
+        }
+    }
+}
+
+impl<'a, T, W> OccupiedEntry<'a, T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T> + ?Sized
+{
+    pub fn key(&self) -> &T {
+        &self.key
+    }
+
+    /// Restart the timer with a new duration
+    pub fn reset(&mut self, time: Duration) {
+        self.wheel.restart(self.key.clone(), time);
+    }
+
+    /// Cancel the timer, returning a vacant entry for the key
+    pub fn cancel(self) -> VacantEntry<'a, T, W> {
+        self.wheel.stop(self.key.clone());
+        VacantEntry {
+            wheel: self.wheel,
+            key: self.key
+        }
+    }
+}
+
+impl<'a, T, W> VacantEntry<'a, T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T> + ?Sized
+{
+    pub fn key(&self) -> &T {
+        &self.key
+    }
+
+    pub fn into_key(self) -> T {
+        self.key
+    }
+
+    /// Start the timer
+    pub fn start(self, time: Duration) {
+        self.wheel.start(self.key, time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{AllocWheel, CopyWheel, Resolution, Wheel};
+
+    type EntryFn<W> = for<'a> fn(&'a mut W, &'static str) -> Entry<'a, &'static str, W>;
+
+    fn exercise<W>(wheel: &mut W, entry: EntryFn<W>)
+        where W: Wheel<&'static str, Expired = Vec<&'static str>>
+    {
+        entry(wheel, "a").or_start(Duration::from_millis(20));
+        // Already running, so this doesn't restart the timer
+        entry(wheel, "a").or_start(Duration::from_millis(50));
+        entry(wheel, "b").and_reset(Duration::from_millis(10)).or_start(Duration::from_millis(30));
+        entry(wheel, "c").or_start(Duration::from_millis(10));
+        assert!(entry(wheel, "c").cancel());
+        assert!(!entry(wheel, "c").cancel());
+
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(vec!["a"], wheel.expire());
+
+        entry(wheel, "b").and_reset(Duration::from_millis(20));
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(0, wheel.expire().len());
+        assert_eq!(vec!["b"], wheel.expire());
+    }
+
+    #[test]
+    fn copy_wheel_entries() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        exercise(&mut wheel, |wheel, key| wheel.entry(key));
+    }
+
+    #[test]
+    fn alloc_wheel_entries() {
+        let mut wheel = AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        exercise(&mut wheel, |wheel, key| wheel.entry(key));
+    }
+}
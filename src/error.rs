@@ -0,0 +1,97 @@
+use core::fmt;
+use core::time::Duration;
+
+/// The errors returned by the fallible operations of the wheels and helpers
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum FerrisError {
+    /// The configuration can't be used to build a wheel, for the given reason
+    InvalidConfig(&'static str),
+
+    /// A timer duration is zero
+    ZeroDuration,
+
+    /// A timer duration is too short or too long for the resolutions of the wheel, which can't go
+    /// past the given bound
+    DurationOutOfRange(Duration),
+
+    /// There is no room for another timer
+    AtCapacity,
+
+    /// A timer is already running for the key
+    AlreadyScheduled,
+
+    /// No timer is running for the key
+    NotFound
+}
+
+impl fmt::Display for FerrisError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FerrisError::InvalidConfig(reason) => {
+                write!(f, "invalid wheel configuration: {}", reason)
+            },
+            FerrisError::ZeroDuration => write!(f, "timer duration is zero"),
+            FerrisError::DurationOutOfRange(time) => {
+                write!(f, "timer duration {:?} is out of range for the wheel", time)
+            },
+            FerrisError::AtCapacity => write!(f, "the wheel is at capacity"),
+            FerrisError::AlreadyScheduled => write!(f, "a timer is already running for the key"),
+            FerrisError::NotFound => write!(f, "no timer is running for the key")
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for FerrisError {}
+
+/// The reasons `try_start` can refuse to start a timer
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum StartError {
+    /// The duration is zero
+    Zero,
+
+    /// The duration is shorter than the wheel can represent, which is given
+    TooShort(Duration),
+
+    /// The duration isn't shorter than the span of the wheel, which is given. Only `U64Wheel`
+    /// reports this, since the other wheels hold longer timers in an overflow list.
+    TooLong(Duration),
+
+    /// A timer is already running for the key
+    DuplicateKey,
+
+    /// The wheel already holds its maximum number of timers
+    Full
+}
+
+impl fmt::Display for StartError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StartError::Zero => write!(f, "timer duration is zero"),
+            StartError::TooShort(min) => {
+                write!(f, "timer duration is shorter than the minimum of {:?}", min)
+            },
+            StartError::TooLong(span) => {
+                write!(f, "timer duration is not shorter than the wheel span of {:?}", span)
+            },
+            StartError::DuplicateKey => write!(f, "a timer is already running for the key"),
+            StartError::Full => write!(f, "the wheel holds its maximum number of timers")
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl ::std::error::Error for StartError {}
+
+impl From<StartError> for FerrisError {
+    /// Let callers that handle every kind of error use `?` on `try_start`
+    fn from(error: StartError) -> FerrisError {
+        match error {
+            StartError::Zero => FerrisError::ZeroDuration,
+            StartError::TooShort(min) => FerrisError::DurationOutOfRange(min),
+            StartError::TooLong(span) => FerrisError::DurationOutOfRange(span),
+            StartError::DuplicateKey => FerrisError::AlreadyScheduled,
+            StartError::Full => FerrisError::AtCapacity
+        }
+    }
+}
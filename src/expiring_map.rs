@@ -0,0 +1,143 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::time::Duration;
+use alloc::vec::Vec;
+use collections::HashMap;
+use super::{CopyWheel, Resolution, Wheel};
+
+/// A map whose entries are evicted once their time to live runs out.
+///
+/// Each entry has a timer on a `CopyWheel`, and `tick` advances the wheel and returns the entries
+/// it evicted, so the map must be driven by calling `tick` at the maximum resolution. The wheel
+/// runs in precise mode, so an entry is evicted on the first tick after its TTL has passed rather
+/// than rounded to the resolution of a coarser wheel.
+///
+/// Unlike `MapWheel`, which is a wheel that happens to hold values, this puts the map first: TTLs
+/// shorter than a tick are rounded up to a tick instead of dropping the value, and replacing or
+/// removing an entry always cancels its old timer.
+pub struct ExpiringMap<K: Eq + Hash + Debug + Clone, V> {
+    entries: HashMap<K, V>,
+    wheel: CopyWheel<K>,
+    expired: Vec<K>
+}
+
+impl<K: Eq + Hash + Debug + Clone, V> ExpiringMap<K, V> {
+    /// Create a map whose TTLs are tracked by a wheel with the given resolutions. See
+    /// `CopyWheel::new`.
+    pub fn new(resolutions: Vec<Resolution>) -> ExpiringMap<K, V> {
+        let mut wheel = CopyWheel::new(resolutions);
+        wheel.set_precise(true);
+        ExpiringMap {
+            entries: HashMap::new(),
+            wheel,
+            expired: Vec::new()
+        }
+    }
+
+    /// Insert a value that is evicted after `ttl`, returning the value it replaced.
+    ///
+    /// Replacing a value restarts its TTL.
+    pub fn insert(&mut self, key: K, value: V, ttl: Duration) -> Option<V> {
+        let ttl = ttl.max(self.wheel.tick_duration());
+        self.wheel.start(key.clone(), ttl);
+        self.entries.insert(key, value)
+    }
+
+    /// Restart the TTL of an entry without changing its value. Returns false if there is no such
+    /// entry.
+    pub fn refresh(&mut self, key: &K, ttl: Duration) -> bool {
+        let ttl = ttl.max(self.wheel.tick_duration());
+        self.entries.contains_key(key) && self.wheel.restart(key.clone(), ttl)
+    }
+
+    /// Remove an entry before it expires
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let value = self.entries.remove(key)?;
+        self.wheel.cancel(key);
+        Some(value)
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.entries.get_mut(key)
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    /// The time left until an entry is evicted. See `CopyWheel::remaining`.
+    pub fn ttl(&self, key: &K) -> Option<Duration> {
+        self.wheel.remaining(key)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter()
+    }
+
+    /// The number of entries in the map
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// How often `tick` must be called
+    pub fn tick_duration(&self) -> Duration {
+        self.wheel.tick_duration()
+    }
+
+    /// Advance the wheel by one tick and return the entries whose TTL ran out
+    pub fn tick(&mut self) -> Vec<(K, V)> {
+        self.wheel.expire_into(&mut self.expired);
+        let entries = &mut self.entries;
+        self.expired.drain(..)
+            .filter_map(|key| entries.remove(&key).map(|value| (key, value)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    #[test]
+    fn entries_are_evicted_when_their_ttl_runs_out() {
+        let mut map = ExpiringMap::new(vec![Resolution::TenMs, Resolution::Sec]);
+        assert_eq!(None, map.insert("session", 1, Duration::from_millis(1300)));
+        assert_eq!(None, map.insert("token", 2, Duration::from_micros(10)));
+        assert_eq!(None, map.insert("nonce", 3, Duration::from_millis(20)));
+        assert_eq!(Some(3), map.insert("nonce", 4, Duration::from_millis(30)));
+        assert!(map.refresh(&"session", Duration::from_millis(50)));
+        assert!(!map.refresh(&"missing", Duration::from_millis(50)));
+        assert_eq!(Some(Duration::from_millis(40)), map.ttl(&"nonce"));
+        assert_eq!(3, map.iter().count());
+
+        let mut evicted = Vec::new();
+        for tick in 1..8 {
+            evicted.extend(map.tick().into_iter().map(|(key, value)| (tick, key, value)));
+        }
+        assert_eq!(vec![(2, "token", 2), (4, "nonce", 4), (6, "session", 1)], evicted);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn removed_entries_stay_removed() {
+        let mut map = ExpiringMap::new(vec![Resolution::TenMs, Resolution::Sec]);
+        map.insert(1, "a", Duration::from_millis(20));
+        assert_eq!(Some("a"), map.remove(&1));
+        map.insert(1, "b", Duration::from_millis(40));
+        for _ in 0..4 {
+            assert!(map.tick().is_empty());
+        }
+        assert_eq!(Some(&"b"), map.get(&1));
+        assert_eq!(vec![(1, "b")], map.tick());
+    }
+}
@@ -0,0 +1,267 @@
+//! A C API for embedding a wheel with `u64` keys.
+//!
+//! ```c
+//! typedef struct FerrisWheel FerrisWheel;
+//!
+//! FerrisWheel *ferris_wheel_new(const uint32_t *resolutions, size_t len);
+//! void ferris_wheel_free(FerrisWheel *wheel);
+//! void ferris_wheel_start(FerrisWheel *wheel, uint64_t key, uint64_t millis);
+//! uint64_t ferris_wheel_schedule(FerrisWheel *wheel, uint64_t millis);
+//! void ferris_wheel_stop(FerrisWheel *wheel, uint64_t key);
+//! size_t ferris_wheel_expire(FerrisWheel *wheel, uint64_t *out, size_t cap);
+//! size_t ferris_wheel_pending(const FerrisWheel *wheel);
+//! size_t ferris_wheel_take_expired(FerrisWheel *wheel, uint64_t *out, size_t cap);
+//! ```
+//!
+//! Resolutions are given as codes: `0` for 1ms, `1` for 10ms, `2` for 100ms, `3` for seconds, `4`
+//! for minutes, `5` for hours, `6` for 10us, `7` for 100us, `8` for days and `9` for weeks. Keys
+//! that expire when the caller's buffer is full are held by the wheel and can be collected with
+//! `ferris_wheel_take_expired` before the next tick.
+//!
+//! Callers with their own ids can use them as keys. Those without can let the wheel hand out
+//! opaque handles with `ferris_wheel_schedule`, which then come back from `ferris_wheel_expire`
+//! and are passed to `ferris_wheel_stop` like keys.
+//!
+//! Build a shared or static library for linking with
+//! `cargo rustc --release --lib --features ffi --crate-type cdylib` or `--crate-type staticlib`.
+
+use core::ptr;
+use core::slice;
+use core::time::Duration;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use super::{CopyWheel, Resolution, Wheel};
+
+/// The opaque handle given to C callers
+pub struct FerrisWheel {
+    wheel: CopyWheel<u64>,
+    pending: Vec<u64>,
+    next_handle: u64
+}
+
+fn resolution(code: u32) -> Option<Resolution> {
+    match code {
+        0 => Some(Resolution::Ms),
+        1 => Some(Resolution::TenMs),
+        2 => Some(Resolution::HundredMs),
+        3 => Some(Resolution::Sec),
+        4 => Some(Resolution::Min),
+        5 => Some(Resolution::Hour),
+        6 => Some(Resolution::TenUs),
+        7 => Some(Resolution::HundredUs),
+        8 => Some(Resolution::Day),
+        9 => Some(Resolution::Week),
+        _ => None
+    }
+}
+
+/// Create a wheel from `len` resolution codes. Returns null if there are no resolutions or any of
+/// the codes is unknown.
+///
+/// # Safety
+///
+/// `resolutions` must point to `len` readable values. The wheel must be freed with
+/// `ferris_wheel_free`.
+#[no_mangle]
+pub unsafe extern "C" fn ferris_wheel_new(resolutions: *const u32,
+                                          len: usize) -> *mut FerrisWheel {
+    if resolutions.is_null() || len == 0 {
+        return ptr::null_mut();
+    }
+    let codes = slice::from_raw_parts(resolutions, len);
+    let resolutions: Option<Vec<Resolution>> = codes.iter().map(|code| resolution(*code)).collect();
+    match resolutions {
+        Some(resolutions) => Box::into_raw(Box::new(FerrisWheel {
+            wheel: CopyWheel::new(resolutions),
+            pending: Vec::new(),
+            next_handle: 1
+        })),
+        None => ptr::null_mut()
+    }
+}
+
+/// Free a wheel. Passing null does nothing.
+///
+/// # Safety
+///
+/// `wheel` must be null or a pointer returned by `ferris_wheel_new` that hasn't been freed.
+#[no_mangle]
+pub unsafe extern "C" fn ferris_wheel_free(wheel: *mut FerrisWheel) {
+    if !wheel.is_null() {
+        drop(Box::from_raw(wheel));
+    }
+}
+
+/// Start a timer for `key` that expires after `millis` milliseconds, restarting it if it is
+/// already running
+///
+/// # Safety
+///
+/// `wheel` must be a live pointer returned by `ferris_wheel_new`.
+#[no_mangle]
+pub unsafe extern "C" fn ferris_wheel_start(wheel: *mut FerrisWheel, key: u64, millis: u64) {
+    if let Some(wheel) = wheel.as_mut() {
+        wheel.wheel.start(key, Duration::from_millis(millis));
+    }
+}
+
+/// Start a timer that expires after `millis` milliseconds under a new handle, and return the
+/// handle. Returns 0, which is never a handle, if `millis` is shorter than a tick.
+///
+/// Handles are counted up from 1, skipping any key with a running timer.
+///
+/// # Safety
+///
+/// `wheel` must be a live pointer returned by `ferris_wheel_new`.
+#[no_mangle]
+pub unsafe extern "C" fn ferris_wheel_schedule(wheel: *mut FerrisWheel, millis: u64) -> u64 {
+    let wheel = match wheel.as_mut() {
+        Some(wheel) => wheel,
+        None => return 0
+    };
+    let time = Duration::from_millis(millis);
+    if time < wheel.wheel.tick_duration() {
+        return 0;
+    }
+    while wheel.next_handle == 0 || wheel.wheel.contains(&wheel.next_handle) {
+        wheel.next_handle = wheel.next_handle.wrapping_add(1);
+    }
+    let handle = wheel.next_handle;
+    wheel.next_handle = wheel.next_handle.wrapping_add(1);
+    wheel.wheel.start(handle, time);
+    handle
+}
+
+/// Stop the timer for `key` if it is running
+///
+/// # Safety
+///
+/// `wheel` must be a live pointer returned by `ferris_wheel_new`.
+#[no_mangle]
+pub unsafe extern "C" fn ferris_wheel_stop(wheel: *mut FerrisWheel, key: u64) {
+    if let Some(wheel) = wheel.as_mut() {
+        wheel.wheel.stop(key);
+    }
+}
+
+/// Advance the wheel by one tick and write up to `cap` expired keys to `out`, returning the number
+/// written. Keys that don't fit are kept for `ferris_wheel_take_expired`.
+///
+/// Any keys still pending from earlier ticks are written first.
+///
+/// # Safety
+///
+/// `wheel` must be a live pointer returned by `ferris_wheel_new`, and `out` must point to `cap`
+/// writable values.
+#[no_mangle]
+pub unsafe extern "C" fn ferris_wheel_expire(wheel: *mut FerrisWheel,
+                                             out: *mut u64,
+                                             cap: usize) -> usize {
+    match wheel.as_mut() {
+        Some(handle) => {
+            let expired = handle.wheel.expire();
+            handle.pending.extend(expired);
+            ferris_wheel_take_expired(wheel, out, cap)
+        },
+        None => 0
+    }
+}
+
+/// The number of expired keys that haven't been written out yet
+///
+/// # Safety
+///
+/// `wheel` must be a live pointer returned by `ferris_wheel_new`.
+#[no_mangle]
+pub unsafe extern "C" fn ferris_wheel_pending(wheel: *const FerrisWheel) -> usize {
+    wheel.as_ref().map_or(0, |wheel| wheel.pending.len())
+}
+
+/// Write up to `cap` pending expired keys to `out` without advancing the wheel, returning the
+/// number written
+///
+/// # Safety
+///
+/// `wheel` must be a live pointer returned by `ferris_wheel_new`, and `out` must point to `cap`
+/// writable values.
+#[no_mangle]
+pub unsafe extern "C" fn ferris_wheel_take_expired(wheel: *mut FerrisWheel,
+                                                   out: *mut u64,
+                                                   cap: usize) -> usize {
+    let wheel = match wheel.as_mut() {
+        Some(wheel) => wheel,
+        None => return 0
+    };
+    if out.is_null() {
+        return 0;
+    }
+    let count = cap.min(wheel.pending.len());
+    let out = slice::from_raw_parts_mut(out, count);
+    for (slot, key) in out.iter_mut().zip(wheel.pending.drain(..count)) {
+        *slot = key;
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn invalid_config_returns_null() {
+        unsafe {
+            assert!(ferris_wheel_new(ptr::null(), 0).is_null());
+            assert!(ferris_wheel_new([1, 10].as_ptr(), 2).is_null());
+        }
+    }
+
+    #[test]
+    fn expire_into_small_buffer() {
+        unsafe {
+            let resolutions = [1u32, 3];
+            let wheel = ferris_wheel_new(resolutions.as_ptr(), resolutions.len());
+            assert!(!wheel.is_null());
+            for key in 0..5 {
+                ferris_wheel_start(wheel, key, 10);
+            }
+            ferris_wheel_start(wheel, 5, 10);
+            ferris_wheel_stop(wheel, 5);
+
+            let mut out = [0u64; 2];
+            assert_eq!(0, ferris_wheel_expire(wheel, out.as_mut_ptr(), out.len()));
+            assert_eq!(2, ferris_wheel_expire(wheel, out.as_mut_ptr(), out.len()));
+            assert_eq!(3, ferris_wheel_pending(wheel));
+            let mut rest = [0u64; 8];
+            assert_eq!(3, ferris_wheel_take_expired(wheel, rest.as_mut_ptr(), rest.len()));
+            assert_eq!(0, ferris_wheel_pending(wheel));
+
+            let mut keys: Vec<u64> = out.iter().chain(rest[..3].iter()).cloned().collect();
+            keys.sort();
+            assert_eq!(vec![0, 1, 2, 3, 4], keys);
+            ferris_wheel_free(wheel);
+        }
+    }
+
+    #[test]
+    fn handles_are_unique() {
+        unsafe {
+            let resolutions = [1u32, 3];
+            let wheel = ferris_wheel_new(resolutions.as_ptr(), resolutions.len());
+            ferris_wheel_start(wheel, 2, 10);
+            let first = ferris_wheel_schedule(wheel, 10);
+            let second = ferris_wheel_schedule(wheel, 20);
+            assert_eq!((1, 3), (first, second));
+            assert_eq!(0, ferris_wheel_schedule(wheel, 1));
+            ferris_wheel_stop(wheel, second);
+
+            let mut out = [0u64; 4];
+            assert_eq!(0, ferris_wheel_expire(wheel, out.as_mut_ptr(), out.len()));
+            assert_eq!(2, ferris_wheel_expire(wheel, out.as_mut_ptr(), out.len()));
+            out[..2].sort();
+            assert_eq!([1, 2], out[..2]);
+            assert_eq!(0, ferris_wheel_expire(wheel, out.as_mut_ptr(), out.len()));
+            ferris_wheel_free(wheel);
+        }
+    }
+}
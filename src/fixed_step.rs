@@ -0,0 +1,120 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::time::Duration;
+use alloc::vec::Vec;
+use super::Wheel;
+
+/// A fixed timestep accumulator for game loops.
+///
+/// Feed it the real time elapsed each frame and it drives a wheel by however many whole ticks fit,
+/// carrying the leftover time into the next frame. All arithmetic is done on integer `Duration`s,
+/// so no time is lost to rounding no matter how uneven the frame times are. The remainder is
+/// exposed as `alpha` for interpolating rendered state between ticks.
+///
+/// The tick must match the maximum resolution of the wheel being driven.
+#[derive(Debug, Clone)]
+pub struct FixedStep {
+    tick: Duration,
+    accumulator: Duration,
+    max_ticks: Option<u64>
+}
+
+impl FixedStep {
+    pub fn new(tick: Duration) -> FixedStep {
+        assert!(tick > Duration::from_secs(0));
+        FixedStep {
+            tick,
+            accumulator: Duration::from_secs(0),
+            max_ticks: None
+        }
+    }
+
+    /// Run at most `max_ticks` per update, discarding any whole ticks beyond that.
+    ///
+    /// This avoids a spiral where a slow frame causes more ticks, which causes a slower frame.
+    pub fn with_max_ticks(tick: Duration, max_ticks: u64) -> FixedStep {
+        let mut step = FixedStep::new(tick);
+        step.max_ticks = Some(max_ticks);
+        step
+    }
+
+    /// Add elapsed time and return the number of whole ticks that should be run
+    pub fn advance(&mut self, elapsed: Duration) -> u64 {
+        self.accumulator += elapsed;
+        let tick = self.tick.as_nanos();
+        let mut ticks = (self.accumulator.as_nanos() / tick) as u64;
+        let remainder = (self.accumulator.as_nanos() % tick) as u64;
+        self.accumulator = Duration::from_nanos(remainder);
+        if let Some(max) = self.max_ticks {
+            if ticks > max {
+                ticks = max;
+            }
+        }
+        ticks
+    }
+
+    /// Add elapsed time and expire the wheel once for every whole tick, returning all the keys
+    /// that expired
+    pub fn update<T, W>(&mut self, wheel: &mut W, elapsed: Duration) -> Vec<T>
+        where T: Eq + Hash + Debug + Clone,
+              W: Wheel<T> + ?Sized
+    {
+        let mut expired = Vec::new();
+        for _ in 0..self.advance(elapsed) {
+            expired.extend(wheel.expire());
+        }
+        expired
+    }
+
+    /// The time accumulated towards the next tick
+    pub fn remainder(&self) -> Duration {
+        self.accumulator
+    }
+
+    /// The fraction of a tick accumulated towards the next tick, in `[0, 1)`
+    pub fn alpha(&self) -> f64 {
+        self.accumulator.as_nanos() as f64 / self.tick.as_nanos() as f64
+    }
+
+    pub fn tick(&self) -> Duration {
+        self.tick
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{CopyWheel, Resolution, Wheel};
+
+    #[test]
+    fn uneven_frames_do_not_drift() {
+        let mut step = FixedStep::new(Duration::from_millis(10));
+        let mut ticks = 0;
+        // 1000 frames of 16.666ms is 16666ms
+        for _ in 0..1000 {
+            ticks += step.advance(Duration::from_micros(16_666));
+        }
+        assert_eq!(1666, ticks);
+        assert_eq!(Duration::from_millis(6), step.remainder());
+        assert!((step.alpha() - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn max_ticks_discards_backlog() {
+        let mut step = FixedStep::with_max_ticks(Duration::from_millis(10), 3);
+        assert_eq!(3, step.advance(Duration::from_millis(105)));
+        assert_eq!(Duration::from_millis(5), step.remainder());
+        assert_eq!(1, step.advance(Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn update_drives_wheel() {
+        let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("a", Duration::from_millis(50));
+        let mut step = FixedStep::new(Duration::from_millis(10));
+        assert!(step.update(&mut wheel, Duration::from_millis(33)).is_empty());
+        assert!(step.update(&mut wheel, Duration::from_millis(20)).is_empty());
+        assert_eq!(vec!["a"], step.update(&mut wheel, Duration::from_millis(10)));
+    }
+}
@@ -0,0 +1,193 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use core::time::Duration;
+use alloc::vec::Vec;
+use collections::HashMap;
+use super::Wheel;
+
+/// A wheel whose timers can be started in groups, so that a whole group can be cancelled at once.
+///
+/// Give each connection a group id and start its timers with `start_in_group`, and
+/// `cancel_group` cleans them all up when the connection closes in time proportional to the
+/// group's size. Timers started with `start` belong to no group, and restarting a grouped timer
+/// keeps it in its group.
+///
+/// A timer leaves its group when it is stopped or expires, unless it is an interval timer, which
+/// stays in its group until it is stopped.
+pub struct GroupedWheel<G, T, W>
+    where G: Eq + Hash + Clone,
+          T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    wheel: W,
+    groups: HashMap<G, HashMap<T, ()>>,
+    // The group of each grouped key, and whether it is an interval timer
+    members: HashMap<T, (G, bool)>,
+    _key: PhantomData<T>
+}
+
+impl<G, T, W> GroupedWheel<G, T, W>
+    where G: Eq + Hash + Clone,
+          T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    pub fn new(wheel: W) -> GroupedWheel<G, T, W> {
+        GroupedWheel {
+            wheel,
+            groups: HashMap::new(),
+            members: HashMap::new(),
+            _key: PhantomData
+        }
+    }
+
+    /// Start or restart a timer as part of `group`, moving it out of any group it was in
+    pub fn start_in_group(&mut self, key: T, group: G, time: Duration) {
+        self.leave_group(&key);
+        self.wheel.start(key.clone(), time);
+        if !self.groups.contains_key(&group) {
+            self.groups.insert(group.clone(), HashMap::new());
+        }
+        if let Some(keys) = self.groups.get_mut(&group) {
+            keys.insert(key.clone(), ());
+        }
+        self.members.insert(key, (group, false));
+    }
+
+    /// Cancel every timer in `group`, returning how many were cancelled
+    pub fn cancel_group(&mut self, group: &G) -> usize {
+        let keys = match self.groups.remove(group) {
+            Some(keys) => keys,
+            None => return 0
+        };
+        let count = keys.len();
+        for (key, _) in keys {
+            self.members.remove(&key);
+            self.wheel.stop(key);
+        }
+        count
+    }
+
+    /// The number of running timers in `group`
+    pub fn group_len(&self, group: &G) -> usize {
+        self.groups.get(group).map_or(0, |keys| keys.len())
+    }
+
+    /// The group a running timer belongs to, if any
+    pub fn group_of(&self, key: &T) -> Option<&G> {
+        self.members.get(key).map(|(group, _)| group)
+    }
+
+    pub fn wheel(&self) -> &W {
+        &self.wheel
+    }
+
+    pub fn into_inner(self) -> W {
+        self.wheel
+    }
+
+    fn leave_group(&mut self, key: &T) {
+        if let Some((group, _)) = self.members.remove(key) {
+            let empty = match self.groups.get_mut(&group) {
+                Some(keys) => {
+                    keys.remove(key);
+                    keys.is_empty()
+                },
+                None => false
+            };
+            if empty {
+                self.groups.remove(&group);
+            }
+        }
+    }
+}
+
+impl<G, T, W> Wheel<T> for GroupedWheel<G, T, W>
+    where G: Eq + Hash + Clone,
+          T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    type Expired = Vec<T>;
+
+    /// Start or restart a timer, keeping it in its group if it has one
+    fn start(&mut self, key: T, time: Duration) {
+        if let Some(member) = self.members.get_mut(&key) {
+            member.1 = false;
+        }
+        self.wheel.start(key, time);
+    }
+
+    fn stop(&mut self, key: T) -> Option<Duration> {
+        self.leave_group(&key);
+        self.wheel.stop(key)
+    }
+
+    fn expire(&mut self) -> Vec<T> {
+        let expired: Vec<T> = self.wheel.expire().into_iter().collect();
+        if !self.members.is_empty() {
+            for key in &expired {
+                if let Some(&(_, false)) = self.members.get(key) {
+                    self.leave_group(key);
+                }
+            }
+        }
+        expired
+    }
+
+    fn start_with_delay(&mut self, key: T, delay: Duration, period: Duration) {
+        if let Some(member) = self.members.get_mut(&key) {
+            member.1 = true;
+        }
+        self.wheel.start_with_delay(key, delay, period);
+    }
+
+    fn restart(&mut self, key: T, time: Duration) -> bool {
+        self.wheel.restart(key, time)
+    }
+
+    fn tick_duration(&self) -> Duration {
+        self.wheel.tick_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{CopyWheel, Resolution, Wheel};
+
+    #[test]
+    fn cancel_group_stops_its_timers() {
+        let mut wheel = GroupedWheel::new(CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]));
+        wheel.start_in_group("conn1/read", 1, Duration::from_millis(10));
+        wheel.start_in_group("conn1/write", 1, Duration::from_millis(20));
+        wheel.start_in_group("conn2/read", 2, Duration::from_millis(10));
+        wheel.start("global", Duration::from_millis(10));
+        assert_eq!(2, wheel.group_len(&1));
+        assert_eq!(Some(&2), wheel.group_of(&"conn2/read"));
+
+        assert_eq!(2, wheel.cancel_group(&1));
+        assert_eq!(0, wheel.cancel_group(&1));
+        assert!(wheel.expire().is_empty());
+        let mut expired = wheel.expire();
+        expired.sort();
+        assert_eq!(vec!["conn2/read", "global"], expired);
+        assert_eq!(0, wheel.group_len(&2));
+        assert!(wheel.expire().is_empty());
+    }
+
+    #[test]
+    fn interval_timers_stay_in_their_group() {
+        let mut wheel = GroupedWheel::new(CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]));
+        wheel.start_in_group("ping", "conn", Duration::from_millis(10));
+        wheel.start_interval("ping", Duration::from_millis(10));
+        wheel.start_in_group("once", "conn", Duration::from_millis(10));
+        wheel.expire();
+        assert_eq!(2, wheel.expire().len());
+        assert_eq!(1, wheel.group_len(&"conn"));
+        assert_eq!(1, wheel.cancel_group(&"conn"));
+        for _ in 0..10 {
+            assert!(wheel.expire().is_empty());
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use core::cell::RefCell;
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use core::time::Duration;
+use alloc::rc::{Rc, Weak};
+use super::{TimerScope, Wheel};
+
+/// A wheel that can be shared by the code that owns timers.
+///
+/// Cloning a `SharedWheel` gives another handle to the same wheel. This is what lets a
+/// `TimerGuard` cancel its timer when it is dropped, without holding a borrow of the wheel for the
+/// life of the timer.
+pub struct SharedWheel<T, W> {
+    wheel: Rc<RefCell<W>>,
+    _key: PhantomData<T>
+}
+
+impl<T, W> SharedWheel<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    pub fn new(wheel: W) -> SharedWheel<T, W> {
+        SharedWheel {
+            wheel: Rc::new(RefCell::new(wheel)),
+            _key: PhantomData
+        }
+    }
+
+    /// Start a timer that is cancelled when the returned guard is dropped
+    pub fn start_guarded(&self, key: T, time: Duration) -> TimerGuard<T, W> {
+        self.wheel.borrow_mut().start(key.clone(), time);
+        TimerGuard {
+            wheel: Rc::downgrade(&self.wheel),
+            key: Some(key)
+        }
+    }
+
+    /// Create a scope for starting timers that are all cancelled when the scope is dropped
+    pub fn scope(&self) -> TimerScope<T, W> {
+        TimerScope::new(Rc::downgrade(&self.wheel))
+    }
+
+    /// Run a closure with exclusive access to the wheel
+    ///
+    /// # Panics
+    ///
+    /// Panics if the wheel is already borrowed, for example by a call from within the closure.
+    pub fn with<F, R>(&self, f: F) -> R
+        where F: FnOnce(&mut W) -> R
+    {
+        f(&mut self.wheel.borrow_mut())
+    }
+}
+
+impl<T, W> Clone for SharedWheel<T, W> {
+    fn clone(&self) -> SharedWheel<T, W> {
+        SharedWheel {
+            wheel: self.wheel.clone(),
+            _key: PhantomData
+        }
+    }
+}
+
+impl<T, W> Wheel<T> for SharedWheel<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    type Expired = W::Expired;
+
+    fn start(&mut self, key: T, time: Duration) {
+        self.wheel.borrow_mut().start(key, time);
+    }
+
+    fn stop(&mut self, key: T) -> Option<Duration> {
+        self.wheel.borrow_mut().stop(key)
+    }
+
+    fn expire(&mut self) -> W::Expired {
+        self.wheel.borrow_mut().expire()
+    }
+
+    fn start_with_delay(&mut self, key: T, delay: Duration, period: Duration) {
+        self.wheel.borrow_mut().start_with_delay(key, delay, period);
+    }
+
+    fn restart(&mut self, key: T, time: Duration) -> bool {
+        self.wheel.borrow_mut().restart(key, time)
+    }
+
+    fn tick_duration(&self) -> Duration {
+        self.wheel.borrow().tick_duration()
+    }
+}
+
+/// A timer that is cancelled when the guard is dropped.
+///
+/// Tie the guard to the lifetime of whatever is waiting on the timer, such as a request, and the
+/// timer can't outlive it. The guard doesn't keep the wheel alive.
+///
+/// The guard cancels by key, so if the timer has already expired and the same key was started
+/// again elsewhere, dropping the guard cancels that timer. Use a unique key per guard to avoid
+/// this.
+pub struct TimerGuard<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    wheel: Weak<RefCell<W>>,
+    key: Option<T>
+}
+
+impl<T, W> TimerGuard<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    pub fn key(&self) -> &T {
+        self.key.as_ref().unwrap()
+    }
+
+    /// Cancel the timer now rather than when the guard goes out of scope
+    pub fn cancel(self) {
+        drop(self);
+    }
+
+    /// Release the guard without cancelling the timer, returning the key
+    pub fn disarm(mut self) -> T {
+        self.key.take().unwrap()
+    }
+}
+
+impl<T, W> Drop for TimerGuard<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    fn drop(&mut self) {
+        if let (Some(key), Some(wheel)) = (self.key.take(), self.wheel.upgrade()) {
+            wheel.borrow_mut().stop(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{CopyWheel, Resolution, Wheel};
+
+    #[test]
+    fn dropping_guard_cancels_timer() {
+        let mut wheel = SharedWheel::new(CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]));
+        let a = wheel.start_guarded("a", Duration::from_millis(10));
+        let b = wheel.start_guarded("b", Duration::from_millis(10));
+        let c = wheel.start_guarded("c", Duration::from_millis(10));
+        assert_eq!(&"a", a.key());
+        drop(a);
+        b.cancel();
+        assert_eq!("c", c.disarm());
+        assert!(wheel.with(|wheel| wheel.cancel("a")).is_none());
+        assert!(wheel.expire().is_empty());
+        assert_eq!(vec!["c"], wheel.expire());
+    }
+
+    #[test]
+    fn guard_outlives_wheel() {
+        let wheel = SharedWheel::new(CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]));
+        let guard = wheel.start_guarded("a", Duration::from_millis(10));
+        drop(wheel);
+        drop(guard);
+    }
+}
@@ -0,0 +1,194 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::mem;
+use core::time::Duration;
+use alloc::vec::Vec;
+use collections::HashMap;
+use super::{Resolution, Wheel};
+use super::{exact_deadline, remaining, resolution_duration};
+
+struct HashedEntry<T> {
+    key: T,
+    // The number of times the slot is visited before the timer is due
+    rounds: u64
+}
+
+/// A single ring of slots, where each timer counts down the turns of the ring left before it is
+/// due, as in Netty's `HashedWheelTimer`.
+///
+/// A timer goes in the slot its deadline falls in, whatever its length, so there are no coarser
+/// wheels to move between and no limit on durations. Every visit to a slot walks all of its
+/// timers, so this suits workloads where most timers are shorter than a turn of the ring, such
+/// as request timeouts clustered around a single value. The hierarchical wheels are better when
+/// durations are spread widely.
+///
+/// Cancelled timers leave an entry in their slot, which is dropped on the next visit. Restarted
+/// timers leave one that is dropped once its rounds run out.
+pub struct HashedWheel<T: Eq + Hash + Debug + Clone> {
+    resolution: Resolution,
+    slots: Vec<Vec<HashedEntry<T>>>,
+    keys: HashMap<T, u64>,
+    periods: HashMap<T, Duration>,
+    tick: u64
+}
+
+impl<T: Eq + Hash + Debug + Clone> HashedWheel<T> {
+    /// Create a ring of `slots` slots, each lasting a tick of `resolution`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `slots` is zero.
+    pub fn new(resolution: Resolution, slots: usize) -> HashedWheel<T> {
+        assert!(slots > 0, "a hashed wheel needs at least one slot");
+        HashedWheel {
+            resolution,
+            slots: (0..slots).map(|_| Vec::new()).collect(),
+            keys: HashMap::new(),
+            periods: HashMap::new(),
+            tick: 0
+        }
+    }
+
+    /// The number of running timers
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn contains(&self, key: &T) -> bool {
+        self.keys.contains_key(key)
+    }
+
+    /// The time left until a timer expires, rounded up to a whole number of ticks
+    pub fn remaining(&self, key: &T) -> Option<Duration> {
+        let deadline = *self.keys.get(key)?;
+        Some(remaining(self.tick, deadline, self.tick_duration()))
+    }
+
+    // Schedule a timer without changing its period, returning false if `time` is shorter than a
+    // tick
+    fn schedule(&mut self, key: T, time: Duration) -> bool {
+        let deadline = match exact_deadline(self.tick, self.tick_duration(), time) {
+            Some(deadline) => deadline,
+            None => return false
+        };
+        let size = self.slots.len() as u64;
+        let ticks = deadline - self.tick;
+        // The slot is first visited within a turn, and then once per turn
+        let first = (ticks - 1) % size + 1;
+        let entry = HashedEntry {
+            key: key.clone(),
+            rounds: (ticks - first) / size
+        };
+        self.slots[(deadline % size) as usize].push(entry);
+        self.keys.insert(key, deadline);
+        true
+    }
+}
+
+impl<T: Eq + Hash + Debug + Clone> Wheel<T> for HashedWheel<T> {
+    type Expired = Vec<T>;
+
+    /// Start a timer, restarting it if it's already running. Durations shorter than a tick are
+    /// ignored.
+    fn start(&mut self, key: T, time: Duration) {
+        if self.schedule(key.clone(), time) {
+            self.periods.remove(&key);
+        }
+    }
+
+    fn stop(&mut self, key: T) -> Option<Duration> {
+        let left = self.remaining(&key)?;
+        self.keys.remove(&key);
+        self.periods.remove(&key);
+        Some(left)
+    }
+
+    fn expire(&mut self) -> Vec<T> {
+        self.tick += 1;
+        let now = self.tick;
+        let index = (now % self.slots.len() as u64) as usize;
+        let mut expired = Vec::new();
+        let keys = &mut self.keys;
+        let entries = mem::take(&mut self.slots[index]);
+        for mut entry in entries {
+            match keys.get(&entry.key) {
+                Some(&deadline) if entry.rounds == 0 && deadline == now => {
+                    keys.remove(&entry.key);
+                    expired.push(entry.key);
+                },
+                Some(_) if entry.rounds > 0 => {
+                    entry.rounds -= 1;
+                    self.slots[index].push(entry);
+                },
+                // Cancelled, or restarted to another deadline
+                _ => ()
+            }
+        }
+        for key in &expired {
+            if let Some(period) = self.periods.get(key).cloned() {
+                self.schedule(key.clone(), period);
+            }
+        }
+        expired
+    }
+
+    fn start_with_delay(&mut self, key: T, delay: Duration, period: Duration) {
+        if self.schedule(key.clone(), delay) {
+            let period = period.max(self.tick_duration());
+            self.periods.insert(key, period);
+        }
+    }
+
+    fn restart(&mut self, key: T, time: Duration) -> bool {
+        self.keys.contains_key(&key) && self.schedule(key, time)
+    }
+
+    fn tick_duration(&self) -> Duration {
+        resolution_duration(self.resolution)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{Resolution, Wheel};
+
+    #[test]
+    fn timers_wait_out_their_rounds() {
+        let mut wheel = HashedWheel::new(Resolution::TenMs, 8);
+        wheel.start("a", Duration::from_millis(20));
+        wheel.start("b", Duration::from_millis(100));
+        wheel.start("c", Duration::from_millis(260));
+        wheel.start_interval("d", Duration::from_millis(70));
+        wheel.start("e", Duration::from_millis(100));
+        wheel.stop("e");
+        assert_eq!(Some(Duration::from_millis(270)), wheel.remaining(&"c"));
+
+        let mut fired = Vec::new();
+        for tick in 1..30 {
+            for key in wheel.expire() {
+                fired.push((key, tick));
+            }
+        }
+        assert_eq!(vec![("a", 3), ("d", 8), ("b", 11), ("d", 16), ("d", 24), ("c", 27)], fired);
+        assert_eq!(1, wheel.len());
+    }
+
+    #[test]
+    fn restarted_timers_drop_their_old_entry() {
+        let mut wheel = HashedWheel::new(Resolution::Ms, 4);
+        wheel.start(1u64, Duration::from_millis(9));
+        assert!(wheel.restart(1, Duration::from_millis(1)));
+        assert!(wheel.expire().is_empty());
+        assert_eq!(vec![1], wheel.expire());
+        for _ in 0..12 {
+            assert!(wheel.expire().is_empty());
+        }
+        assert!(wheel.slots.iter().all(Vec::is_empty));
+    }
+}
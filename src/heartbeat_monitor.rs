@@ -0,0 +1,161 @@
+use std::hash::Hash;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Duration;
+use super::{CopyWheel, Wheel, Resolution};
+
+struct Peer {
+    timeout: Duration,
+    silent: bool
+}
+
+/// Tracks the liveness of a set of peers from the heartbeats they send.
+///
+/// Each peer has a timer that every `beat` restarts, and `expire` reports the peers whose timer
+/// ran out before their next beat. A silent peer stays known to the monitor, so a late beat
+/// revives it with the same timeout; `forget` removes it for good. Timeouts are set per peer,
+/// falling back to the monitor's default for peers that start beating unannounced. Like the
+/// wheels, `expire` must be called at the maximum resolution, and timeouts are rounded to the
+/// resolution of the wheel they land in. Timeouts shorter than a tick are rounded up to a tick.
+pub struct HeartbeatMonitor<K: Eq + Hash + Debug + Clone> {
+    default_timeout: Duration,
+    peers: HashMap<K, Peer>,
+    wheel: CopyWheel<K>
+}
+
+impl<K: Eq + Hash + Debug + Clone> HeartbeatMonitor<K> {
+    pub fn new(resolutions: Vec<Resolution>, default_timeout: Duration) -> HeartbeatMonitor<K> {
+        HeartbeatMonitor {
+            default_timeout,
+            peers: HashMap::new(),
+            wheel: CopyWheel::new(resolutions)
+        }
+    }
+
+    /// Start watching a peer with its own timeout, as if it had just beaten. Watching a peer that
+    /// is already watched replaces its timeout.
+    pub fn watch(&mut self, key: K, timeout: Duration) {
+        let timeout = timeout.max(self.wheel.tick_duration());
+        self.wheel.start(key.clone(), timeout);
+        self.peers.insert(key, Peer {
+            timeout,
+            silent: false
+        });
+    }
+
+    /// Record a heartbeat from a peer, restarting its timer. Peers that aren't watched yet are
+    /// watched with the default timeout.
+    ///
+    /// Returns true if the peer had been reported silent and is now alive again.
+    pub fn beat(&mut self, key: K) -> bool {
+        let (timeout, was_silent) = match self.peers.get_mut(&key) {
+            Some(peer) => {
+                let was_silent = peer.silent;
+                peer.silent = false;
+                (peer.timeout, was_silent)
+            },
+            None => {
+                let timeout = self.default_timeout;
+                self.watch(key, timeout);
+                return false;
+            }
+        };
+        self.wheel.start(key, timeout);
+        was_silent
+    }
+
+    /// Stop watching a peer. Returns false if it wasn't watched.
+    pub fn forget(&mut self, key: &K) -> bool {
+        if self.peers.remove(key).is_none() {
+            return false;
+        }
+        self.wheel.cancel(key);
+        true
+    }
+
+    /// Whether a peer is watched and hasn't gone silent
+    pub fn is_alive(&self, key: &K) -> bool {
+        self.peers.get(key).is_some_and(|peer| !peer.silent)
+    }
+
+    /// The peers that have gone silent and haven't beaten since
+    pub fn silent(&self) -> Vec<K> {
+        self.peers.iter()
+            .filter(|&(_, peer)| peer.silent)
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    /// The timeout of a watched peer
+    pub fn timeout(&self, key: &K) -> Option<Duration> {
+        self.peers.get(key).map(|peer| peer.timeout)
+    }
+
+    /// The number of watched peers, silent or not
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// How often `expire` must be called
+    pub fn tick_duration(&self) -> Duration {
+        self.wheel.tick_duration()
+    }
+
+    /// Advance the wheel by one tick and return the peers that just went silent
+    pub fn expire(&mut self) -> Vec<K> {
+        let silent = self.wheel.expire();
+        for key in &silent {
+            if let Some(peer) = self.peers.get_mut(key) {
+                peer.silent = true;
+            }
+        }
+        silent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    #[test]
+    fn peers_that_stop_beating_go_silent() {
+        let mut monitor = HeartbeatMonitor::new(vec![Resolution::TenMs, Resolution::Sec],
+                                                Duration::from_millis(30));
+        monitor.watch("slow", Duration::from_millis(80));
+        let mut silent = Vec::new();
+        for tick in 1..11 {
+            if tick < 5 {
+                assert!(!monitor.beat("fast"));
+            }
+            silent.extend(monitor.expire().into_iter().map(|key| (tick, key)));
+        }
+        assert_eq!(vec![(7, "fast"), (9, "slow")], silent);
+        assert!(!monitor.is_alive(&"fast"));
+        assert_eq!(2, monitor.silent().len());
+
+        assert!(monitor.beat("fast"));
+        assert!(monitor.is_alive(&"fast"));
+        assert_eq!(Some(Duration::from_millis(30)), monitor.timeout(&"fast"));
+        assert!(monitor.forget(&"slow"));
+        assert_eq!(1, monitor.len());
+    }
+
+    #[test]
+    fn timeouts_shorter_than_a_tick_still_go_silent() {
+        let mut monitor = HeartbeatMonitor::new(vec![Resolution::TenMs, Resolution::Sec],
+                                                Duration::from_millis(5));
+        monitor.watch("a", Duration::from_millis(5));
+        assert!(!monitor.beat("b"));
+        assert_eq!(Some(Duration::from_millis(10)), monitor.timeout(&"b"));
+        assert!(monitor.expire().is_empty());
+        let mut silent = monitor.expire();
+        silent.sort();
+        assert_eq!(vec!["a", "b"], silent);
+    }
+}
@@ -0,0 +1,122 @@
+use core::time::Duration;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use collections::HashMap;
+use super::{CopyWheel, Resolution, Wheel};
+
+/// A wheel keyed by strings, where each running key is stored once.
+///
+/// A `CopyWheel<String>` keeps a copy of each key in its hashmap and another in the slot for the
+/// timer, and long keys such as URLs or session tokens make that expensive. This wheel gives each
+/// running key a small id instead, and only the ids are copied into the slots. The string is
+/// shared between the lookup table and the id table, and handed back when the timer expires.
+pub struct InternedWheel {
+    wheel: CopyWheel<u32>,
+    ids: HashMap<Rc<str>, u32>,
+    names: Vec<Option<Rc<str>>>,
+    free: Vec<u32>
+}
+
+impl InternedWheel {
+    /// Create a wheel with the given resolutions. See `CopyWheel::new`.
+    pub fn new(resolutions: Vec<Resolution>) -> InternedWheel {
+        InternedWheel {
+            wheel: CopyWheel::new(resolutions),
+            ids: HashMap::new(),
+            names: Vec::new(),
+            free: Vec::new()
+        }
+    }
+
+    /// Start a timer with the given duration, restarting it if it is already running
+    pub fn start(&mut self, key: &str, time: Duration) {
+        let id = match self.ids.get(key) {
+            Some(id) => *id,
+            None => self.intern(key)
+        };
+        self.wheel.start(id, time);
+        // The duration may not have fit the wheel, in which case the id isn't needed
+        if !self.wheel.keys.contains_key(&id) {
+            self.release(id);
+        }
+    }
+
+    /// Cancel a timer
+    pub fn stop(&mut self, key: &str) {
+        if let Some(id) = self.ids.get(key).cloned() {
+            self.wheel.stop(id);
+            self.release(id);
+        }
+    }
+
+    /// Return the keys of any expired timers
+    pub fn expire(&mut self) -> Vec<Rc<str>> {
+        let expired = self.wheel.expire();
+        expired.into_iter().map(|id| self.release(id)).collect()
+    }
+
+    /// The number of running timers
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    fn intern(&mut self, key: &str) -> u32 {
+        let name: Rc<str> = Rc::from(key);
+        let id = match self.free.pop() {
+            Some(id) => {
+                self.names[id as usize] = Some(name.clone());
+                id
+            },
+            None => {
+                self.names.push(Some(name.clone()));
+                (self.names.len() - 1) as u32
+            }
+        };
+        self.ids.insert(name, id);
+        id
+    }
+
+    fn release(&mut self, id: u32) -> Rc<str> {
+        let name = self.names[id as usize].take().unwrap();
+        self.ids.remove(&*name);
+        self.free.push(id);
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    #[test]
+    fn interned_keys_expire() {
+        let mut wheel = InternedWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start("https://example.com/a", Duration::from_millis(20));
+        wheel.start("https://example.com/b", Duration::from_millis(10));
+        wheel.start("https://example.com/b", Duration::from_millis(20));
+        wheel.start("https://example.com/c", Duration::from_millis(10));
+        wheel.stop("https://example.com/c");
+        // Too short for the wheel, so it isn't kept
+        wheel.start("https://example.com/d", Duration::from_millis(1));
+        assert_eq!(2, wheel.len());
+
+        // The id freed by "c" is reused
+        wheel.start("https://example.com/e", Duration::from_millis(10));
+        assert_eq!(3, wheel.names.len());
+
+        assert!(wheel.expire().is_empty());
+        let expired: Vec<Rc<str>> = wheel.expire();
+        assert_eq!(vec![Rc::from("https://example.com/e")], expired);
+        let mut expired = wheel.expire();
+        expired.sort();
+        assert_eq!(vec![Rc::from("https://example.com/a"), Rc::from("https://example.com/b")],
+                   expired);
+        assert!(wheel.is_empty());
+    }
+}
@@ -0,0 +1,77 @@
+use collections::{hash_map, HashMap};
+#[cfg(feature = "alloc-wheel")]
+use alloc::rc::Rc;
+use core::time::Duration;
+use super::remaining;
+
+enum Keys<T> {
+    #[cfg(feature = "copy-wheel")]
+    Copied(hash_map::IntoIter<T, u64>),
+    #[cfg(feature = "alloc-wheel")]
+    Allocated(hash_map::IntoIter<Rc<T>, u64>)
+}
+
+/// A consuming iterator over the timers still pending in a wheel.
+///
+/// Each key is yielded along with the time remaining until it would have expired. Since timers are
+/// only tracked to the tick, the remaining time is rounded up to a whole number of ticks.
+pub struct IntoIter<T> {
+    keys: Keys<T>,
+    tick: u64,
+    tick_duration: Duration
+}
+
+impl<T> IntoIter<T> {
+    #[cfg(feature = "copy-wheel")]
+    pub(crate) fn copied(keys: HashMap<T, u64>, tick: u64, tick_duration: Duration) -> IntoIter<T> {
+        IntoIter {
+            keys: Keys::Copied(keys.into_iter()),
+            tick,
+            tick_duration
+        }
+    }
+
+    #[cfg(feature = "alloc-wheel")]
+    pub(crate) fn allocated(keys: HashMap<Rc<T>, u64>,
+                            tick: u64,
+                            tick_duration: Duration) -> IntoIter<T>
+    {
+        IntoIter {
+            keys: Keys::Allocated(keys.into_iter()),
+            tick,
+            tick_duration
+        }
+    }
+}
+
+impl<T> Iterator for IntoIter<T> {
+    type Item = (T, Duration);
+
+    fn next(&mut self) -> Option<(T, Duration)> {
+        let (key, deadline) = match self.keys {
+            #[cfg(feature = "copy-wheel")]
+            Keys::Copied(ref mut keys) => keys.next()?,
+            #[cfg(feature = "alloc-wheel")]
+            Keys::Allocated(ref mut keys) => {
+                let (key, deadline) = keys.next()?;
+                // The wheel slots only hold weak references, so this is the only strong one
+                match Rc::try_unwrap(key) {
+                    Ok(key) => (key, deadline),
+                    Err(_) => unreachable!()
+                }
+            }
+        };
+        Some((key, remaining(self.tick, deadline, self.tick_duration)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self.keys {
+            #[cfg(feature = "copy-wheel")]
+            Keys::Copied(ref keys) => keys.size_hint(),
+            #[cfg(feature = "alloc-wheel")]
+            Keys::Allocated(ref keys) => keys.size_hint()
+        }
+    }
+}
+
+impl<T> ExactSizeIterator for IntoIter<T> {}
@@ -0,0 +1,312 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::time::Duration;
+use alloc::vec::Vec;
+use collections::HashMap;
+use super::{Resolution, Wheel};
+use super::{remaining, resolution_duration, slots_ahead, wheel_sizes};
+
+struct Node<T> {
+    key: T,
+    deadline: u64,
+    period: Option<Duration>,
+    // The wheel and slot the node is linked into
+    wheel: usize,
+    slot: usize,
+    prev: Option<usize>,
+    next: Option<usize>
+}
+
+/// A wheel whose slots are doubly linked lists of timers, so stopping a timer unlinks it straight
+/// away.
+///
+/// The other wheels leave an entry behind in the slot of a cancelled or restarted timer, which is
+/// skipped when the slot is visited. That memory adds up when nearly every timer is cancelled, as
+/// with TCP retransmission timers. Here a slot only ever holds running timers, at the cost of a
+/// node with two links per timer.
+pub struct IntrusiveWheel<T: Eq + Hash + Debug + Clone> {
+    resolutions: Vec<Resolution>,
+    keys: HashMap<T, usize>,
+    nodes: Vec<Option<Node<T>>>,
+    free: Vec<usize>,
+    // The first node in each slot of each wheel
+    heads: Vec<Vec<Option<usize>>>,
+    slot_indexes: Vec<usize>,
+    tick: u64
+}
+
+impl<T: Eq + Hash + Debug + Clone> IntrusiveWheel<T> {
+    /// Create a set of hierarchical inner wheels. See `CopyWheel::new`.
+    pub fn new(mut resolutions: Vec<Resolution>) -> IntrusiveWheel<T> {
+        let sizes = wheel_sizes(&mut resolutions);
+        IntrusiveWheel {
+            resolutions,
+            keys: HashMap::new(),
+            nodes: Vec::new(),
+            free: Vec::new(),
+            heads: sizes.iter().map(|size| vec![None; *size]).collect(),
+            slot_indexes: vec![0; sizes.len()],
+            tick: 0
+        }
+    }
+
+    /// The number of running timers
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn contains(&self, key: &T) -> bool {
+        self.keys.contains_key(key)
+    }
+
+    /// The time left until a timer expires, rounded up to a whole number of ticks
+    pub fn remaining(&self, key: &T) -> Option<Duration> {
+        let index = *self.keys.get(key)?;
+        let deadline = self.node(index).deadline;
+        Some(remaining(self.tick, deadline, self.tick_duration()))
+    }
+
+    fn node(&self, index: usize) -> &Node<T> {
+        self.nodes[index].as_ref().expect("a running timer has a node")
+    }
+
+    fn node_mut(&mut self, index: usize) -> &mut Node<T> {
+        self.nodes[index].as_mut().expect("a running timer has a node")
+    }
+
+    // Find the wheel, slot and deadline for a timer of `time`, or `None` if it is too short
+    fn place(&self, time: Duration) -> Option<(usize, usize, u64)> {
+        for wheel in (0..self.resolutions.len()).rev() {
+            let ahead = slots_ahead(self.resolutions[wheel], time, wheel == self.resolutions.len() - 1);
+            if ahead == 1 {
+                continue;
+            }
+            let size = self.heads[wheel].len();
+            let ahead = ahead.min(size);
+            let ticks_per_slot: u64 = self.heads[..wheel].iter()
+                .map(|slots| slots.len() as u64)
+                .product();
+            let deadline = (self.tick / ticks_per_slot + ahead as u64) * ticks_per_slot;
+            return Some((wheel, (self.slot_indexes[wheel] + ahead) % size, deadline));
+        }
+        None
+    }
+
+    fn link(&mut self, index: usize, wheel: usize, slot: usize) {
+        let next = self.heads[wheel][slot];
+        if let Some(next) = next {
+            self.node_mut(next).prev = Some(index);
+        }
+        {
+            let node = self.node_mut(index);
+            node.wheel = wheel;
+            node.slot = slot;
+            node.prev = None;
+            node.next = next;
+        }
+        self.heads[wheel][slot] = Some(index);
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let (wheel, slot, prev, next) = {
+            let node = self.node(index);
+            (node.wheel, node.slot, node.prev, node.next)
+        };
+        match prev {
+            Some(prev) => self.node_mut(prev).next = next,
+            None => self.heads[wheel][slot] = next
+        }
+        if let Some(next) = next {
+            self.node_mut(next).prev = prev;
+        }
+    }
+
+    // Move a running timer to a new deadline, returning false if `time` is too short
+    fn reschedule(&mut self, index: usize, time: Duration) -> bool {
+        match self.place(time) {
+            Some((wheel, slot, deadline)) => {
+                self.unlink(index);
+                self.link(index, wheel, slot);
+                self.node_mut(index).deadline = deadline;
+                true
+            },
+            None => false
+        }
+    }
+}
+
+impl<T: Eq + Hash + Debug + Clone> Wheel<T> for IntrusiveWheel<T> {
+    type Expired = Vec<T>;
+
+    /// Start a timer, restarting it if it's already running. Durations shorter than a tick are
+    /// ignored.
+    fn start(&mut self, key: T, time: Duration) {
+        if let Some(&index) = self.keys.get(&key) {
+            if self.reschedule(index, time) {
+                self.node_mut(index).period = None;
+            }
+            return;
+        }
+        let (wheel, slot, deadline) = match self.place(time) {
+            Some(placement) => placement,
+            None => return
+        };
+        let node = Node {
+            key: key.clone(),
+            deadline,
+            period: None,
+            wheel,
+            slot,
+            prev: None,
+            next: None
+        };
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = Some(node);
+                index
+            },
+            None => {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            }
+        };
+        self.link(index, wheel, slot);
+        self.keys.insert(key, index);
+    }
+
+    fn stop(&mut self, key: T) -> Option<Duration> {
+        let left = self.remaining(&key)?;
+        let index = self.keys.remove(&key)?;
+        self.unlink(index);
+        self.nodes[index] = None;
+        self.free.push(index);
+        Some(left)
+    }
+
+    fn expire(&mut self) -> Vec<T> {
+        self.tick += 1;
+        let mut due = Vec::new();
+        for wheel in 0..self.heads.len() {
+            let size = self.heads[wheel].len();
+            let slot = (self.slot_indexes[wheel] + 1) % size;
+            self.slot_indexes[wheel] = slot;
+            let mut next = self.heads[wheel][slot].take();
+            while let Some(index) = next {
+                next = self.node(index).next;
+                due.push(index);
+            }
+
+            // We haven't wrapped around to the next wheel
+            if slot != 0 {
+                break;
+            }
+        }
+        // Every timer in a slot is due when the slot is visited
+        let mut expired = Vec::with_capacity(due.len());
+        for index in due {
+            let period = self.node(index).period;
+            let key = self.node(index).key.clone();
+            let rearmed = match period {
+                Some(period) => match self.place(period) {
+                    Some((wheel, slot, deadline)) => {
+                        self.link(index, wheel, slot);
+                        self.node_mut(index).deadline = deadline;
+                        true
+                    },
+                    None => false
+                },
+                None => false
+            };
+            if !rearmed {
+                self.keys.remove(&key);
+                self.nodes[index] = None;
+                self.free.push(index);
+            }
+            expired.push(key);
+        }
+        expired
+    }
+
+    fn start_with_delay(&mut self, key: T, delay: Duration, period: Duration) {
+        self.start(key.clone(), delay);
+        let period = period.max(self.tick_duration());
+        if let Some(&index) = self.keys.get(&key) {
+            self.node_mut(index).period = Some(period);
+        }
+    }
+
+    fn restart(&mut self, key: T, time: Duration) -> bool {
+        match self.keys.get(&key) {
+            Some(&index) => self.reschedule(index, time),
+            None => false
+        }
+    }
+
+    fn tick_duration(&self) -> Duration {
+        resolution_duration(self.resolutions[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{Resolution, Wheel};
+
+    fn linked(wheel: &IntrusiveWheel<u64>) -> usize {
+        let mut count = 0;
+        for slots in &wheel.heads {
+            for head in slots {
+                let mut next = *head;
+                while let Some(index) = next {
+                    count += 1;
+                    next = wheel.node(index).next;
+                }
+            }
+        }
+        count
+    }
+
+    #[test]
+    fn stop_unlinks_straight_away() {
+        let mut wheel = IntrusiveWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        for key in 0..100 {
+            wheel.start(key, Duration::from_millis(20));
+        }
+        for key in 1..100 {
+            assert_eq!(Some(Duration::from_millis(30)), wheel.stop(key));
+        }
+        assert_eq!(1, linked(&wheel));
+        wheel.start(7, Duration::from_secs(2));
+        wheel.start(7, Duration::from_millis(30));
+        assert_eq!(2, linked(&wheel));
+        assert_eq!(None, wheel.stop(50));
+
+        assert!(wheel.expire().is_empty());
+        assert!(wheel.expire().is_empty());
+        assert_eq!(vec![0], wheel.expire());
+        assert_eq!(vec![7], wheel.expire());
+        assert_eq!(0, linked(&wheel));
+        assert!(wheel.is_empty());
+        assert!(wheel.nodes.iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn interval_timers_are_relinked() {
+        let mut wheel = IntrusiveWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start_interval(1, Duration::from_millis(20));
+        let mut fired = Vec::new();
+        for tick in 1..13 {
+            if !wheel.expire().is_empty() {
+                fired.push(tick);
+            }
+        }
+        assert_eq!(vec![3, 6, 9, 12], fired);
+        assert_eq!(Some(Duration::from_millis(30)), wheel.stop(1));
+        assert_eq!(0, linked(&wheel));
+    }
+}
@@ -0,0 +1,235 @@
+use std::hash::Hash;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Duration;
+use super::{CopyWheel, Wheel, Resolution};
+
+/// An event reported by `KeepaliveManager::expire`
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum KeepaliveEvent<P> {
+    /// Nothing has been sent to the peer for a keepalive interval
+    SendKeepalive(P),
+    /// Nothing has been heard from the peer within its dead timeout. The peer has been removed.
+    PeerDead(P)
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+enum Kind {
+    Keepalive,
+    Dead
+}
+
+struct Peer {
+    interval: Duration,
+    dead_timeout: Duration,
+    keepalive: u64,
+    dead: u64
+}
+
+/// Keepalive and dead peer detection timers for a set of peers.
+///
+/// Each peer has two timers sharing one wheel. The keepalive timer fires every interval and is
+/// pushed back by `sent_to`, so peers with other traffic aren't sent redundant keepalives. The dead
+/// timer is pushed back by `heard_from` and, if it ever fires, the peer is reported dead and
+/// forgotten. Intervals and timeouts shorter than a tick are rounded up to a tick.
+pub struct KeepaliveManager<P: Eq + Hash + Debug + Clone> {
+    interval: Duration,
+    dead_timeout: Duration,
+    peers: HashMap<P, Peer>,
+    wheel: CopyWheel<(P, Kind, u64)>,
+    next_generation: u64
+}
+
+impl<P: Eq + Hash + Debug + Clone> KeepaliveManager<P> {
+    /// Create a manager whose peers use the given keepalive interval and dead timeout by default
+    pub fn new(resolutions: Vec<Resolution>,
+               interval: Duration,
+               dead_timeout: Duration) -> KeepaliveManager<P>
+    {
+        KeepaliveManager {
+            interval,
+            dead_timeout,
+            peers: HashMap::new(),
+            wheel: CopyWheel::new(resolutions),
+            next_generation: 0
+        }
+    }
+
+    /// Start tracking a peer with the default interval and dead timeout
+    pub fn add_peer(&mut self, peer: P) {
+        let (interval, dead_timeout) = (self.interval, self.dead_timeout);
+        self.add_peer_with(peer, interval, dead_timeout);
+    }
+
+    /// Start tracking a peer with its own interval and dead timeout.
+    /// Adding a peer that is already tracked restarts both of its timers.
+    pub fn add_peer_with(&mut self, peer: P, interval: Duration, dead_timeout: Duration) {
+        self.remove_peer(&peer);
+        let keepalive = self.arm(peer.clone(), Kind::Keepalive, interval);
+        let dead = self.arm(peer.clone(), Kind::Dead, dead_timeout);
+        self.peers.insert(peer, Peer {
+            interval,
+            dead_timeout,
+            keepalive,
+            dead
+        });
+    }
+
+    /// Stop tracking a peer. Returns false if the peer wasn't tracked.
+    pub fn remove_peer(&mut self, peer: &P) -> bool {
+        match self.peers.remove(peer) {
+            Some(state) => {
+                self.wheel.stop((peer.clone(), Kind::Keepalive, state.keepalive));
+                self.wheel.stop((peer.clone(), Kind::Dead, state.dead));
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Record that a message was received from the peer, restarting its dead timer.
+    /// Returns false if the peer isn't tracked.
+    pub fn heard_from(&mut self, peer: &P) -> bool {
+        self.restart(peer, Kind::Dead)
+    }
+
+    /// Record that a message was sent to the peer, restarting its keepalive interval.
+    /// Returns false if the peer isn't tracked.
+    pub fn sent_to(&mut self, peer: &P) -> bool {
+        self.restart(peer, Kind::Keepalive)
+    }
+
+    pub fn contains(&self, peer: &P) -> bool {
+        self.peers.contains_key(peer)
+    }
+
+    /// The number of tracked peers
+    pub fn len(&self) -> usize {
+        self.peers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.peers.is_empty()
+    }
+
+    /// Advance the wheel by one tick and return any keepalives due or peers found dead
+    pub fn expire(&mut self) -> Vec<KeepaliveEvent<P>> {
+        let mut events = Vec::new();
+        for (peer, kind, generation) in self.wheel.expire() {
+            let current = match self.peers.get(&peer) {
+                Some(state) => match kind {
+                    Kind::Keepalive => state.keepalive,
+                    Kind::Dead => state.dead
+                },
+                None => continue
+            };
+            if current != generation {
+                continue;
+            }
+            match kind {
+                Kind::Keepalive => {
+                    self.restart(&peer, Kind::Keepalive);
+                    events.push(KeepaliveEvent::SendKeepalive(peer));
+                },
+                Kind::Dead => {
+                    self.remove_peer(&peer);
+                    events.push(KeepaliveEvent::PeerDead(peer));
+                }
+            }
+        }
+        events
+    }
+
+    fn restart(&mut self, peer: &P, kind: Kind) -> bool {
+        let (old, duration) = match self.peers.get(peer) {
+            Some(state) => match kind {
+                Kind::Keepalive => (state.keepalive, state.interval),
+                Kind::Dead => (state.dead, state.dead_timeout)
+            },
+            None => return false
+        };
+        self.wheel.stop((peer.clone(), kind, old));
+        let generation = self.arm(peer.clone(), kind, duration);
+        let state = self.peers.get_mut(peer).unwrap();
+        match kind {
+            Kind::Keepalive => state.keepalive = generation,
+            Kind::Dead => state.dead = generation
+        }
+        true
+    }
+
+    fn arm(&mut self, peer: P, kind: Kind, duration: Duration) -> u64 {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        let duration = duration.max(self.wheel.tick_duration());
+        self.wheel.start((peer, kind, generation), duration);
+        generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    fn manager() -> KeepaliveManager<&'static str> {
+        KeepaliveManager::new(vec![Resolution::TenMs, Resolution::Sec],
+                              Duration::from_millis(20),
+                              Duration::from_millis(50))
+    }
+
+    #[test]
+    fn silent_peer_gets_keepalives_then_dies() {
+        let mut manager = manager();
+        manager.add_peer("a");
+        let mut events = Vec::new();
+        for _ in 0..20 {
+            events.extend(manager.expire());
+        }
+        // A keepalive at tick 3. The peer is dead at tick 6, suppressing the second keepalive.
+        assert_eq!(vec![KeepaliveEvent::SendKeepalive("a"), KeepaliveEvent::PeerDead("a")], events);
+        assert!(!manager.contains(&"a"));
+    }
+
+    #[test]
+    fn durations_shorter_than_a_tick_still_fire() {
+        let mut manager = manager();
+        manager.add_peer_with("a", Duration::from_millis(5), Duration::from_secs(5));
+        manager.add_peer_with("b", Duration::from_secs(5), Duration::from_millis(5));
+        let mut events = Vec::new();
+        for _ in 0..6 {
+            events.extend(manager.expire());
+        }
+        assert_eq!(vec![KeepaliveEvent::SendKeepalive("a"), KeepaliveEvent::PeerDead("b"),
+                        KeepaliveEvent::SendKeepalive("a"), KeepaliveEvent::SendKeepalive("a")],
+                   events);
+        assert!(!manager.contains(&"b"));
+    }
+
+    #[test]
+    fn heard_from_keeps_peer_alive() {
+        let mut manager = manager();
+        manager.add_peer("a");
+        for _ in 0..50 {
+            for event in manager.expire() {
+                assert_eq!(KeepaliveEvent::SendKeepalive("a"), event);
+            }
+            assert!(manager.heard_from(&"a"));
+        }
+        assert_eq!(1, manager.len());
+    }
+
+    #[test]
+    fn sent_to_defers_keepalive() {
+        let mut manager = manager();
+        manager.add_peer_with("a", Duration::from_millis(20), Duration::from_secs(5));
+        for _ in 0..50 {
+            assert!(manager.expire().is_empty());
+            assert!(manager.sent_to(&"a"));
+        }
+        assert!(manager.remove_peer(&"a"));
+        assert!(!manager.heard_from(&"a"));
+        assert!(manager.is_empty());
+    }
+}
@@ -9,13 +9,22 @@
 //! get to 1 sec).
 //!
 //! In order for the timer to operate correctly, it must tick at the maximum resolution. For
-//! instance if 10ms and 1s resolutions are used, `expire()` must be called every 10ms.
+//! instance if 10ms and 1s resolutions are used, `expire()` must be called every 10ms. A loop that
+//! stalls now and then doesn't have to let its timers drift late. `expire_until` counts the ticks
+//! due since the wheel was created and runs the ones that were missed on the next call, and
+//! `FixedStep` does the same from the time elapsed between calls for the helpers that only have
+//! `expire()`. `run_blocking` and `Timer::drive` schedule each tick from when they started and
+//! catch up by themselves.
 //!
 //! The minimum length of a timer is limited by the highest resolution. For instance if 10ms and 1s
-//! resolutions were used, the minimum length of a timer would be 10ms.
+//! resolutions were used, the minimum length of a timer would be 10ms. `Resolution::TenUs` and
+//! `Resolution::HundredUs` go below a millisecond for uses like RTT probes and packet pacing, as
+//! long as something can call `expire()` that often.
 //!
-//! The maximum length of a timer is limited by the lowest resolution. For instance if 10ms, and 1s
-//! resolutions were used, the maximum length of a timer would be 59s.
+//! The span of the wheels is limited by the lowest resolution. For instance if 10ms, and 1s
+//! resolutions were used, the span would be 59s. `CopyWheel` and `AllocWheel` keep longer timers
+//! in an overflow list sorted by deadline, and move them into the wheels once they come within
+//! range. The other wheels clamp them to the last slot.
 //!
 //! There is no migration between wheels. A timer is assigned to a single wheel and is scheduled at
 //! it's minimum resolution. E.g. If a timer is scheduled for 1.3s it will be scheduled to
@@ -24,34 +33,488 @@
 //! track of offsets for the next inner wheel for wheel to wheel migration, and thus save memory.
 //! And since the migration ddoesn't actually occur, we save cpu, and potentially
 //! extra allocations.
+//!
+//! Wheels can opt in to migration with `set_precise` when timers need to expire at the resolution
+//! of the first wheel, whichever wheel they start in. `set_slack` goes the other way, letting
+//! timers expire late so that those due close together fire on the same tick.
+//!
+//! The crate supports `no_std` builds with `alloc` when the default `std` feature is disabled. The
+//! wheels are available in such builds, while the helpers that need std's randomness or hashing
+//! are not.
+//!
+//! The crate builds for `wasm32-unknown-unknown`, where the `browser` feature adds a driver that
+//! ticks a wheel from `requestAnimationFrame` or `setInterval` timestamps. Reading `Instant` panics
+//! on that target, so with the `std` feature the wheels don't read the clock when they are
+//! created, and `last_tick_at` panics until `sync_clock` or `expire_until` has given them the
+//! time.
+//!
+//! The `uuid` feature adds `UuidKey`, a 16 byte key that hashes faster than a plain byte array.
+//!
+//! The `ffi` feature exposes a C API in the `ffi` module for embedding a wheel in C or C++
+//! programs.
+//!
+//! Each wheel sits behind its own feature, `alloc-wheel`, `copy-wheel`, `u64-wheel`, `slab-wheel`,
+//! `intrusive-wheel` and `hashed-wheel`, so that builds can include only the ones they use. All
+//! are enabled by default, and at least one of `alloc-wheel` and `copy-wheel` is required. The
+//! helpers are built on `CopyWheel` and need the `copy-wheel` feature.
+
+#![cfg_attr(all(not(feature = "std"), not(test)), no_std)]
 
+#[cfg(any(feature = "std", test))]
+extern crate core;
+#[macro_use]
+extern crate alloc;
+
+#[cfg(not(any(feature = "alloc-wheel", feature = "copy-wheel")))]
+compile_error!("at least one of the `alloc-wheel` and `copy-wheel` features must be enabled");
+
+#[macro_use]
+mod macros;
+
+pub mod prelude;
+
+#[cfg(feature = "alloc-wheel")]
 mod alloc_wheel;
+mod any_wheel;
+#[cfg(feature = "browser")]
+mod browser;
+mod builder;
+mod callback_wheel;
+#[cfg(feature = "std")]
+mod clock;
+mod collections;
+#[cfg(feature = "copy-wheel")]
+mod debounce;
+#[cfg(feature = "copy-wheel")]
 mod copy_wheel;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+mod cron_wheel;
+#[cfg(feature = "std")]
+mod driver;
+mod entry;
+mod error;
+#[cfg(feature = "copy-wheel")]
+mod expiring_map;
+mod grouped_wheel;
+mod guard;
+#[cfg(feature = "hashed-wheel")]
+mod hashed_wheel;
+#[cfg(feature = "intrusive-wheel")]
+mod intrusive_wheel;
+#[cfg(feature = "copy-wheel")]
+mod interned_wheel;
+#[cfg(all(feature = "ffi", feature = "copy-wheel"))]
+pub mod ffi;
+#[cfg(any(feature = "alloc-wheel", feature = "copy-wheel"))]
+mod into_iter;
+#[cfg(feature = "copy-wheel")]
+mod map_wheel;
+mod observer;
+#[cfg(feature = "std")]
+mod queued_wheel;
+mod scope;
+#[cfg(feature = "std")]
+mod service;
+#[cfg(feature = "slab-wheel")]
+mod slab_wheel;
+mod fixed_step;
+mod ticks;
+mod token_wheel;
+mod tracked_wheel;
+#[cfg(feature = "u64-wheel")]
+mod u64_wheel;
+#[cfg(feature = "uuid")]
+mod uuid_key;
+#[cfg(any(test, not(feature = "std"), feature = "u64-wheel"))]
+mod table;
 
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+mod animations;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+mod concurrent_wheel;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+mod heartbeat_monitor;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+mod keepalive_manager;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+mod lru_ttl_cache;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+mod periodic_tasks;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+mod raft_timeouts;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+mod rate_limiter;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+mod retry_scheduler;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+mod rng;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+mod rto_manager;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+mod sleep;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+mod watchdog;
+
+#[cfg(feature = "alloc-wheel")]
 pub use alloc_wheel::AllocWheel;
+#[cfg(feature = "copy-wheel")]
 pub use copy_wheel::CopyWheel;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+pub use cron_wheel::{CronSchedule, CronWheel};
+pub use entry::{Entry, OccupiedEntry, VacantEntry};
+pub use error::{FerrisError, StartError};
+#[cfg(feature = "copy-wheel")]
+pub use expiring_map::ExpiringMap;
+pub use grouped_wheel::GroupedWheel;
+pub use guard::{SharedWheel, TimerGuard};
+#[cfg(feature = "hashed-wheel")]
+pub use hashed_wheel::HashedWheel;
+#[cfg(feature = "intrusive-wheel")]
+pub use intrusive_wheel::IntrusiveWheel;
+#[cfg(feature = "copy-wheel")]
+pub use interned_wheel::InternedWheel;
+#[cfg(feature = "copy-wheel")]
+pub use map_wheel::MapWheel;
+pub use observer::{ObservedWheel, WheelObserver};
+#[cfg(feature = "std")]
+pub use queued_wheel::{QueuedWheel, QueueHandle};
+pub use scope::TimerScope;
+#[cfg(feature = "std")]
+pub use service::TimerService;
+#[cfg(feature = "slab-wheel")]
+pub use slab_wheel::{SlabWheel, TimerId};
+#[cfg(any(feature = "alloc-wheel", feature = "copy-wheel"))]
+pub use into_iter::IntoIter;
+pub use any_wheel::AnyWheel;
+pub use builder::{WheelBuilder, WheelKind};
+pub use callback_wheel::{Callback, CallbackWheel};
+#[cfg(feature = "copy-wheel")]
+pub use debounce::{Debouncer, Throttler};
+pub use fixed_step::FixedStep;
+#[cfg(feature = "std")]
+pub use clock::{Clock, MockClock, SystemClock};
+#[cfg(feature = "std")]
+pub use driver::{run_blocking, run_blocking_with};
+pub use ticks::Ticks;
+pub use token_wheel::{TimerToken, TokenWheel};
+pub use tracked_wheel::{ExpiredTimer, TrackedWheel};
+#[cfg(feature = "u64-wheel")]
+pub use u64_wheel::U64Wheel;
+#[cfg(feature = "uuid")]
+pub use uuid_key::UuidKey;
+#[cfg(all(feature = "uuid", feature = "alloc-wheel"))]
+pub use uuid_key::UuidAllocWheel;
+#[cfg(all(feature = "uuid", feature = "copy-wheel"))]
+pub use uuid_key::UuidCopyWheel;
+#[cfg(feature = "browser")]
+pub use browser::BrowserDriver;
+
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+pub use animations::{Animations, AnimationEvent};
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+pub use concurrent_wheel::ConcurrentWheel;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+pub use heartbeat_monitor::HeartbeatMonitor;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+pub use keepalive_manager::{KeepaliveManager, KeepaliveEvent};
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+pub use lru_ttl_cache::LruTtlCache;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+pub use periodic_tasks::PeriodicTasks;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+pub use raft_timeouts::{RaftTimeouts, RaftTimeout};
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+pub use rate_limiter::{RateLimiter, RateDecision};
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+pub use retry_scheduler::{Backoff, RetryScheduler};
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+pub use rto_manager::RtoManager;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+pub use sleep::{Sleep, Timer, TimerHandle};
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+pub use watchdog::{Watchdog, Stall};
 
-use std::hash::Hash;
-use std::fmt::Debug;
-use std::time::Duration;
+use core::borrow::Borrow;
+use core::cmp::Ordering;
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::time::Duration;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use collections::HashMap;
 
 /// A resolution for a wheel in the hierarchy
 ///
-/// The tick rate of the wheel must match the highest resolution of the wheel.
-#[derive(Debug, Eq, PartialEq, Ord, PartialOrd)]
+/// The tick rate of the wheel must match the highest resolution of the wheel. Resolutions are
+/// ordered from finest to coarsest.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum Resolution {
+    TenUs,
+    HundredUs,
     Ms,
     TenMs,
     HundredMs,
     Sec,
     Min,
-    Hour
+    Hour,
+    Day,
+    Week,
+    /// A wheel whose slots last for the given duration, for tick rates that none of the other
+    /// resolutions match, such as a 16ms game loop.
+    ///
+    /// A custom resolution next to another resolution must divide it, or be divided by it, into
+    /// a whole number of slots, and one directly above a resolution finer than a second can't be
+    /// longer than a second. The coarsest wheel gets 60 slots if its resolution is custom.
+    Custom(Duration)
+}
+
+impl Resolution {
+    fn is_custom(self) -> bool {
+        matches!(self, Resolution::Custom(_))
+    }
+}
+
+impl Ord for Resolution {
+    fn cmp(&self, other: &Resolution) -> Ordering {
+        resolution_duration(*self).cmp(&resolution_duration(*other))
+            .then(self.is_custom().cmp(&other.is_custom()))
+    }
+}
+
+impl PartialOrd for Resolution {
+    fn partial_cmp(&self, other: &Resolution) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// What `start` does with a timer that is already running.
+///
+/// `restart` always re-arms the timer, and `try_start` always reports `StartError::DuplicateKey`,
+/// whatever the policy.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum DuplicatePolicy {
+    /// Restart the timer with the new duration. This is the default.
+    #[default]
+    Replace,
+    /// Keep whichever of the running and new deadlines is sooner
+    KeepEarliest,
+    /// Keep whichever of the running and new deadlines is later
+    KeepLatest,
+    /// Leave the running timer alone
+    Reject
 }
 
+impl DuplicatePolicy {
+    // Whether a timer due at tick `old` keeps its deadline when started again to be due at `new`
+    fn keeps_running(self, old: u64, new: u64) -> bool {
+        match self {
+            DuplicatePolicy::Replace => false,
+            DuplicatePolicy::KeepEarliest => old <= new,
+            DuplicatePolicy::KeepLatest => old >= new,
+            DuplicatePolicy::Reject => true
+        }
+    }
+}
+
+/// How a wheel that isn't precise rounds a duration to the slots of the wheel it lands in.
+///
+/// A timer goes in the coarsest wheel whose resolution it is at least as long as, so with 1 second
+/// and 10ms wheels a 1.3s timer goes in the 1 second wheel. Precise wheels expire timers on the
+/// tick after their duration whatever the rounding.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum Rounding {
+    /// Round up to the next slot, so 1.3s becomes 2 seconds. This is the default.
+    #[default]
+    Up,
+    /// Round down, so 1.3s becomes 1 second, for timers that are better early than late
+    Down,
+    /// Round to the nearest slot, so 1.3s becomes 1 second and 1.7s becomes 2
+    Nearest
+}
+
+impl Rounding {
+    // The slots ahead of the current one to put a timer of `time` in at `resolution`, given the
+    // `slots_ahead` of the timer. Returns 0 if the timer belongs in a finer wheel.
+    fn slots(self, ahead: usize, resolution: Resolution, time: Duration) -> usize {
+        if ahead == 1 {
+            return 0;
+        }
+        match self {
+            Rounding::Up => ahead,
+            Rounding::Down => ahead - 1,
+            Rounding::Nearest => {
+                let slot = resolution_duration(resolution).as_nanos();
+                if time.as_nanos() % slot * 2 >= slot {
+                    ahead
+                } else {
+                    ahead - 1
+                }
+            }
+        }
+    }
+}
+
+/// Whether a timer may expire late or early.
+///
+/// The tick in progress when a timer starts has already partly passed, so no tick lies exactly
+/// one duration away. Timers are put one slot further on to make up for it, so they never expire
+/// early, unless the policy is `NoLater`. In a wheel that isn't precise the slot is one of the
+/// wheel the timer lands in, after its `Rounding`. Slack still lets timers expire late.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum TickPolicy {
+    /// Expire on the first tick after the duration has passed, up to a tick late. This is the
+    /// default.
+    #[default]
+    NoEarlier,
+    /// Expire on the last tick before the duration has passed, up to a tick early, or up to a slot
+    /// early in coarser wheels, for timers such as watchdogs that must not miss their deadline
+    NoLater
+}
+
+impl TickPolicy {
+    // The tick to expire a timer started at `tick` on, given the deadline from `exact_deadline`
+    fn deadline(self, tick: u64, target: u64) -> u64 {
+        match self {
+            TickPolicy::NoEarlier => target,
+            TickPolicy::NoLater => (target - 1).max(tick + 1)
+        }
+    }
+
+    // The slots ahead to put a timer in, given the slots from `Rounding::slots`. A timer is never
+    // moved down to a finer wheel, where the time left might be counted wrongly.
+    fn slots(self, slots: usize) -> usize {
+        match self {
+            TickPolicy::NoLater if slots > 1 => slots - 1,
+            _ => slots
+        }
+    }
+}
+
+/// The operations common to all wheels.
+///
+/// Each implementation chooses the collection that `expire` returns, so a wheel that avoids
+/// allocating isn't forced to build a `Vec`.
+///
+/// The trait is object safe, so the implementation can be chosen at runtime and used through a
+/// `Box<dyn Wheel<T, Expired = Vec<T>>>`. See `WheelBuilder::build_boxed`.
 pub trait Wheel<T: Eq + Hash + Debug + Clone> {
+    /// The keys returned from a call to `expire`
+    type Expired: IntoIterator<Item = T>;
+
     fn start(&mut self, key: T, time: Duration);
-    fn stop(&mut self, key: T);
-    fn expire(&mut self) -> Vec<T>;
+
+    /// Cancel a timer, returning the time that was left before it would have expired, or `None`
+    /// if it wasn't running.
+    ///
+    /// The time left is rounded up to a whole number of ticks. The key is taken by value to keep
+    /// the trait object safe; the wheels' inherent `cancel` takes a reference instead.
+    fn stop(&mut self, key: T) -> Option<Duration>;
+    fn expire(&mut self) -> Self::Expired;
+
+    /// Start a timer that expires every `period` until it is stopped
+    fn start_interval(&mut self, key: T, period: Duration) {
+        self.start_with_delay(key, period, period)
+    }
+
+    /// Start a timer that first expires after `delay`, and then every `period` until it is
+    /// stopped.
+    ///
+    /// Each expiry is rescheduled from the tick it fired on, with the same rounding as `start`.
+    /// Periods shorter than a tick are rounded up to one tick, and starting the key again with
+    /// `start` makes it a one shot timer.
+    fn start_with_delay(&mut self, key: T, delay: Duration, period: Duration);
+
+    /// Restart a running timer so that it next expires after `time`, keeping its period if it is
+    /// an interval timer.
+    ///
+    /// Returns false and leaves the wheel unchanged if the timer isn't running or `time` is too
+    /// short for the wheel.
+    fn restart(&mut self, key: T, time: Duration) -> bool;
+
+    /// Like `stop`, but returns `FerrisError::NotFound` if the timer wasn't running
+    fn try_stop(&mut self, key: T) -> Result<Duration, FerrisError> {
+        self.stop(key).ok_or(FerrisError::NotFound)
+    }
+
+    /// Like `restart`, but returns why the timer wasn't restarted: `FerrisError::NotFound` if it
+    /// wasn't running, or an error for a duration shorter than a tick.
+    fn try_restart(&mut self, key: T, time: Duration) -> Result<(), FerrisError> {
+        let tick = self.tick_duration();
+        if time == Duration::from_secs(0) {
+            Err(FerrisError::ZeroDuration)
+        } else if time < tick {
+            Err(FerrisError::DurationOutOfRange(tick))
+        } else if self.restart(key, time) {
+            Ok(())
+        } else {
+            Err(FerrisError::NotFound)
+        }
+    }
+
+    /// How often `expire` must be called, which is the highest resolution of the wheel
+    fn tick_duration(&self) -> Duration;
+}
+
+impl<T, W> Wheel<T> for &mut W
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T> + ?Sized
+{
+    type Expired = W::Expired;
+
+    fn start(&mut self, key: T, time: Duration) {
+        (**self).start(key, time)
+    }
+
+    fn stop(&mut self, key: T) -> Option<Duration> {
+        (**self).stop(key)
+    }
+
+    fn expire(&mut self) -> W::Expired {
+        (**self).expire()
+    }
+
+    fn start_with_delay(&mut self, key: T, delay: Duration, period: Duration) {
+        (**self).start_with_delay(key, delay, period)
+    }
+
+    fn restart(&mut self, key: T, time: Duration) -> bool {
+        (**self).restart(key, time)
+    }
+
+    fn tick_duration(&self) -> Duration {
+        (**self).tick_duration()
+    }
+}
+
+impl<T, W> Wheel<T> for Box<W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T> + ?Sized
+{
+    type Expired = W::Expired;
+
+    fn start(&mut self, key: T, time: Duration) {
+        (**self).start(key, time)
+    }
+
+    fn stop(&mut self, key: T) -> Option<Duration> {
+        (**self).stop(key)
+    }
+
+    fn expire(&mut self) -> W::Expired {
+        (**self).expire()
+    }
+
+    fn start_with_delay(&mut self, key: T, delay: Duration, period: Duration) {
+        (**self).start_with_delay(key, delay, period)
+    }
+
+    fn restart(&mut self, key: T, time: Duration) -> bool {
+        (**self).restart(key, time)
+    }
+
+    fn tick_duration(&self) -> Duration {
+        (**self).tick_duration()
+    }
 }
 
 /// An entry in a InnerWheel
@@ -81,19 +544,467 @@ impl<T: Debug + Clone> InnerWheel<T> {
     }
 }
 
+// Return the tick at which a slot `slot` places ahead of the current slot in the wheel after
+// `inner` is next visited.
+//
+// The slot indexes of the hierarchy form a mixed radix counter of the ticks so far, where each
+// slot in a wheel lasts for the product of the sizes of the wheels inside it.
+fn deadline<T: Debug + Clone>(tick: u64, inner: &[InnerWheel<T>], slot: usize) -> u64 {
+    let ticks_per_slot: u64 = inner.iter().map(|wheel| wheel.slots.len() as u64).product();
+    (tick / ticks_per_slot + slot as u64) * ticks_per_slot
+}
+
+// Return the tick a timer of `time` would expire on in the first wheel, or `None` if it is shorter
+// than a tick
+fn exact_deadline(tick: u64, tick_duration: Duration, time: Duration) -> Option<u64> {
+    match Ticks::from_duration(time, tick_duration).0 {
+        0 => None,
+        ticks => Some(tick.saturating_add(ticks).saturating_add(1))
+    }
+}
+
+// Return the duration to schedule a timer of `time` for so that it expires at the end of the
+// window of `slack` its deadline falls in. Windows are counted from tick 0, so timers started at
+// different times but due in the same window share a tick.
+fn coalesce(tick: u64, tick_duration: Duration, time: Duration, slack: Duration) -> Duration {
+    let window = Ticks::from_duration(slack, tick_duration).0;
+    if window < 2 {
+        return time;
+    }
+    match exact_deadline(tick, tick_duration, time) {
+        Some(target) => match target.checked_next_multiple_of(window) {
+            Some(target) => Ticks(target - tick - 1).to_duration(tick_duration),
+            None => time
+        },
+        None => time
+    }
+}
+
+// Return the duration to start a timer for so that it expires on the first tick at least `wait`
+// after the current one. Waits shorter than two ticks get the shortest timer the wheels support.
+#[cfg(feature = "std")]
+fn time_until(wait: Duration, tick: Duration) -> Duration {
+    let ticks = Ticks::from_duration_ceil(wait, tick);
+    Ticks(ticks.0.max(2) - 1).to_duration(tick)
+}
+
+// The instant a new wheel counts its ticks from. Reading the clock panics on
+// wasm32-unknown-unknown, so there a wheel waits to be given the time by `sync_clock` or its first
+// `expire_until`.
+#[cfg(feature = "std")]
+fn start_instant() -> Option<std::time::Instant> {
+    if cfg!(all(target_arch = "wasm32", target_os = "unknown")) {
+        None
+    } else {
+        Some(std::time::Instant::now())
+    }
+}
+
+// Return the tick the furthest slot of the coarsest wheel is visited at. Timers due after this
+// wait in the overflow list.
+fn span_deadline<T: Debug + Clone>(tick: u64, wheels: &[InnerWheel<T>]) -> u64 {
+    let last = wheels.len() - 1;
+    deadline(tick, &wheels[..last], wheels[last].slots.len())
+}
+
+// Replace the slot deadlines in `keys` with the exact deadlines of the precise timers
+fn with_targets<K, T>(mut keys: HashMap<K, u64>, targets: HashMap<T, u64>) -> HashMap<K, u64>
+    where K: Eq + Hash + Borrow<T>,
+          T: Eq + Hash
+{
+    for (key, target) in targets {
+        if let Some(deadline) = keys.get_mut(&key) {
+            *deadline = target;
+        }
+    }
+    keys
+}
+
+// Return the wheel and slot to put a precise timer due at `target` in, and the tick that slot is
+// visited at.
+//
+// This is the coarsest wheel with a slot visited by `target`, and the last such slot, so the timer
+// drops into finer wheels as it comes due and expires from the first wheel exactly at `target`.
+fn precise_slot<T: Debug + Clone>(tick: u64,
+                                  wheels: &[InnerWheel<T>],
+                                  slot_indexes: &[usize],
+                                  target: u64) -> (usize, usize, u64)
+{
+    for index in (0..wheels.len()).rev() {
+        let ticks_per_slot: u64 = wheels[..index].iter()
+            .map(|wheel| wheel.slots.len() as u64)
+            .product();
+        let ahead = target / ticks_per_slot - tick / ticks_per_slot;
+        if ahead > 0 {
+            let size = wheels[index].slots.len();
+            let ahead = (ahead as usize).min(size);
+            let slot = (slot_indexes[index] + ahead) % size;
+            return (index, slot, deadline(tick, &wheels[..index], ahead));
+        }
+    }
+    unreachable!("a precise deadline is after the current tick")
+}
+
+// Return the tick at which slot `slot` of `wheels[index]` is next visited, which is the deadline
+// of the timers in it that are still running
+fn slot_deadline<T: Debug + Clone>(tick: u64,
+                                   wheels: &[InnerWheel<T>],
+                                   slot_indexes: &[usize],
+                                   index: usize,
+                                   slot: usize) -> u64
+{
+    let size = wheels[index].slots.len();
+    // The current slot was drained on this tick, so it is a full turn away
+    let ahead = match (slot + size - slot_indexes[index]) % size {
+        0 => size,
+        ahead => ahead
+    };
+    deadline(tick, &wheels[..index], ahead)
+}
+
+// Return the earliest deadline of a running timer, or `None` if there are none.
+//
+// Each wheel is scanned forward from its current slot for an entry that `is_running` at the slot's
+// deadline, stopping early once the deadlines pass the earliest found so far.
+fn next_deadline<T, F>(tick: u64,
+                       wheels: &[InnerWheel<T>],
+                       slot_indexes: &[usize],
+                       mut is_running: F) -> Option<u64>
+    where T: Debug + Clone,
+          F: FnMut(&T, u64) -> bool
+{
+    let mut next: Option<u64> = None;
+    for (index, wheel) in wheels.iter().enumerate() {
+        let size = wheel.slots.len();
+        for ahead in 1..size + 1 {
+            let deadline = deadline(tick, &wheels[..index], ahead);
+            if next.is_some_and(|next| deadline >= next) {
+                break;
+            }
+            let slot = &wheel.slots[(slot_indexes[index] + ahead) % size];
+            if slot.entries.iter().any(|entry| is_running(entry, deadline)) {
+                next = Some(deadline);
+                break;
+            }
+        }
+    }
+    next
+}
+
+// Turn the wheels on by `ticks`, passing the entries of each slot visited on the way to `visit`.
+//
+// A wheel moves on by one slot each time the wheel below it wraps around, as it does when ticking
+// one at a time, but a wheel that would make more than one full turn has each slot visited once.
+fn advance_slots<T, F>(tick: u64,
+                       ticks: u64,
+                       wheels: &mut [InnerWheel<T>],
+                       slot_indexes: &mut [usize],
+                       mut visit: F)
+    where T: Debug + Clone,
+          F: FnMut(&mut Vec<T>)
+{
+    let end = tick + ticks;
+    let mut ticks_per_slot = 1;
+    for (wheel, slot_index) in wheels.iter_mut().zip(slot_indexes.iter_mut()) {
+        let size = wheel.slots.len();
+        let moves = end / ticks_per_slot - tick / ticks_per_slot;
+        if moves == 0 {
+            break;
+        }
+        for step in 1..moves.min(size as u64) as usize + 1 {
+            visit(&mut wheel.slots[(*slot_index + step) % size].entries);
+        }
+        *slot_index = (*slot_index + (moves % size as u64) as usize) % size;
+        ticks_per_slot *= size as u64;
+    }
+}
+
+// Remove the first entry that `is_entry` matches from the slot visited at `deadline`, returning
+// whether one was found.
+//
+// Slots are only visited at multiples of the ticks they span, so each wheel has at most one slot
+// for a deadline.
+fn remove_slot_entry<T, F>(tick: u64,
+                           wheels: &mut [InnerWheel<T>],
+                           slot_indexes: &[usize],
+                           deadline: u64,
+                           mut is_entry: F) -> bool
+    where T: Debug + Clone,
+          F: FnMut(&T) -> bool
+{
+    let mut ticks_per_slot = 1;
+    for (wheel, slot_index) in wheels.iter_mut().zip(slot_indexes) {
+        let size = wheel.slots.len();
+        if deadline > tick && deadline.is_multiple_of(ticks_per_slot) {
+            let ahead = (deadline / ticks_per_slot - tick / ticks_per_slot) as usize;
+            if ahead <= size {
+                let entries = &mut wheel.slots[(slot_index + ahead) % size].entries;
+                if let Some(position) = entries.iter().position(&mut is_entry) {
+                    entries.remove(position);
+                    return true;
+                }
+            }
+        }
+        ticks_per_slot *= size as u64;
+    }
+    false
+}
+
+// Remove and return true for a key that is due to expire at tick `now`.
+//
+// Keys that were cancelled are no longer present and keys that were restarted have a different
+// deadline, so stale slot entries for either are skipped.
+fn is_due<K, Q>(keys: &mut HashMap<K, u64>, key: &Q, now: u64) -> bool
+    where K: Eq + Hash + Borrow<Q>,
+          Q: Eq + Hash + ?Sized
+{
+    if keys.get(key) == Some(&now) {
+        keys.remove(key);
+        return true;
+    }
+    false
+}
+
+// Check that a timer of `time` is long enough for the wheel, rather than being dropped
+fn check_duration(resolutions: &[Resolution], time: Duration) -> Result<(), StartError> {
+    let min = resolution_duration(resolutions[0]);
+    if time == Duration::from_secs(0) {
+        Err(StartError::Zero)
+    } else if time < min {
+        Err(StartError::TooShort(min))
+    } else {
+        Ok(())
+    }
+}
+
+// Check that a timer of `time` fits in a wheel without an overflow list, rather than being clamped
+// to the last slot
+#[cfg(feature = "u64-wheel")]
+fn check_span<T: Debug + Clone>(resolutions: &[Resolution],
+                                wheels: &[InnerWheel<T>],
+                                time: Duration) -> Result<(), StartError>
+{
+    check_duration(resolutions, time)?;
+    let coarsest = resolutions.len() - 1;
+    let span = resolution_duration(resolutions[coarsest]) * wheels[coarsest].slots.len() as u32;
+    if time >= span {
+        return Err(StartError::TooLong(span));
+    }
+    Ok(())
+}
+
+// The length of a single slot at the given resolution
+fn resolution_duration(resolution: Resolution) -> Duration {
+    match resolution {
+        Resolution::TenUs => Duration::from_micros(10),
+        Resolution::HundredUs => Duration::from_micros(100),
+        Resolution::Ms => Duration::from_millis(1),
+        Resolution::TenMs => Duration::from_millis(10),
+        Resolution::HundredMs => Duration::from_millis(100),
+        Resolution::Sec => Duration::from_secs(1),
+        Resolution::Min => Duration::from_secs(60),
+        Resolution::Hour => Duration::from_secs(3600),
+        Resolution::Day => Duration::from_secs(24 * 3600),
+        Resolution::Week => Duration::from_secs(7 * 24 * 3600),
+        Resolution::Custom(duration) => duration
+    }
+}
+
+// The number of slots ahead of the current one that a timer of `time` goes in, at `resolution`.
+//
+// A result of 1 means the timer is too short for this resolution and belongs in a finer wheel.
+// The wheels finer than a second only count the part of the time within a second, as coarser
+// wheels take the rest, while custom wheels and the coarsest wheel count all of it.
+fn slots_ahead(resolution: Resolution, time: Duration, coarsest: bool) -> usize {
+    let slots = match resolution {
+        Resolution::Custom(duration) => Ticks::from_duration(time, duration).0,
+        _ if coarsest => Ticks::from_duration(time, resolution_duration(resolution)).0,
+        Resolution::Week => time.as_secs() / (7 * 24 * 3600),
+        Resolution::Day => time.as_secs() / (24 * 3600),
+        Resolution::Hour => time.as_secs() / 3600,
+        Resolution::Min => time.as_secs() / 60,
+        Resolution::Sec => time.as_secs(),
+        Resolution::HundredMs => u64::from(time.subsec_nanos() / (1000 * 1000 * 100)),
+        Resolution::TenMs => u64::from(time.subsec_nanos() / (1000 * 1000 * 10)),
+        Resolution::Ms => u64::from(time.subsec_millis()),
+        Resolution::HundredUs => u64::from(time.subsec_micros() / 100),
+        Resolution::TenUs => u64::from(time.subsec_micros() / 10)
+    };
+    slots as usize + 1
+}
+
+// The time left until `deadline` when ticks of `tick_duration` are driving the wheel
+fn remaining(tick: u64, deadline: u64, tick_duration: Duration) -> Duration {
+    Ticks(deadline).saturating_sub(Ticks(tick)).to_duration(tick_duration)
+}
+
+// The resolutions used by `Default`: 10ms ticks with timers of up to a day
+fn default_resolutions() -> Vec<Resolution> {
+    vec![Resolution::TenMs, Resolution::HundredMs, Resolution::Sec, Resolution::Min, Resolution::Hour]
+}
+
+// 10ms ticks with timers of up to an hour
+fn network_timeout_resolutions() -> Vec<Resolution> {
+    vec![Resolution::TenMs, Resolution::HundredMs, Resolution::Sec, Resolution::Min]
+}
+
+// 1s ticks with timers of up to a day
+fn coarse_job_resolutions() -> Vec<Resolution> {
+    vec![Resolution::Sec, Resolution::Min, Resolution::Hour]
+}
+
+// The number of `finer` slots in one `coarser` slot, if it is a whole number above 1
+fn custom_ratio(finer: Resolution, coarser: Resolution) -> Option<u128> {
+    let (finer, coarser) = (resolution_duration(finer).as_nanos(), resolution_duration(coarser).as_nanos());
+    if finer == 0 || coarser % finer != 0 || coarser / finer < 2 {
+        return None;
+    }
+    Some(coarser / finer)
+}
+
+// Determine the wheel size for each resolution as `wheel_sizes` does, then apply the slot counts
+// in `slots`. Only the coarsest wheel can be given a different count, since each finer wheel has to
+// cover exactly one slot of the next.
+fn sized_wheels(mut resolutions: Vec<Resolution>,
+                slots: &[(Resolution, usize)]) -> Result<(Vec<Resolution>, Vec<usize>), &'static str>
+{
+    let mut sizes = wheel_sizes(&mut resolutions);
+    let coarsest = resolutions.len() - 1;
+    for &(resolution, count) in slots {
+        if count < 2 {
+            return Err("a wheel needs at least 2 slots");
+        }
+        match resolutions.iter().position(|r| *r == resolution) {
+            Some(i) if i == coarsest => sizes[i] = count,
+            Some(i) if sizes[i] == count => (),
+            Some(_) => return Err("only the coarsest wheel can change its number of slots"),
+            None => return Err("slots were given for a resolution the wheel doesn't have")
+        }
+    }
+    Ok((resolutions, sizes))
+}
+
+// Pick the fewest resolutions that tick every `tick` and hold timers of up to `max`, along with
+// the slots to give the coarsest wheel if even weeks don't reach `max`.
+//
+// The finest resolution is the one lasting `tick`, or a custom one if none does. Built in
+// resolutions are followed by as many of seconds, minutes, hours, days and weeks as are needed,
+// and custom ones by custom resolutions 60 times as long as the last.
+fn range_wheels(tick: Duration, max: Duration) -> (Vec<Resolution>, Vec<(Resolution, usize)>) {
+    const BUILT_IN: [Resolution; 10] = [
+        Resolution::TenUs, Resolution::HundredUs, Resolution::Ms, Resolution::TenMs,
+        Resolution::HundredMs, Resolution::Sec, Resolution::Min, Resolution::Hour, Resolution::Day,
+        Resolution::Week
+    ];
+    assert!(tick > Duration::from_secs(0), "the tick must be longer than zero");
+    let finest = BUILT_IN.iter().cloned()
+        .find(|resolution| resolution_duration(*resolution) == tick)
+        .unwrap_or(Resolution::Custom(tick));
+    let mut resolutions = vec![finest];
+    loop {
+        let sizes = wheel_sizes(&mut resolutions.clone());
+        let coarsest = resolutions[resolutions.len() - 1];
+        let slot = resolution_duration(coarsest);
+        if Ticks(sizes[sizes.len() - 1] as u64).to_duration(slot) > max {
+            return (resolutions, Vec::new());
+        }
+        let next = if finest.is_custom() {
+            slot.checked_mul(60).map(Resolution::Custom)
+        } else {
+            BUILT_IN.iter().cloned().find(|resolution| *resolution >= Resolution::Sec && *resolution > coarsest)
+        };
+        match next {
+            Some(next) => resolutions.push(next),
+            None => {
+                let slots = Ticks::from_duration(max, slot).0 as usize + 1;
+                return (resolutions, vec![(coarsest, slots)]);
+            }
+        }
+    }
+}
+
+// The size of the wheel at `resolutions[i]` if it or the next coarser wheel is custom, in which
+// case it is the number of its slots in one of the next wheel's
+fn custom_size(resolutions: &[Resolution], i: usize) -> Option<usize> {
+    match resolutions.get(i + 1) {
+        None if resolutions[i].is_custom() => Some(60),
+        Some(&next) if resolutions[i].is_custom() || next.is_custom() => {
+            custom_ratio(resolutions[i], next).map(|ratio| ratio as usize)
+        },
+        _ => None
+    }
+}
+
+// Check that the resolutions in a sorted and deduped hierarchy line up with their neighbours
+fn check_resolutions(resolutions: &[Resolution]) -> Result<(), &'static str> {
+    for (i, &resolution) in resolutions.iter().enumerate() {
+        if resolution_duration(resolution) == Duration::from_secs(0) {
+            return Err("a custom resolution must be longer than zero");
+        }
+        let next = match resolutions.get(i + 1) {
+            Some(&next) => next,
+            None => continue
+        };
+        // Built in wheels finer than a second only count the time within a second
+        if !resolution.is_custom() && resolution < Resolution::Sec && resolution_duration(next) > Duration::from_secs(1) {
+            return Err("a resolution above a built in one finer than a second can't be over a second");
+        }
+        if !resolution.is_custom() && !next.is_custom() {
+            continue;
+        }
+        if custom_ratio(resolution, next).is_none() {
+            return Err("neighbouring resolutions must divide into a whole number of slots");
+        }
+    }
+    Ok(())
+}
+
 // Determine the wheel size for each resolution.
 //
-// Wheel sizes less than one second are adjusted based on the next lowest resolution so that
-// resolutions don't overlap.
+// Wheel sizes are adjusted based on the next lowest resolution so that resolutions don't overlap
+// or leave gaps, so with no hour wheel a minute wheel covers a whole day. The coarsest wheel gets
+// a fixed number of slots, and a sub-second one covers a whole second.
 #[doc(hidden)]
 pub fn wheel_sizes(resolutions: &mut Vec<Resolution>) -> Vec<usize> {
-    assert!(resolutions.len() > 0);
+    assert!(!resolutions.is_empty());
     resolutions.sort();
     resolutions.dedup();
+    if let Err(reason) = check_resolutions(resolutions) {
+        panic!("{}", reason);
+    }
     let end = resolutions.len() - 1;
     let mut sizes = Vec::with_capacity(resolutions.len());
     for i in 0..resolutions.len() {
+        if let Some(size) = custom_size(resolutions, i) {
+            sizes.push(size);
+            continue;
+        }
         let wheel_size = match resolutions[i] {
+            Resolution::TenUs => {
+                if i == end {
+                    100_000
+                } else {
+                    match resolutions[i+1] {
+                        Resolution::HundredUs => 10,
+                        Resolution::Ms => 100,
+                        Resolution::TenMs => 1000,
+                        Resolution::HundredMs => 10_000,
+                        _ => 100_000
+                    }
+                }
+            },
+            Resolution::HundredUs => {
+                if i == end {
+                    10_000
+                } else {
+                    match resolutions[i+1] {
+                        Resolution::Ms => 10,
+                        Resolution::TenMs => 100,
+                        Resolution::HundredMs => 1000,
+                        _ => 10_000
+                    }
+                }
+            },
             Resolution::Ms => {
                 if i == end {
                     1000
@@ -116,9 +1027,23 @@ pub fn wheel_sizes(resolutions: &mut Vec<Resolution>) -> Vec<usize> {
                 }
             },
             Resolution::HundredMs => 10,
+            // Built in resolutions of a second and up always divide into each other
+            Resolution::Sec | Resolution::Min | Resolution::Hour | Resolution::Day if i < end => {
+                resolution_duration(resolutions[i + 1]).as_secs() as usize
+                    / resolution_duration(resolutions[i]).as_secs() as usize
+            },
             Resolution::Sec => 60,
             Resolution::Min => 60,
-            Resolution::Hour => 24
+            Resolution::Hour => 24,
+            Resolution::Day => {
+                if i == end {
+                    31
+                } else {
+                    7
+                }
+            },
+            Resolution::Week => 5,
+            Resolution::Custom(_) => unreachable!("custom wheels are sized by custom_size")
         };
         sizes.push(wheel_size);
     }
@@ -128,6 +1053,46 @@ pub fn wheel_sizes(resolutions: &mut Vec<Resolution>) -> Vec<usize> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn wheels_usable_as_trait_objects() {
+        let mut wheels: Vec<Box<dyn Wheel<u64, Expired = Vec<u64>>>> = vec![
+            Box::new(CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec])),
+            Box::new(AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]))
+        ];
+        for wheel in wheels.iter_mut() {
+            wheel.start(1, Duration::from_millis(10));
+            wheel.start(2, Duration::from_millis(10));
+            assert_eq!(Some(Duration::from_millis(20)), wheel.stop(2));
+            assert_eq!(None, wheel.stop(2));
+            assert!(wheel.expire().is_empty());
+            assert_eq!(vec![1], wheel.expire());
+        }
+    }
+
+    // Takes the wheel by value to check that references and boxes are wheels themselves
+    fn expire_twice<W: Wheel<u64, Expired = Vec<u64>>>(mut wheel: W) -> Vec<u64> {
+        wheel.expire();
+        wheel.expire()
+    }
+
+    #[test]
+    fn references_and_boxes_are_wheels() {
+        let mut copy = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        copy.start(1, Duration::from_millis(10));
+        assert_eq!(vec![1], expire_twice(&mut copy));
+
+        let mut boxed = Box::new(AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec]));
+        boxed.start(2, Duration::from_millis(10));
+        assert_eq!(vec![2], expire_twice(boxed));
+
+        let mut dynamic: Box<dyn Wheel<u64, Expired = Vec<u64>>> = Box::new(copy);
+        dynamic.start(3, Duration::from_millis(10));
+        assert_eq!(vec![3], expire_twice(&mut dynamic));
+        dynamic.start(4, Duration::from_millis(10));
+        assert_eq!(vec![4], expire_twice(dynamic));
+    }
 
     #[test]
     fn resolutions_sorted_and_deduped() {
@@ -136,24 +1101,60 @@ mod tests {
         assert_eq!(vec![Resolution::TenMs, Resolution::Sec, Resolution::Min], resolutions);
     }
 
+    #[test]
+    fn ranges_pick_the_fewest_resolutions() {
+        let ms = Duration::from_millis;
+        let hours = |hours: u64| Duration::from_secs(hours * 3600);
+        assert_eq!((vec![Resolution::Ms], vec![]), range_wheels(ms(1), ms(500)));
+        assert_eq!((vec![Resolution::TenMs, Resolution::Sec, Resolution::Min], vec![]),
+                   range_wheels(ms(10), Duration::from_secs(59 * 60)));
+        assert_eq!(Resolution::Hour, *range_wheels(ms(10), hours(1)).0.last().unwrap());
+        assert_eq!((vec![Resolution::Sec, Resolution::Min, Resolution::Hour, Resolution::Day], vec![]),
+                   range_wheels(ms(1000), hours(48)));
+        assert_eq!((vec![Resolution::Custom(ms(16)), Resolution::Custom(ms(960))], vec![]),
+                   range_wheels(ms(16), ms(50_000)));
+        assert_eq!(4, range_wheels(ms(16), hours(1)).0.len());
+        assert_eq!(5, range_wheels(ms(16), hours(30 * 24)).0.len());
+        let all = vec![Resolution::Hour, Resolution::Day, Resolution::Week];
+        assert_eq!((all, vec![(Resolution::Week, 9)]), range_wheels(hours(1), hours(8 * 7 * 24)));
+    }
+
     #[test]
     fn wheel_sizes_correct() {
-        let mut resolutions = vec![
+        let mut resolutions = [
             vec![Resolution::Ms, Resolution::TenMs, Resolution::Sec],
             vec![Resolution::Ms, Resolution::HundredMs, Resolution::Sec, Resolution::Min],
             vec![Resolution::Ms, Resolution::Sec],
-            vec![Resolution::TenMs, Resolution::HundredMs, Resolution::Sec]
+            vec![Resolution::TenMs, Resolution::HundredMs, Resolution::Sec],
+            vec![Resolution::TenUs, Resolution::HundredUs, Resolution::Ms, Resolution::Sec],
+            vec![Resolution::HundredUs, Resolution::TenMs, Resolution::Sec],
+            vec![Resolution::Ms, Resolution::Custom(Duration::from_millis(250)), Resolution::Sec],
+            vec![Resolution::Custom(Duration::from_millis(16)), Resolution::Custom(Duration::from_millis(480))],
+            vec![Resolution::Min, Resolution::Hour, Resolution::Day],
+            vec![Resolution::Hour, Resolution::Day, Resolution::Week],
+            vec![Resolution::Hour, Resolution::Week],
+            vec![Resolution::Min, Resolution::Day],
+            vec![Resolution::TenMs, Resolution::Sec, Resolution::Hour]
         ];
 
         let expected = vec![
             vec![10, 100, 60],
             vec![100, 10, 60, 60],
             vec![1000, 60],
-            vec![10, 10, 60]
+            vec![10, 10, 60],
+            vec![10, 10, 1000, 60],
+            vec![100, 100, 60],
+            vec![250, 4, 60],
+            vec![30, 60],
+            vec![60, 24, 31],
+            vec![24, 7, 5],
+            vec![168, 5],
+            vec![1440, 31],
+            vec![100, 3600, 24]
         ];
 
-        for (mut r, expected) in resolutions.iter_mut().zip(expected) {
-            assert_eq!(expected, wheel_sizes(&mut r));
+        for (r, expected) in resolutions.iter_mut().zip(expected) {
+            assert_eq!(expected, wheel_sizes(r));
         }
     }
 }
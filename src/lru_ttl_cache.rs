@@ -0,0 +1,247 @@
+use std::hash::Hash;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Duration;
+use super::{CopyWheel, Wheel, Resolution};
+
+/// A node in the recency list. Nodes live in a slab and are linked by index.
+struct Node<K, V> {
+    key: K,
+    value: V,
+    generation: u64,
+    prev: Option<usize>,
+    next: Option<usize>
+}
+
+/// A bounded cache that evicts the least recently used entry when full and also evicts entries
+/// once their time to live expires.
+///
+/// Recency is tracked with an intrusive doubly linked list stored in a slab, so `get`, `insert`
+/// and `remove` are all O(1). Time based eviction is handled by a `CopyWheel` rather than by
+/// scanning entries, so the cache must be driven by calling `expire` at the maximum resolution,
+/// exactly like the wheel itself.
+///
+/// Each insert of a key is tagged with a generation in the wheel. Re-inserting a key therefore
+/// resets its TTL without the older timer evicting the fresh entry.
+pub struct LruTtlCache<K: Eq + Hash + Debug + Clone, V> {
+    capacity: usize,
+    map: HashMap<K, usize>,
+    nodes: Vec<Option<Node<K, V>>>,
+    free: Vec<usize>,
+    head: Option<usize>,
+    tail: Option<usize>,
+    wheel: CopyWheel<(K, u64)>,
+    next_generation: u64
+}
+
+impl<K: Eq + Hash + Debug + Clone, V> LruTtlCache<K, V> {
+    /// Create a cache holding at most `capacity` entries, with TTLs tracked by a wheel with the
+    /// given resolutions.
+    pub fn new(capacity: usize, resolutions: Vec<Resolution>) -> LruTtlCache<K, V> {
+        assert!(capacity > 0);
+        LruTtlCache {
+            capacity,
+            map: HashMap::with_capacity(capacity),
+            nodes: Vec::with_capacity(capacity),
+            free: Vec::new(),
+            head: None,
+            tail: None,
+            wheel: CopyWheel::new(resolutions),
+            next_generation: 0
+        }
+    }
+
+    /// Insert a value that will be evicted after `ttl`.
+    ///
+    /// If the key was already present its value is replaced, its TTL restarted, and the old value
+    /// returned. If the cache is full, the least recently used entry is evicted. TTLs shorter than
+    /// a tick are rounded up to a tick, since the wheel would drop them.
+    pub fn insert(&mut self, key: K, value: V, ttl: Duration) -> Option<V> {
+        let ttl = ttl.max(self.wheel.tick_duration());
+        let old = self.remove(&key);
+        if self.map.len() == self.capacity {
+            if let Some(tail) = self.tail {
+                self.remove_node(tail);
+            }
+        }
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        self.wheel.start((key.clone(), generation), ttl);
+        let node = Node {
+            key: key.clone(),
+            value,
+            generation,
+            prev: None,
+            next: None
+        };
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.nodes[index] = Some(node);
+                index
+            },
+            None => {
+                self.nodes.push(Some(node));
+                self.nodes.len() - 1
+            }
+        };
+        self.push_front(index);
+        self.map.insert(key, index);
+        old
+    }
+
+    /// Return the value for a key and mark it as most recently used.
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let index = *self.map.get(key)?;
+        self.unlink(index);
+        self.push_front(index);
+        self.nodes[index].as_ref().map(|node| &node.value)
+    }
+
+    /// Return the value for a key without changing its recency.
+    pub fn peek(&self, key: &K) -> Option<&V> {
+        let index = *self.map.get(key)?;
+        self.nodes[index].as_ref().map(|node| &node.value)
+    }
+
+    /// Remove a key from the cache, cancelling its TTL.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let index = *self.map.get(key)?;
+        Some(self.remove_node(index).1)
+    }
+
+    /// The number of entries in the cache
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// The maximum number of entries in the cache
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Advance the TTL wheel by one tick and return any entries whose TTL expired.
+    pub fn expire(&mut self) -> Vec<(K, V)> {
+        let mut expired = Vec::new();
+        for (key, generation) in self.wheel.expire() {
+            let index = match self.map.get(&key) {
+                Some(index) => *index,
+                None => continue
+            };
+            let current = self.nodes[index].as_ref().map(|node| node.generation);
+            if current == Some(generation) {
+                expired.push(self.remove_node(index));
+            }
+        }
+        expired
+    }
+
+    fn remove_node(&mut self, index: usize) -> (K, V) {
+        self.unlink(index);
+        let node = self.nodes[index].take().unwrap();
+        self.free.push(index);
+        self.map.remove(&node.key);
+        self.wheel.stop((node.key.clone(), node.generation));
+        (node.key, node.value)
+    }
+
+    fn push_front(&mut self, index: usize) {
+        let old_head = self.head;
+        {
+            let node = self.nodes[index].as_mut().unwrap();
+            node.prev = None;
+            node.next = old_head;
+        }
+        match old_head {
+            Some(head) => self.nodes[head].as_mut().unwrap().prev = Some(index),
+            None => self.tail = Some(index)
+        }
+        self.head = Some(index);
+    }
+
+    fn unlink(&mut self, index: usize) {
+        let (prev, next) = {
+            let node = self.nodes[index].as_ref().unwrap();
+            (node.prev, node.next)
+        };
+        match prev {
+            Some(prev) => self.nodes[prev].as_mut().unwrap().next = next,
+            None => self.head = next
+        }
+        match next {
+            Some(next) => self.nodes[next].as_mut().unwrap().prev = prev,
+            None => self.tail = prev
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    fn cache(capacity: usize) -> LruTtlCache<&'static str, u32> {
+        LruTtlCache::new(capacity, vec![Resolution::TenMs, Resolution::Sec])
+    }
+
+    #[test]
+    fn entries_expire_after_ttl() {
+        let mut cache = cache(10);
+        cache.insert("a", 1, Duration::from_millis(50));
+        cache.insert("b", 2, Duration::from_secs(2));
+
+        // 50ms lands in slot 6 of the 10ms wheel
+        for _ in 0..5 {
+            assert_eq!(0, cache.expire().len());
+        }
+        assert_eq!(vec![("a", 1)], cache.expire());
+        assert_eq!(None, cache.peek(&"a"));
+        assert_eq!(Some(&2), cache.peek(&"b"));
+        assert_eq!(1, cache.len());
+    }
+
+    #[test]
+    fn ttls_shorter_than_a_tick_still_expire() {
+        let mut cache = cache(10);
+        cache.insert("a", 1, Duration::from_millis(3));
+        cache.insert("b", 2, Duration::from_secs(0));
+        assert_eq!(0, cache.expire().len());
+        let mut expired = cache.expire();
+        expired.sort();
+        assert_eq!(vec![("a", 1), ("b", 2)], expired);
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    fn least_recently_used_is_evicted() {
+        let mut cache = cache(2);
+        let ttl = Duration::from_secs(5);
+        cache.insert("a", 1, ttl);
+        cache.insert("b", 2, ttl);
+        assert_eq!(Some(&1), cache.get(&"a"));
+        cache.insert("c", 3, ttl);
+        assert_eq!(None, cache.peek(&"b"));
+        assert_eq!(Some(&1), cache.peek(&"a"));
+        assert_eq!(Some(&3), cache.peek(&"c"));
+        assert_eq!(2, cache.len());
+    }
+
+    #[test]
+    fn reinsert_restarts_ttl() {
+        let mut cache = cache(10);
+        cache.insert("a", 1, Duration::from_millis(20));
+        cache.expire();
+        assert_eq!(Some(1), cache.insert("a", 2, Duration::from_millis(50)));
+
+        // The first timer would have fired on the 3rd tick
+        for _ in 0..5 {
+            assert_eq!(0, cache.expire().len());
+        }
+        assert_eq!(vec![("a", 2)], cache.expire());
+        assert!(cache.is_empty());
+    }
+}
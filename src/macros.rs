@@ -0,0 +1,72 @@
+/// Declare or construct a wheel from a kind, key type and list of resolutions.
+///
+/// The expression form constructs a wheel:
+///
+/// ```
+/// #[macro_use] extern crate ferris;
+/// use ferris::Wheel;
+/// use std::time::Duration;
+///
+/// # fn main() {
+/// let mut wheel = wheel!{ kind: Copy, key: u64, resolutions: [TenMs, Sec, Min] };
+/// wheel.start(1, Duration::from_secs(5));
+/// # }
+/// ```
+///
+/// The item form declares a type alias along with a constructor function of the same name, so that
+/// the configuration lives in one place:
+///
+/// ```
+/// #[macro_use] extern crate ferris;
+///
+/// wheel!{ pub type ConnWheel; kind: Alloc, key: u32, resolutions: [TenMs, Sec, Min] }
+///
+/// # fn main() {
+/// let wheel: ConnWheel = ConnWheel();
+/// # }
+/// ```
+///
+/// `kind` is either `Copy` or `Alloc`, and the resolutions are variants of `ferris::Resolution`.
+#[macro_export]
+macro_rules! wheel {
+    ($vis:vis type $name:ident; kind: $kind:ident, key: $key:ty,
+     resolutions: [$($resolution:ident),+ $(,)*] $(,)*) => {
+        $vis type $name = wheel!(@type $kind, $key);
+
+        #[allow(non_snake_case, dead_code)]
+        $vis fn $name() -> $name {
+            wheel!{ kind: $kind, key: $key, resolutions: [$($resolution),+] }
+        }
+    };
+    (kind: $kind:ident, key: $key:ty, resolutions: [$($resolution:ident),+ $(,)*] $(,)*) => {
+        <wheel!(@type $kind, $key)>::new(vec![$($crate::Resolution::$resolution),+])
+    };
+    (@type Copy, $key:ty) => { $crate::CopyWheel<$key> };
+    (@type Alloc, $key:ty) => { $crate::AllocWheel<$key> };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::super::{AllocWheel, CopyWheel, Wheel};
+
+    wheel!{ type TestWheel; kind: Copy, key: &'static str, resolutions: [TenMs, Sec] }
+
+    #[test]
+    fn macro_wheels_expire() {
+        let mut declared: TestWheel = TestWheel();
+        let mut copy: CopyWheel<&'static str> =
+            wheel!{ kind: Copy, key: &'static str, resolutions: [Sec, TenMs,] };
+        let mut alloc: AllocWheel<&'static str> =
+            wheel!{ kind: Alloc, key: &'static str, resolutions: [TenMs, Sec] };
+        declared.start("a", Duration::from_millis(10));
+        copy.start("a", Duration::from_millis(10));
+        alloc.start("a", Duration::from_millis(10));
+        assert!(declared.expire().is_empty());
+        assert!(copy.expire().is_empty());
+        assert!(alloc.expire().is_empty());
+        assert_eq!(vec!["a"], declared.expire());
+        assert_eq!(vec!["a"], copy.expire());
+        assert_eq!(vec!["a"], alloc.expire());
+    }
+}
@@ -0,0 +1,110 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::time::Duration;
+use alloc::vec::Vec;
+use collections::HashMap;
+use super::{CopyWheel, Resolution, Wheel};
+
+/// A wheel that stores a value with each timer and hands it back when the timer expires.
+///
+/// This saves keeping a separate map from keys to values in step with the wheel. The keys are
+/// tracked by a `CopyWheel`, and each value is stored once, alongside its key.
+pub struct MapWheel<K: Eq + Hash + Debug + Clone, V> {
+    wheel: CopyWheel<K>,
+    values: HashMap<K, V>,
+    expired: Vec<K>
+}
+
+impl<K: Eq + Hash + Debug + Clone, V> MapWheel<K, V> {
+    /// Create a wheel with the given resolutions. See `CopyWheel::new`.
+    pub fn new(resolutions: Vec<Resolution>) -> MapWheel<K, V> {
+        MapWheel {
+            wheel: CopyWheel::new(resolutions),
+            values: HashMap::new(),
+            expired: Vec::new()
+        }
+    }
+
+    /// Start a timer with the given duration and value, restarting it if it is already running.
+    ///
+    /// Returns the value of the timer that was running, if any. The value is dropped if the
+    /// duration is too short for the wheel.
+    pub fn start_with(&mut self, key: K, value: V, time: Duration) -> Option<V> {
+        self.wheel.start(key.clone(), time);
+        if !self.wheel.contains(&key) {
+            return None;
+        }
+        self.values.insert(key, value)
+    }
+
+    /// Cancel a timer, returning its value
+    pub fn stop(&mut self, key: &K) -> Option<V> {
+        self.wheel.cancel(key)?;
+        self.values.remove(key)
+    }
+
+    /// Return the keys and values of any expired timers
+    pub fn expire(&mut self) -> Vec<(K, V)> {
+        self.wheel.expire_into(&mut self.expired);
+        let values = &mut self.values;
+        self.expired.drain(..)
+            .filter_map(|key| values.remove(&key).map(|value| (key, value)))
+            .collect()
+    }
+
+    /// The value of a running timer
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.values.get(key)
+    }
+
+    pub fn get_mut(&mut self, key: &K) -> Option<&mut V> {
+        self.values.get_mut(key)
+    }
+
+    /// The time left until a timer expires. See `CopyWheel::remaining`.
+    pub fn remaining(&self, key: &K) -> Option<Duration> {
+        self.wheel.remaining(key)
+    }
+
+    /// The number of running timers
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// How often `expire` must be called
+    pub fn tick_duration(&self) -> Duration {
+        self.wheel.tick_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    #[test]
+    fn values_come_back_on_expiry() {
+        let mut wheel = MapWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        assert_eq!(None, wheel.start_with(1, "request 1", Duration::from_millis(10)));
+        assert_eq!(None, wheel.start_with(2, "request 2", Duration::from_millis(10)));
+        assert_eq!(Some("request 2"), wheel.start_with(2, "retry 2", Duration::from_millis(20)));
+        assert_eq!(None, wheel.start_with(3, "too short", Duration::from_micros(10)));
+        assert_eq!(2, wheel.len());
+        *wheel.get_mut(&1).unwrap() = "updated 1";
+
+        assert!(wheel.expire().is_empty());
+        assert_eq!(vec![(1, "updated 1")], wheel.expire());
+        assert_eq!(Some(&"retry 2"), wheel.get(&2));
+        assert_eq!(Some("retry 2"), wheel.stop(&2));
+        assert_eq!(None, wheel.stop(&2));
+        assert!(wheel.is_empty());
+        for _ in 0..10 {
+            assert!(wheel.expire().is_empty());
+        }
+    }
+}
@@ -0,0 +1,161 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::marker::PhantomData;
+use core::time::Duration;
+use alloc::vec::Vec;
+use super::Wheel;
+
+/// Hooks called by an `ObservedWheel` as its timers start, are cancelled and expire.
+///
+/// Every method does nothing by default, so an observer only implements the ones it needs. Use
+/// them for logging, metrics or checking invariants.
+pub trait WheelObserver<T> {
+    /// A timer was started or restarted to expire after `time`
+    fn on_start(&mut self, _key: &T, _time: Duration) {}
+
+    /// A running timer was cancelled with `remaining` time left
+    fn on_cancel(&mut self, _key: &T, _remaining: Duration) {}
+
+    /// A timer expired
+    fn on_expire(&mut self, _key: &T) {}
+}
+
+/// The observer that ignores everything
+impl<T> WheelObserver<T> for () {}
+
+/// A wheel that reports what happens to its timers to a `WheelObserver`.
+///
+/// Wheels that aren't wrapped pay nothing for observers, and the calls to an observer that
+/// ignores an event are optimized away.
+pub struct ObservedWheel<T, W, O>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>,
+          O: WheelObserver<T>
+{
+    wheel: W,
+    observer: O,
+    _key: PhantomData<T>
+}
+
+impl<T, W, O> ObservedWheel<T, W, O>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>,
+          O: WheelObserver<T>
+{
+    pub fn new(wheel: W, observer: O) -> ObservedWheel<T, W, O> {
+        ObservedWheel {
+            wheel,
+            observer,
+            _key: PhantomData
+        }
+    }
+
+    pub fn wheel(&self) -> &W {
+        &self.wheel
+    }
+
+    pub fn observer(&self) -> &O {
+        &self.observer
+    }
+
+    pub fn observer_mut(&mut self) -> &mut O {
+        &mut self.observer
+    }
+
+    pub fn into_inner(self) -> (W, O) {
+        (self.wheel, self.observer)
+    }
+}
+
+impl<T, W, O> Wheel<T> for ObservedWheel<T, W, O>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>,
+          O: WheelObserver<T>
+{
+    type Expired = Vec<T>;
+
+    fn start(&mut self, key: T, time: Duration) {
+        self.observer.on_start(&key, time);
+        self.wheel.start(key, time);
+    }
+
+    fn stop(&mut self, key: T) -> Option<Duration> {
+        let remaining = self.wheel.stop(key.clone());
+        if let Some(remaining) = remaining {
+            self.observer.on_cancel(&key, remaining);
+        }
+        remaining
+    }
+
+    fn expire(&mut self) -> Vec<T> {
+        let expired: Vec<T> = self.wheel.expire().into_iter().collect();
+        for key in &expired {
+            self.observer.on_expire(key);
+        }
+        expired
+    }
+
+    fn start_with_delay(&mut self, key: T, delay: Duration, period: Duration) {
+        self.observer.on_start(&key, delay);
+        self.wheel.start_with_delay(key, delay, period);
+    }
+
+    fn restart(&mut self, key: T, time: Duration) -> bool {
+        let restarted = self.wheel.restart(key.clone(), time);
+        if restarted {
+            self.observer.on_start(&key, time);
+        }
+        restarted
+    }
+
+    fn tick_duration(&self) -> Duration {
+        self.wheel.tick_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{CopyWheel, Resolution, Wheel};
+
+    #[derive(Default)]
+    struct Counts {
+        started: usize,
+        cancelled: usize,
+        expired: Vec<&'static str>
+    }
+
+    impl WheelObserver<&'static str> for Counts {
+        fn on_start(&mut self, _key: &&'static str, _time: Duration) {
+            self.started += 1;
+        }
+
+        fn on_cancel(&mut self, _key: &&'static str, _remaining: Duration) {
+            self.cancelled += 1;
+        }
+
+        fn on_expire(&mut self, key: &&'static str) {
+            self.expired.push(*key);
+        }
+    }
+
+    #[test]
+    fn observer_sees_every_event() {
+        let wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        let mut wheel = ObservedWheel::new(wheel, Counts::default());
+        wheel.start("a", Duration::from_millis(10));
+        wheel.start("b", Duration::from_millis(10));
+        assert!(wheel.restart("b", Duration::from_millis(20)));
+        assert!(!wheel.restart("c", Duration::from_millis(20)));
+        wheel.stop("a");
+        wheel.stop("a");
+        for _ in 0..3 {
+            wheel.expire();
+        }
+        let (_, counts) = wheel.into_inner();
+        assert_eq!(3, counts.started);
+        assert_eq!(1, counts.cancelled);
+        assert_eq!(vec!["b"], counts.expired);
+    }
+}
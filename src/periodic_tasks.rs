@@ -0,0 +1,143 @@
+use std::hash::Hash;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Duration;
+use super::{CopyWheel, Wheel, Resolution};
+
+/// A registry of named recurring tasks sharing a single wheel.
+///
+/// Each registered task fires once per period and is re-armed automatically when it fires, so a
+/// gossip round, an anti-entropy sweep and a metrics flush can all be driven from one `expire`
+/// call per tick. Re-registering a task replaces its period and restarts its interval.
+pub struct PeriodicTasks<N: Eq + Hash + Debug + Clone> {
+    tasks: HashMap<N, (Duration, u64)>,
+    wheel: CopyWheel<(N, u64)>,
+    next_generation: u64
+}
+
+impl<N: Eq + Hash + Debug + Clone> PeriodicTasks<N> {
+    pub fn new(resolutions: Vec<Resolution>) -> PeriodicTasks<N> {
+        PeriodicTasks {
+            tasks: HashMap::new(),
+            wheel: CopyWheel::new(resolutions),
+            next_generation: 0
+        }
+    }
+
+    /// Register a task to run every `period`. The first run is one period from now. Periods
+    /// shorter than a tick are rounded up to a tick.
+    pub fn register(&mut self, name: N, period: Duration) {
+        let period = period.max(self.wheel.tick_duration());
+        self.unregister(&name);
+        self.arm(name, period);
+    }
+
+    /// Remove a task. Returns false if the task was not registered.
+    pub fn unregister(&mut self, name: &N) -> bool {
+        match self.tasks.remove(name) {
+            Some((_, generation)) => {
+                self.wheel.stop((name.clone(), generation));
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Return the period of a registered task
+    pub fn period(&self, name: &N) -> Option<Duration> {
+        self.tasks.get(name).map(|&(period, _)| period)
+    }
+
+    /// The number of registered tasks
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Advance the wheel by one tick and return the tasks that are due
+    pub fn expire(&mut self) -> Vec<N> {
+        let mut due = Vec::new();
+        for (name, generation) in self.wheel.expire() {
+            let period = match self.tasks.get(&name) {
+                Some(&(period, current)) if current == generation => period,
+                _ => continue
+            };
+            self.arm(name.clone(), period);
+            due.push(name);
+        }
+        due
+    }
+
+    fn arm(&mut self, name: N, period: Duration) {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        self.wheel.start((name.clone(), generation), period);
+        self.tasks.insert(name, (period, generation));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    #[test]
+    fn tasks_fire_every_period() {
+        let mut tasks = PeriodicTasks::new(vec![Resolution::TenMs, Resolution::Sec]);
+        tasks.register("gossip", Duration::from_millis(20));
+        tasks.register("flush", Duration::from_millis(50));
+
+        let mut gossip = 0;
+        let mut flush = 0;
+        for _ in 0..30 {
+            for name in tasks.expire() {
+                match name {
+                    "gossip" => gossip += 1,
+                    "flush" => flush += 1,
+                    _ => unreachable!()
+                }
+            }
+        }
+        // Each period is rounded up by one 10ms slot
+        assert_eq!(10, gossip);
+        assert_eq!(5, flush);
+    }
+
+    #[test]
+    fn periods_shorter_than_a_tick_still_fire() {
+        let mut tasks = PeriodicTasks::new(vec![Resolution::TenMs, Resolution::Sec]);
+        tasks.register("poll", Duration::from_millis(5));
+        assert_eq!(Some(Duration::from_millis(10)), tasks.period(&"poll"));
+        let fires: usize = (0..30).map(|_| tasks.expire().len()).sum();
+        assert_eq!(15, fires);
+    }
+
+    #[test]
+    fn unregistered_tasks_stop_firing() {
+        let mut tasks = PeriodicTasks::new(vec![Resolution::TenMs, Resolution::Sec]);
+        tasks.register("gossip", Duration::from_millis(20));
+        assert_eq!(Some(Duration::from_millis(20)), tasks.period(&"gossip"));
+        assert!(tasks.unregister(&"gossip"));
+        assert!(!tasks.unregister(&"gossip"));
+        assert!(tasks.is_empty());
+        for _ in 0..10 {
+            assert!(tasks.expire().is_empty());
+        }
+    }
+
+    #[test]
+    fn reregister_restarts_interval() {
+        let mut tasks = PeriodicTasks::new(vec![Resolution::TenMs, Resolution::Sec]);
+        tasks.register("gossip", Duration::from_millis(20));
+        tasks.expire();
+        tasks.register("gossip", Duration::from_millis(50));
+        for _ in 0..5 {
+            assert!(tasks.expire().is_empty());
+        }
+        assert_eq!(vec!["gossip"], tasks.expire());
+    }
+}
@@ -0,0 +1,31 @@
+//! The commonly used traits and types, for glob importing.
+//!
+//! ```
+//! use ferris::prelude::*;
+//! use std::time::Duration;
+//!
+//! let mut wheel = CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+//! wheel.start("a", Duration::from_millis(50));
+//! ```
+
+pub use super::{Wheel, AnyWheel, DuplicatePolicy, FerrisError, Resolution, Rounding, StartError, TickPolicy, Ticks,
+                WheelBuilder, WheelKind};
+#[cfg(feature = "alloc-wheel")]
+pub use super::AllocWheel;
+#[cfg(feature = "copy-wheel")]
+pub use super::CopyWheel;
+#[cfg(feature = "std")]
+pub use super::{Clock, MockClock, SystemClock};
+#[cfg(feature = "copy-wheel")]
+pub use super::MapWheel;
+#[cfg(feature = "hashed-wheel")]
+pub use super::HashedWheel;
+#[cfg(feature = "intrusive-wheel")]
+pub use super::IntrusiveWheel;
+#[cfg(feature = "slab-wheel")]
+pub use super::{SlabWheel, TimerId};
+#[cfg(feature = "u64-wheel")]
+pub use super::U64Wheel;
+#[cfg(all(feature = "std", feature = "copy-wheel"))]
+pub use super::Backoff;
+pub use super::{Callback, CallbackWheel, GroupedWheel, TimerToken, TokenWheel};
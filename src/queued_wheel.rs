@@ -0,0 +1,180 @@
+use std::hash::Hash;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::Duration;
+use super::Wheel;
+
+enum Command<T> {
+    Start(T, Duration),
+    StartWithDelay(T, Duration, Duration),
+    Restart(T, Duration),
+    Stop(T)
+}
+
+/// A wheel that any thread can start and stop timers on through a `QueueHandle`, without locking
+/// the wheel.
+///
+/// Handles push commands onto a channel, and the thread that owns the wheel applies them in order
+/// at the top of each `expire`, before the tick. This fits the usual model of one thread driving
+/// the wheel, and a start from another thread takes effect on the next tick.
+pub struct QueuedWheel<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    wheel: W,
+    sender: Sender<Command<T>>,
+    commands: Receiver<Command<T>>,
+    _key: PhantomData<T>
+}
+
+impl<T, W> QueuedWheel<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    pub fn new(wheel: W) -> QueuedWheel<T, W> {
+        let (sender, commands) = channel();
+        QueuedWheel {
+            wheel,
+            sender,
+            commands,
+            _key: PhantomData
+        }
+    }
+
+    /// Return a handle for starting and stopping timers from other threads
+    pub fn handle(&self) -> QueueHandle<T> {
+        QueueHandle {
+            sender: self.sender.clone()
+        }
+    }
+
+    /// Apply the commands sent so far, without waiting for the next `expire`
+    pub fn apply_commands(&mut self) {
+        while let Ok(command) = self.commands.try_recv() {
+            match command {
+                Command::Start(key, time) => self.wheel.start(key, time),
+                Command::StartWithDelay(key, delay, period) => {
+                    self.wheel.start_with_delay(key, delay, period)
+                },
+                Command::Restart(key, time) => {
+                    self.wheel.restart(key, time);
+                },
+                Command::Stop(key) => {
+                    self.wheel.stop(key);
+                }
+            }
+        }
+    }
+
+    pub fn wheel(&self) -> &W {
+        &self.wheel
+    }
+
+    pub fn into_inner(self) -> W {
+        self.wheel
+    }
+}
+
+/// Calls made directly on the wheel apply any queued commands first, so they are ordered after
+/// everything the handles sent before them.
+impl<T, W> Wheel<T> for QueuedWheel<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    type Expired = W::Expired;
+
+    fn start(&mut self, key: T, time: Duration) {
+        self.apply_commands();
+        self.wheel.start(key, time);
+    }
+
+    fn stop(&mut self, key: T) -> Option<Duration> {
+        self.apply_commands();
+        self.wheel.stop(key)
+    }
+
+    fn expire(&mut self) -> W::Expired {
+        self.apply_commands();
+        self.wheel.expire()
+    }
+
+    fn start_with_delay(&mut self, key: T, delay: Duration, period: Duration) {
+        self.apply_commands();
+        self.wheel.start_with_delay(key, delay, period);
+    }
+
+    fn restart(&mut self, key: T, time: Duration) -> bool {
+        self.apply_commands();
+        self.wheel.restart(key, time)
+    }
+
+    fn tick_duration(&self) -> Duration {
+        self.wheel.tick_duration()
+    }
+}
+
+/// A cloneable handle to a `QueuedWheel`, which can be sent to other threads.
+///
+/// Commands sent after the wheel is dropped are ignored.
+pub struct QueueHandle<T> {
+    sender: Sender<Command<T>>
+}
+
+impl<T> QueueHandle<T> {
+    /// Start a timer, restarting it if it's already running
+    pub fn start(&self, key: T, time: Duration) {
+        let _ = self.sender.send(Command::Start(key, time));
+    }
+
+    /// Start an interval timer. See `Wheel::start_with_delay`.
+    pub fn start_with_delay(&self, key: T, delay: Duration, period: Duration) {
+        let _ = self.sender.send(Command::StartWithDelay(key, delay, period));
+    }
+
+    /// Restart a timer if it is still running when the command is applied
+    pub fn restart(&self, key: T, time: Duration) {
+        let _ = self.sender.send(Command::Restart(key, time));
+    }
+
+    pub fn stop(&self, key: T) {
+        let _ = self.sender.send(Command::Stop(key));
+    }
+}
+
+impl<T> Clone for QueueHandle<T> {
+    fn clone(&self) -> QueueHandle<T> {
+        QueueHandle {
+            sender: self.sender.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+    use super::super::{CopyWheel, Resolution, Wheel};
+
+    #[test]
+    fn handles_send_commands_from_other_threads() {
+        let mut wheel = QueuedWheel::new(CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]));
+        let workers: Vec<_> = (0..4u64).map(|worker| {
+            let handle = wheel.handle();
+            thread::spawn(move || {
+                handle.start(worker, Duration::from_millis(10));
+                handle.start(worker + 10, Duration::from_millis(10));
+                handle.stop(worker + 10);
+            })
+        }).collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        wheel.start(20, Duration::from_millis(10));
+        assert!(wheel.expire().is_empty());
+        let mut expired = wheel.expire();
+        expired.sort();
+        assert_eq!(vec![0, 1, 2, 3, 20], expired);
+    }
+}
@@ -0,0 +1,233 @@
+use std::time::Duration;
+use super::{CopyWheel, Wheel, Resolution};
+use rng::Rng;
+
+/// A timeout reported by `RaftTimeouts::expire`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum RaftTimeout {
+    /// No leader was heard from before the randomized election timeout elapsed
+    Election,
+    /// The leader should send a round of heartbeats
+    Heartbeat
+}
+
+/// Election and heartbeat timers for a Raft node.
+///
+/// Every call to `reset_election` picks a fresh timeout uniformly from the configured range, so
+/// that followers don't all time out together. Only the most recently armed election timer can
+/// fire; older timers are ignored even if they are still in the wheel. An election timeout fires
+/// once and must be re-armed, typically when the node starts its campaign.
+///
+/// The heartbeat timer re-arms itself each time it fires until `stop_heartbeat` is called.
+///
+/// Like the wheels, `expire` must be called at the maximum resolution. Timeouts and intervals
+/// shorter than a tick are rounded up to a tick.
+pub struct RaftTimeouts {
+    election_min: Duration,
+    election_max: Duration,
+    heartbeat_interval: Duration,
+    wheel: CopyWheel<(RaftTimeout, u64)>,
+    election: Option<u64>,
+    heartbeat: Option<u64>,
+    next_generation: u64,
+    rng: Rng
+}
+
+impl RaftTimeouts {
+    /// Create the timers. No timer is armed until `reset_election` or `start_heartbeat` is called.
+    pub fn new(resolutions: Vec<Resolution>,
+               election_min: Duration,
+               election_max: Duration,
+               heartbeat_interval: Duration) -> RaftTimeouts
+    {
+        RaftTimeouts::with_rng(resolutions, election_min, election_max, heartbeat_interval,
+                               Rng::from_entropy())
+    }
+
+    /// Create the timers with a fixed jitter seed, so that tests and simulations are reproducible.
+    pub fn with_seed(resolutions: Vec<Resolution>,
+                     election_min: Duration,
+                     election_max: Duration,
+                     heartbeat_interval: Duration,
+                     seed: u64) -> RaftTimeouts
+    {
+        RaftTimeouts::with_rng(resolutions, election_min, election_max, heartbeat_interval,
+                               Rng::new(seed))
+    }
+
+    fn with_rng(resolutions: Vec<Resolution>,
+                election_min: Duration,
+                election_max: Duration,
+                heartbeat_interval: Duration,
+                rng: Rng) -> RaftTimeouts
+    {
+        assert!(election_min <= election_max);
+        RaftTimeouts {
+            election_min,
+            election_max,
+            heartbeat_interval,
+            wheel: CopyWheel::new(resolutions),
+            election: None,
+            heartbeat: None,
+            next_generation: 0,
+            rng
+        }
+    }
+
+    /// Re-arm the election timer with a new random timeout and return the chosen timeout.
+    ///
+    /// Call this whenever a valid message from the leader is received, when granting a vote, and
+    /// when starting an election.
+    pub fn reset_election(&mut self) -> Duration {
+        self.stop_election();
+        let timeout = self.rng.duration_between(self.election_min, self.election_max)
+            .max(self.wheel.tick_duration());
+        let generation = self.next_generation();
+        self.wheel.start((RaftTimeout::Election, generation), timeout);
+        self.election = Some(generation);
+        timeout
+    }
+
+    /// Disarm the election timer
+    pub fn stop_election(&mut self) {
+        if let Some(generation) = self.election.take() {
+            self.wheel.stop((RaftTimeout::Election, generation));
+        }
+    }
+
+    /// Start firing `RaftTimeout::Heartbeat` every heartbeat interval
+    pub fn start_heartbeat(&mut self) {
+        self.stop_heartbeat();
+        self.arm_heartbeat();
+    }
+
+    /// Stop the heartbeat timer
+    pub fn stop_heartbeat(&mut self) {
+        if let Some(generation) = self.heartbeat.take() {
+            self.wheel.stop((RaftTimeout::Heartbeat, generation));
+        }
+    }
+
+    /// Stop waiting for an election and start sending heartbeats
+    pub fn become_leader(&mut self) {
+        self.stop_election();
+        self.start_heartbeat();
+    }
+
+    /// Stop sending heartbeats and wait for the leader with a fresh election timeout
+    pub fn become_follower(&mut self) -> Duration {
+        self.stop_heartbeat();
+        self.reset_election()
+    }
+
+    pub fn election_armed(&self) -> bool {
+        self.election.is_some()
+    }
+
+    pub fn heartbeat_armed(&self) -> bool {
+        self.heartbeat.is_some()
+    }
+
+    /// Advance the timers by one tick and return any timeouts that fired
+    pub fn expire(&mut self) -> Vec<RaftTimeout> {
+        let mut fired = Vec::new();
+        for (kind, generation) in self.wheel.expire() {
+            match kind {
+                RaftTimeout::Election if self.election == Some(generation) => {
+                    self.election = None;
+                    fired.push(kind);
+                },
+                RaftTimeout::Heartbeat if self.heartbeat == Some(generation) => {
+                    self.arm_heartbeat();
+                    fired.push(kind);
+                },
+                _ => ()
+            }
+        }
+        fired
+    }
+
+    fn arm_heartbeat(&mut self) {
+        let generation = self.next_generation();
+        let interval = self.heartbeat_interval.max(self.wheel.tick_duration());
+        self.wheel.start((RaftTimeout::Heartbeat, generation), interval);
+        self.heartbeat = Some(generation);
+    }
+
+    fn next_generation(&mut self) -> u64 {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    fn timeouts() -> RaftTimeouts {
+        RaftTimeouts::with_seed(vec![Resolution::TenMs, Resolution::HundredMs, Resolution::Sec],
+                                Duration::from_millis(150),
+                                Duration::from_millis(300),
+                                Duration::from_millis(50),
+                                42)
+    }
+
+    fn ticks_until(timeouts: &mut RaftTimeouts, expected: RaftTimeout, max: usize) -> Option<usize> {
+        (1..max + 1).find(|_| timeouts.expire().contains(&expected))
+    }
+
+    #[test]
+    fn election_fires_once_within_range() {
+        let mut timeouts = timeouts();
+        timeouts.reset_election();
+        let ticks = ticks_until(&mut timeouts, RaftTimeout::Election, 100).unwrap();
+
+        // 10ms ticks. The wheel never fires early and rounds up by at most one coarse slot.
+        assert!((15..41).contains(&ticks));
+        assert!(!timeouts.election_armed());
+        assert_eq!(None, ticks_until(&mut timeouts, RaftTimeout::Election, 100));
+    }
+
+    #[test]
+    fn reset_prevents_earlier_election() {
+        let mut timeouts = timeouts();
+        timeouts.reset_election();
+        for _ in 0..10 {
+            for _ in 0..10 {
+                assert!(timeouts.expire().is_empty());
+            }
+            timeouts.reset_election();
+        }
+    }
+
+    #[test]
+    fn heartbeat_repeats_until_stopped() {
+        let mut timeouts = timeouts();
+        timeouts.reset_election();
+        timeouts.become_leader();
+        assert!(!timeouts.election_armed());
+        for _ in 0..3 {
+            assert_eq!(Some(6), ticks_until(&mut timeouts, RaftTimeout::Heartbeat, 10));
+        }
+        timeouts.stop_heartbeat();
+        assert_eq!(None, ticks_until(&mut timeouts, RaftTimeout::Heartbeat, 100));
+    }
+
+    #[test]
+    fn timeouts_shorter_than_a_tick_still_fire() {
+        let mut timeouts = RaftTimeouts::with_seed(vec![Resolution::TenMs, Resolution::Sec],
+                                                   Duration::from_millis(2),
+                                                   Duration::from_millis(5),
+                                                   Duration::from_millis(5),
+                                                   7);
+        assert_eq!(Duration::from_millis(10), timeouts.reset_election());
+        assert_eq!(Some(2), ticks_until(&mut timeouts, RaftTimeout::Election, 10));
+        timeouts.become_leader();
+        for _ in 0..3 {
+            assert_eq!(Some(2), ticks_until(&mut timeouts, RaftTimeout::Heartbeat, 10));
+        }
+    }
+}
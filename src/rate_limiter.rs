@@ -0,0 +1,125 @@
+use std::hash::Hash;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Duration;
+use super::{CopyWheel, Wheel, Resolution};
+
+/// The answer to `RateLimiter::check`
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum RateDecision {
+    Allowed,
+    /// The key has used up its window, which closes after this long
+    RetryAfter(Duration)
+}
+
+/// A per key rate limiter allowing up to `limit` operations in each fixed window.
+///
+/// A key's window opens with its first operation and is closed by a timer on a `CopyWheel`, so
+/// idle keys cost nothing once their window has passed and there is no sweep over every key. The
+/// limiter must be driven by calling `expire` at the maximum resolution, exactly like the wheel
+/// itself. Windows close on the first tick after they have fully elapsed.
+pub struct RateLimiter<K: Eq + Hash + Debug + Clone> {
+    limit: usize,
+    window: Duration,
+    // The number of operations in each open window
+    counts: HashMap<K, usize>,
+    wheel: CopyWheel<K>
+}
+
+impl<K: Eq + Hash + Debug + Clone> RateLimiter<K> {
+    /// Create a limiter allowing `limit` operations per key in each `window`, with windows tracked
+    /// by a wheel with the given resolutions.
+    pub fn new(limit: usize, window: Duration, resolutions: Vec<Resolution>) -> RateLimiter<K> {
+        assert!(limit > 0);
+        let mut wheel = CopyWheel::new(resolutions);
+        wheel.set_precise(true);
+        assert!(window >= wheel.tick_duration(), "a window must last at least a tick");
+        RateLimiter {
+            limit,
+            window,
+            counts: HashMap::new(),
+            wheel
+        }
+    }
+
+    /// Count an operation for `key` if its window has room for it.
+    ///
+    /// Operations that are turned away don't count against the window.
+    pub fn check(&mut self, key: K) -> RateDecision {
+        match self.counts.get_mut(&key) {
+            Some(count) if *count < self.limit => *count += 1,
+            Some(_) => {
+                let left = self.wheel.remaining(&key).unwrap_or(self.window);
+                return RateDecision::RetryAfter(left);
+            },
+            None => {
+                self.wheel.start(key.clone(), self.window);
+                self.counts.insert(key, 1);
+            }
+        }
+        RateDecision::Allowed
+    }
+
+    /// The number of operations `key` may still make in its current window
+    pub fn remaining(&self, key: &K) -> usize {
+        self.limit - self.counts.get(key).cloned().unwrap_or(0)
+    }
+
+    /// Close a key's window early, so its next operation opens a new one
+    pub fn reset(&mut self, key: &K) {
+        if self.counts.remove(key).is_some() {
+            self.wheel.stop(key.clone());
+        }
+    }
+
+    /// The number of keys with an open window
+    pub fn len(&self) -> usize {
+        self.counts.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    /// How often `expire` must be called
+    pub fn tick_duration(&self) -> Duration {
+        self.wheel.tick_duration()
+    }
+
+    /// Advance the wheel by one tick and close the windows that have elapsed
+    pub fn expire(&mut self) {
+        for key in self.wheel.expire() {
+            self.counts.remove(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    #[test]
+    fn keys_are_limited_until_their_window_closes() {
+        let mut limiter = RateLimiter::new(2, Duration::from_millis(50),
+                                           vec![Resolution::TenMs, Resolution::Sec]);
+        assert_eq!(RateDecision::Allowed, limiter.check("a"));
+        assert_eq!(RateDecision::Allowed, limiter.check("b"));
+        limiter.expire();
+        assert_eq!(RateDecision::Allowed, limiter.check("a"));
+        assert_eq!(RateDecision::RetryAfter(Duration::from_millis(50)), limiter.check("a"));
+        assert_eq!(0, limiter.remaining(&"a"));
+        assert_eq!(1, limiter.remaining(&"b"));
+        limiter.reset(&"b");
+        assert_eq!(2, limiter.remaining(&"b"));
+
+        for _ in 0..4 {
+            limiter.expire();
+        }
+        assert_eq!(RateDecision::RetryAfter(Duration::from_millis(10)), limiter.check("a"));
+        limiter.expire();
+        assert!(limiter.is_empty());
+        assert_eq!(RateDecision::Allowed, limiter.check("a"));
+    }
+}
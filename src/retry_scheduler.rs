@@ -0,0 +1,177 @@
+use std::hash::Hash;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Duration;
+use super::{CopyWheel, Wheel, Resolution};
+use rng::Rng;
+
+/// How long a `RetryScheduler` waits before each attempt
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    /// The delay before the first attempt
+    pub base: Duration,
+    /// What each delay is multiplied by to get the next
+    pub multiplier: f64,
+    /// The longest delay, however many attempts have been made
+    pub cap: Duration,
+    /// The fraction of each delay, from 0 to 1, that is randomly taken off so that keys that
+    /// failed together don't all retry together
+    pub jitter: f64
+}
+
+impl Backoff {
+    /// The delay before attempt `attempt`, counting from 1, before jitter is taken off
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(i32::MAX as u32) as i32;
+        let secs = self.base.as_secs_f64() * self.multiplier.powi(exponent);
+        if secs.is_finite() && secs < self.cap.as_secs_f64() {
+            Duration::from_secs_f64(secs)
+        } else {
+            self.cap
+        }
+    }
+}
+
+/// Schedules retries for a set of keys, backing off exponentially with each attempt.
+///
+/// `schedule` arms the next attempt for a key, and `expire` returns the keys whose attempt is due
+/// along with its number. A key's attempts keep counting up until `reset` is called, typically
+/// once an attempt succeeds. Like the wheels, `expire` must be called at the maximum resolution,
+/// and delays are rounded to the resolution of the wheel they land in, which suits the long
+/// delays of later attempts.
+pub struct RetryScheduler<K: Eq + Hash + Debug + Clone> {
+    backoff: Backoff,
+    attempts: HashMap<K, u32>,
+    wheel: CopyWheel<K>,
+    rng: Rng
+}
+
+impl<K: Eq + Hash + Debug + Clone> RetryScheduler<K> {
+    pub fn new(resolutions: Vec<Resolution>, backoff: Backoff) -> RetryScheduler<K> {
+        RetryScheduler::with_rng(resolutions, backoff, Rng::from_entropy())
+    }
+
+    /// Create a scheduler with a fixed jitter seed, so that tests and simulations are
+    /// reproducible.
+    pub fn with_seed(resolutions: Vec<Resolution>, backoff: Backoff, seed: u64) -> RetryScheduler<K> {
+        RetryScheduler::with_rng(resolutions, backoff, Rng::new(seed))
+    }
+
+    fn with_rng(resolutions: Vec<Resolution>, backoff: Backoff, rng: Rng) -> RetryScheduler<K> {
+        assert!(backoff.base <= backoff.cap);
+        assert!((0.0..=1.0).contains(&backoff.jitter), "jitter must be between 0 and 1");
+        RetryScheduler {
+            backoff,
+            attempts: HashMap::new(),
+            wheel: CopyWheel::new(resolutions),
+            rng
+        }
+    }
+
+    /// Arm the next attempt for a key and return how long it will wait.
+    ///
+    /// Scheduling a key whose attempt is still pending replaces that attempt with the next one.
+    /// Delays are rounded up to a tick.
+    pub fn schedule(&mut self, key: K) -> Duration {
+        let attempt = {
+            let attempts = self.attempts.entry(key.clone()).or_insert(0);
+            *attempts += 1;
+            *attempts
+        };
+        let max = self.backoff.delay(attempt);
+        let min = max.mul_f64(1.0 - self.backoff.jitter);
+        let delay = self.rng.duration_between(min, max).max(self.wheel.tick_duration());
+        self.wheel.start(key, delay);
+        delay
+    }
+
+    /// Forget a key's attempts and cancel any that is pending, so its next `schedule` starts
+    /// from the base delay
+    pub fn reset(&mut self, key: &K) {
+        if self.attempts.remove(key).is_some() {
+            self.wheel.cancel(key);
+        }
+    }
+
+    /// The number of attempts scheduled for a key since it was last reset
+    pub fn attempts(&self, key: &K) -> u32 {
+        self.attempts.get(key).cloned().unwrap_or(0)
+    }
+
+    /// Whether a key has an attempt waiting to come due
+    pub fn is_pending(&self, key: &K) -> bool {
+        self.wheel.contains(key)
+    }
+
+    /// The number of keys with an attempt pending
+    pub fn len(&self) -> usize {
+        self.wheel.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.wheel.is_empty()
+    }
+
+    /// How often `expire` must be called
+    pub fn tick_duration(&self) -> Duration {
+        self.wheel.tick_duration()
+    }
+
+    /// Advance the wheel by one tick and return the keys due for a retry, with the number of
+    /// the attempt that is due
+    pub fn expire(&mut self) -> Vec<(K, u32)> {
+        let attempts = &self.attempts;
+        self.wheel.expire().into_iter()
+            .map(|key| {
+                let attempt = attempts.get(&key).cloned().unwrap_or(0);
+                (key, attempt)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    fn backoff(jitter: f64) -> Backoff {
+        Backoff {
+            base: Duration::from_millis(20),
+            multiplier: 2.0,
+            cap: Duration::from_millis(100),
+            jitter
+        }
+    }
+
+    #[test]
+    fn delays_grow_until_the_cap() {
+        let mut retries = RetryScheduler::new(vec![Resolution::TenMs, Resolution::Sec], backoff(0.0));
+        let delays: Vec<_> = (0..4).map(|_| retries.schedule("upload")).collect();
+        let millis = |ms: Vec<u64>| ms.into_iter().map(Duration::from_millis).collect::<Vec<_>>();
+        assert_eq!(millis(vec![20, 40, 80, 100]), delays);
+        assert_eq!(4, retries.attempts(&"upload"));
+
+        let mut due = Vec::new();
+        for tick in 1..20 {
+            due.extend(retries.expire().into_iter().map(|(key, attempt)| (tick, key, attempt)));
+        }
+        assert_eq!(vec![(11, "upload", 4)], due);
+        retries.reset(&"upload");
+        assert_eq!(Duration::from_millis(20), retries.schedule("upload"));
+        retries.reset(&"upload");
+        assert!(retries.is_empty());
+    }
+
+    #[test]
+    fn jitter_stays_within_the_delay() {
+        let mut retries = RetryScheduler::with_seed(vec![Resolution::Ms, Resolution::Sec],
+                                                    backoff(0.5), 7);
+        for attempt in 1..10 {
+            let delay = retries.schedule(1);
+            let max = retries.backoff.delay(attempt);
+            assert!(delay >= max / 2 && delay <= max, "{:?} out of range", delay);
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+/// A small xorshift64* generator used to add jitter to timeouts.
+///
+/// This is not cryptographically secure. It only needs to be cheap and spread timeouts out.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // A zero state would only ever produce zeros
+        Rng {
+            state: if seed == 0 { 0x9E37_79B9_7F4A_7C15 } else { seed }
+        }
+    }
+
+    /// Seed the generator from the randomly keyed std hasher
+    pub fn from_entropy() -> Rng {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u64(0);
+        Rng::new(hasher.finish())
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Return a duration uniformly distributed in `[min, max]`
+    pub fn duration_between(&mut self, min: Duration, max: Duration) -> Duration {
+        if max <= min {
+            return min;
+        }
+        let span = (max - min).as_nanos() as u64;
+        let offset = self.next_u64() % span.saturating_add(1);
+        min + Duration::from_nanos(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn durations_stay_in_range() {
+        let mut rng = Rng::new(7);
+        let min = Duration::from_millis(150);
+        let max = Duration::from_millis(300);
+        for _ in 0..1000 {
+            let d = rng.duration_between(min, max);
+            assert!(d >= min && d <= max, "{:?} out of range", d);
+        }
+        assert_eq!(min, rng.duration_between(min, min));
+    }
+}
@@ -0,0 +1,280 @@
+use std::hash::Hash;
+use std::collections::{HashMap, BTreeMap};
+use std::fmt::Debug;
+use std::ops::Range;
+use std::time::Duration;
+use super::{CopyWheel, Wheel, Resolution};
+
+/// Per connection RTT estimate and outstanding retransmission timers
+struct Connection {
+    srtt: Option<Duration>,
+    rttvar: Duration,
+    rto: Duration,
+    outstanding: BTreeMap<u64, u64>
+}
+
+/// Retransmission timers keyed by connection and sequence number.
+///
+/// The retransmission timeout for each connection is computed from RTT samples as described in
+/// RFC 6298, clamped to `[min_rto, max_rto]`, and doubled each time one of the connection's timers
+/// fires. Timers are armed with the connection's current RTO, so `restart` after a retransmission
+/// picks up both new RTT samples and any backoff. An RTO shorter than a tick is rounded up to a tick
+/// when arming the timer.
+///
+/// Acknowledgements cancel whole ranges of sequence numbers at once. Cancelled timers are ignored
+/// if their wheel entry fires later.
+pub struct RtoManager<C: Eq + Hash + Debug + Clone> {
+    initial_rto: Duration,
+    min_rto: Duration,
+    max_rto: Duration,
+    connections: HashMap<C, Connection>,
+    wheel: CopyWheel<(C, u64, u64)>,
+    next_generation: u64
+}
+
+impl<C: Eq + Hash + Debug + Clone> RtoManager<C> {
+    /// Create a manager. Connections start with `initial_rto` until an RTT sample is taken.
+    pub fn new(resolutions: Vec<Resolution>,
+               initial_rto: Duration,
+               min_rto: Duration,
+               max_rto: Duration) -> RtoManager<C>
+    {
+        assert!(min_rto <= max_rto);
+        RtoManager {
+            initial_rto,
+            min_rto,
+            max_rto,
+            connections: HashMap::new(),
+            wheel: CopyWheel::new(resolutions),
+            next_generation: 0
+        }
+    }
+
+    /// Arm the retransmission timer for a sequence number with the connection's current RTO.
+    ///
+    /// Scheduling a sequence number that is already outstanding restarts its timer.
+    pub fn schedule(&mut self, connection: C, seq: u64) {
+        self.cancel(&connection, seq);
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        let rto = {
+            let initial = self.initial_rto.clamp(self.min_rto, self.max_rto);
+            let conn = self.connections.entry(connection.clone()).or_insert_with(|| Connection {
+                srtt: None,
+                rttvar: Duration::from_secs(0),
+                rto: initial,
+                outstanding: BTreeMap::new()
+            });
+            conn.outstanding.insert(seq, generation);
+            conn.rto
+        };
+        self.wheel.start((connection, seq, generation), rto.max(self.wheel.tick_duration()));
+    }
+
+    /// Re-arm a retransmission timer, typically right after retransmitting the segment
+    pub fn restart(&mut self, connection: C, seq: u64) {
+        self.schedule(connection, seq);
+    }
+
+    /// Cancel the timer for a single sequence number. Returns false if it wasn't outstanding.
+    pub fn cancel(&mut self, connection: &C, seq: u64) -> bool {
+        let generation = match self.connections.get_mut(connection) {
+            Some(conn) => conn.outstanding.remove(&seq),
+            None => None
+        };
+        match generation {
+            Some(generation) => {
+                self.wheel.stop((connection.clone(), seq, generation));
+                true
+            },
+            None => false
+        }
+    }
+
+    /// Cumulatively acknowledge every sequence number below `up_to`.
+    /// Returns the number of timers cancelled.
+    pub fn ack(&mut self, connection: &C, up_to: u64) -> usize {
+        self.ack_range(connection, 0..up_to)
+    }
+
+    /// Acknowledge a range of sequence numbers, as with a selective ack.
+    /// Returns the number of timers cancelled.
+    pub fn ack_range(&mut self, connection: &C, range: Range<u64>) -> usize {
+        if range.start >= range.end {
+            return 0;
+        }
+        let acked: Vec<(u64, u64)> = match self.connections.get_mut(connection) {
+            Some(conn) => {
+                let acked: Vec<(u64, u64)> = conn.outstanding.range(range)
+                    .map(|(&seq, &generation)| (seq, generation))
+                    .collect();
+                for &(seq, _) in &acked {
+                    conn.outstanding.remove(&seq);
+                }
+                acked
+            },
+            None => return 0
+        };
+        for &(seq, generation) in &acked {
+            self.wheel.stop((connection.clone(), seq, generation));
+        }
+        acked.len()
+    }
+
+    /// Feed an RTT measurement for a connection and recompute its RTO.
+    ///
+    /// Per Karn's algorithm, callers should not take samples from retransmitted segments.
+    pub fn rtt_sample(&mut self, connection: &C, rtt: Duration) {
+        let (min_rto, max_rto) = (self.min_rto, self.max_rto);
+        if let Some(conn) = self.connections.get_mut(connection) {
+            let srtt = match conn.srtt {
+                None => {
+                    conn.rttvar = rtt / 2;
+                    rtt
+                },
+                Some(srtt) => {
+                    let delta = srtt.abs_diff(rtt);
+                    conn.rttvar = conn.rttvar * 3 / 4 + delta / 4;
+                    srtt * 7 / 8 + rtt / 8
+                }
+            };
+            conn.srtt = Some(srtt);
+            let rto = srtt + conn.rttvar * 4;
+            conn.rto = rto.clamp(min_rto, max_rto);
+        }
+    }
+
+    /// The current retransmission timeout for a connection
+    pub fn rto(&self, connection: &C) -> Option<Duration> {
+        self.connections.get(connection).map(|conn| conn.rto)
+    }
+
+    /// The smoothed RTT for a connection, if any samples have been taken
+    pub fn srtt(&self, connection: &C) -> Option<Duration> {
+        self.connections.get(connection).and_then(|conn| conn.srtt)
+    }
+
+    /// The number of outstanding timers for a connection
+    pub fn outstanding(&self, connection: &C) -> usize {
+        self.connections.get(connection).map_or(0, |conn| conn.outstanding.len())
+    }
+
+    /// Cancel all timers for a connection and forget its RTT state
+    pub fn remove_connection(&mut self, connection: &C) {
+        if let Some(conn) = self.connections.remove(connection) {
+            for (seq, generation) in conn.outstanding {
+                self.wheel.stop((connection.clone(), seq, generation));
+            }
+        }
+    }
+
+    /// Advance the wheel by one tick and return the segments that must be retransmitted.
+    ///
+    /// The RTO of each connection with an expired timer is doubled, up to `max_rto`.
+    pub fn expire(&mut self) -> Vec<(C, u64)> {
+        let max_rto = self.max_rto;
+        let mut expired = Vec::new();
+        for (connection, seq, generation) in self.wheel.expire() {
+            if let Some(conn) = self.connections.get_mut(&connection) {
+                if conn.outstanding.get(&seq) == Some(&generation) {
+                    conn.outstanding.remove(&seq);
+                    conn.rto = (conn.rto * 2).min(max_rto);
+                    expired.push((connection, seq));
+                }
+            }
+        }
+        expired
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    fn manager() -> RtoManager<u32> {
+        RtoManager::new(vec![Resolution::TenMs, Resolution::HundredMs, Resolution::Sec, Resolution::Min],
+                        Duration::from_secs(1),
+                        Duration::from_millis(200),
+                        Duration::from_secs(60))
+    }
+
+    #[test]
+    fn rtt_samples_update_rto() {
+        let mut rto = manager();
+        rto.schedule(1, 0);
+        assert_eq!(Some(Duration::from_secs(1)), rto.rto(&1));
+        rto.rtt_sample(&1, Duration::from_millis(100));
+        // srtt + 4 * rtt/2 = 300ms
+        assert_eq!(Some(Duration::from_millis(300)), rto.rto(&1));
+        assert_eq!(Some(Duration::from_millis(100)), rto.srtt(&1));
+        rto.rtt_sample(&1, Duration::from_millis(100));
+        // rttvar decays to 37.5ms, giving 250ms
+        assert_eq!(Some(Duration::from_millis(250)), rto.rto(&1));
+    }
+
+    #[test]
+    fn expiry_backs_off() {
+        let mut rto = manager();
+        rto.schedule(1, 7);
+        rto.rtt_sample(&1, Duration::from_millis(50));
+        let mut expired = Vec::new();
+        for _ in 0..200 {
+            expired.extend(rto.expire());
+        }
+        assert_eq!(vec![(1, 7)], expired);
+        assert_eq!(Some(Duration::from_millis(400)), rto.rto(&1));
+        assert_eq!(0, rto.outstanding(&1));
+    }
+
+    #[test]
+    fn acks_cancel_ranges() {
+        let mut rto = manager();
+        for seq in 0..10 {
+            rto.schedule(1, seq);
+        }
+        rto.schedule(2, 0);
+        assert_eq!(5, rto.ack(&1, 5));
+        assert_eq!(2, rto.ack_range(&1, 7..9));
+        assert!(rto.cancel(&1, 9));
+        assert_eq!(2, rto.outstanding(&1));
+
+        let mut expired = Vec::new();
+        for _ in 0..200 {
+            expired.extend(rto.expire());
+        }
+        expired.sort();
+        assert_eq!(vec![(1, 5), (1, 6), (2, 0)], expired);
+    }
+
+    #[test]
+    fn removing_connection_cancels_restarted_timers() {
+        let mut rto = manager();
+        rto.schedule(1, 0);
+        for _ in 0..50 {
+            assert!(rto.expire().is_empty());
+        }
+        rto.restart(1, 0);
+        rto.remove_connection(&1);
+        for _ in 0..200 {
+            assert!(rto.expire().is_empty());
+        }
+    }
+
+    #[test]
+    fn rtos_shorter_than_a_tick_still_fire() {
+        let mut rto = RtoManager::new(vec![Resolution::TenMs, Resolution::Sec],
+                                      Duration::from_millis(2),
+                                      Duration::from_millis(1),
+                                      Duration::from_secs(1));
+        rto.schedule(1, 0);
+        assert_eq!(Some(Duration::from_millis(2)), rto.rto(&1));
+        assert!(rto.expire().is_empty());
+        assert_eq!(vec![(1, 0)], rto.expire());
+        assert_eq!(Some(Duration::from_millis(4)), rto.rto(&1));
+        rto.restart(1, 0);
+        assert!(rto.expire().is_empty());
+        assert_eq!(vec![(1, 0)], rto.expire());
+    }
+}
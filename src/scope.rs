@@ -0,0 +1,111 @@
+use core::cell::RefCell;
+use core::hash::Hash;
+use core::mem;
+use core::fmt::Debug;
+use core::time::Duration;
+use alloc::rc::Weak;
+use collections::HashMap;
+use super::Wheel;
+
+/// A set of timers that are all cancelled when the scope is dropped.
+///
+/// Created with `SharedWheel::scope`. Give each connection or task its own scope and start its
+/// timers through it, and they are cleaned up along with it without tracking them by hand. The
+/// scope doesn't keep the wheel alive.
+///
+/// The scope remembers every key started through it until the key is stopped through the scope or
+/// the scope is dropped, whether or not the timer has expired since. As with `TimerGuard`, keys
+/// should be unique to the scope.
+pub struct TimerScope<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    wheel: Weak<RefCell<W>>,
+    keys: HashMap<T, ()>
+}
+
+impl<T, W> TimerScope<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    pub(crate) fn new(wheel: Weak<RefCell<W>>) -> TimerScope<T, W> {
+        TimerScope {
+            wheel,
+            keys: HashMap::new()
+        }
+    }
+
+    /// Start or restart a timer owned by this scope
+    pub fn start(&mut self, key: T, time: Duration) {
+        if let Some(wheel) = self.wheel.upgrade() {
+            wheel.borrow_mut().start(key.clone(), time);
+            self.keys.insert(key, ());
+        }
+    }
+
+    /// Stop a timer owned by this scope
+    pub fn stop(&mut self, key: T) {
+        if self.keys.remove(&key).is_some() {
+            if let Some(wheel) = self.wheel.upgrade() {
+                wheel.borrow_mut().stop(key);
+            }
+        }
+    }
+
+    /// Cancel all the timers owned by this scope, leaving it empty for reuse
+    pub fn cancel_all(&mut self) {
+        let keys = mem::take(&mut self.keys);
+        if let Some(wheel) = self.wheel.upgrade() {
+            let mut wheel = wheel.borrow_mut();
+            for (key, _) in keys {
+                wheel.stop(key);
+            }
+        }
+    }
+
+    /// The number of keys owned by this scope
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+impl<T, W> Drop for TimerScope<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    fn drop(&mut self) {
+        self.cancel_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use super::super::{CopyWheel, Resolution, SharedWheel, Wheel};
+
+    #[test]
+    fn dropping_scope_cancels_its_timers() {
+        let mut wheel = SharedWheel::new(CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]));
+        let mut conn = wheel.scope();
+        let mut other = wheel.scope();
+        conn.start("read", Duration::from_millis(10));
+        conn.start("write", Duration::from_millis(10));
+        conn.start("idle", Duration::from_millis(10));
+        conn.stop("idle");
+        other.start("other", Duration::from_millis(10));
+        assert_eq!(2, conn.len());
+        drop(conn);
+        assert!(wheel.expire().is_empty());
+        assert_eq!(vec!["other"], wheel.expire());
+
+        other.start("again", Duration::from_millis(10));
+        other.cancel_all();
+        assert!(other.is_empty());
+        assert!(wheel.expire().is_empty());
+        assert!(wheel.expire().is_empty());
+    }
+}
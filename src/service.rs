@@ -0,0 +1,113 @@
+use std::hash::Hash;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+use super::{run_blocking, QueueHandle, QueuedWheel, Wheel};
+
+/// A wheel driven by its own thread, which sends the keys of expired timers over a channel.
+///
+/// Timers are started and stopped through the service or through `QueueHandle`s returned by
+/// `handle`, which can be cloned and sent to other threads. The thread ticks the wheel with
+/// `run_blocking` and exits when the service is dropped or the receiver of expired keys is.
+pub struct TimerService<T> {
+    handle: QueueHandle<T>,
+    running: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>
+}
+
+impl<T: Eq + Hash + Debug + Clone + Send + 'static> TimerService<T> {
+    /// Start a thread driving the wheel returned by `new_wheel`, returning the service and the
+    /// receiver of expired keys.
+    ///
+    /// The wheel is created on the service's thread, so wheels that can't be sent between
+    /// threads, such as `AllocWheel`, can be used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `new_wheel` does.
+    pub fn spawn<W, F>(new_wheel: F) -> (TimerService<T>, Receiver<T>)
+        where W: Wheel<T> + 'static,
+              F: FnOnce() -> W + Send + 'static
+    {
+        let running = Arc::new(AtomicBool::new(true));
+        let (handle_sender, handle) = channel();
+        let (sender, expired) = channel();
+        let thread = {
+            let running = running.clone();
+            thread::spawn(move || {
+                let mut wheel = QueuedWheel::new(new_wheel());
+                if handle_sender.send(wheel.handle()).is_err() {
+                    return;
+                }
+                run_blocking(&mut wheel, |_, keys| {
+                    for key in keys {
+                        if sender.send(key).is_err() {
+                            return false;
+                        }
+                    }
+                    running.load(Ordering::Relaxed)
+                });
+            })
+        };
+        let service = TimerService {
+            // The thread only fails to send a handle if `new_wheel` panicked
+            handle: handle.recv().expect("creating the wheel panicked"),
+            running,
+            thread: Some(thread)
+        };
+        (service, expired)
+    }
+}
+
+impl<T> TimerService<T> {
+    /// Start a timer, restarting it if it's already running
+    pub fn start(&self, key: T, time: Duration) {
+        self.handle.start(key, time);
+    }
+
+    pub fn stop(&self, key: T) {
+        self.handle.stop(key);
+    }
+
+    /// Return a handle for starting and stopping timers from other threads
+    pub fn handle(&self) -> QueueHandle<T> {
+        self.handle.clone()
+    }
+}
+
+/// Stops the thread, waiting for it to finish its current tick
+impl<T> Drop for TimerService<T> {
+    fn drop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{AllocWheel, Resolution};
+
+    #[test]
+    fn expired_keys_arrive_on_the_channel() {
+        let (service, expired) = TimerService::spawn(|| {
+            AllocWheel::new(vec![Resolution::TenMs, Resolution::Sec])
+        });
+        let handle = service.handle();
+        thread::spawn(move || handle.start("b", Duration::from_millis(50))).join().unwrap();
+        service.start("a", Duration::from_millis(10));
+        service.start("c", Duration::from_millis(10));
+        service.stop("c");
+        let timeout = Duration::from_secs(5);
+        assert_eq!(Ok("a"), expired.recv_timeout(timeout));
+        assert_eq!(Ok("b"), expired.recv_timeout(timeout));
+        drop(service);
+        assert!(expired.recv().is_err());
+    }
+}
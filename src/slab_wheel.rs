@@ -0,0 +1,244 @@
+use core::mem;
+use core::time::Duration;
+use alloc::vec::Vec;
+use super::{InnerWheel, Resolution};
+use super::{deadline, remaining, resolution_duration, slots_ahead, wheel_sizes};
+
+/// Identifies a timer started on a `SlabWheel`.
+///
+/// Ids are only meaningful to the wheel that returned them. An id stays unique after its timer
+/// expires or is cancelled, so a stale id never refers to a later timer.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct TimerId {
+    index: usize,
+    generation: u64
+}
+
+enum SlabEntry<V> {
+    Occupied { value: V, deadline: u64, generation: u64 },
+    Vacant { next_free: Option<usize>, generation: u64 }
+}
+
+/// A wheel that hands out a `TimerId` for each timer and gives back a value stored with it when
+/// it expires.
+///
+/// Timers live in a slab indexed by their id, so there are no bounds on the value and no hashing.
+/// Cancelling is a slab lookup. As in the other wheels, cancelled timers leave an entry in their
+/// slot that is skipped when the slot is visited.
+pub struct SlabWheel<V> {
+    resolutions: Vec<Resolution>,
+    slab: Vec<SlabEntry<V>>,
+    free: Option<usize>,
+    len: usize,
+    wheels: Vec<InnerWheel<TimerId>>,
+    slot_indexes: Vec<usize>,
+    tick: u64
+}
+
+impl<V> SlabWheel<V> {
+    /// Create a set of hierarchical inner wheels. See `CopyWheel::new`.
+    pub fn new(mut resolutions: Vec<Resolution>) -> SlabWheel<V> {
+        let sizes = wheel_sizes(&mut resolutions);
+        let indexes = vec![0; sizes.len()];
+        SlabWheel {
+            resolutions,
+            slab: Vec::new(),
+            free: None,
+            len: 0,
+            wheels: sizes.iter().map(|size| InnerWheel::new(*size)).collect(),
+            slot_indexes: indexes,
+            tick: 0
+        }
+    }
+
+    /// Start a timer holding `value`, returning its id.
+    ///
+    /// Durations shorter than a tick are rounded up to a tick rather than ignored, so the value
+    /// always comes back. As in `U64Wheel`, durations longer than the wheel span are clamped to
+    /// the last slot.
+    pub fn start(&mut self, time: Duration, value: V) -> TimerId {
+        let time = time.max(self.tick_duration());
+        let (index, generation) = match self.free {
+            Some(index) => match self.slab[index] {
+                SlabEntry::Vacant { next_free, generation } => {
+                    self.free = next_free;
+                    (index, generation)
+                },
+                SlabEntry::Occupied { .. } => unreachable!("free list points at a running timer")
+            },
+            None => {
+                self.slab.push(SlabEntry::Vacant { next_free: None, generation: 0 });
+                (self.slab.len() - 1, 0)
+            }
+        };
+        let id = TimerId { index, generation };
+        let deadline = self.schedule(id, time);
+        self.slab[index] = SlabEntry::Occupied { value, deadline, generation };
+        self.len += 1;
+        id
+    }
+
+    /// Cancel a timer, returning its value, or `None` if it already expired or was cancelled
+    pub fn cancel(&mut self, id: TimerId) -> Option<V> {
+        if !self.contains(id) {
+            return None;
+        }
+        Some(self.release(id.index).0)
+    }
+
+    pub fn contains(&self, id: TimerId) -> bool {
+        match self.slab.get(id.index) {
+            Some(SlabEntry::Occupied { generation, .. }) => *generation == id.generation,
+            _ => false
+        }
+    }
+
+    /// The value of a running timer
+    pub fn get(&self, id: TimerId) -> Option<&V> {
+        match self.slab.get(id.index) {
+            Some(SlabEntry::Occupied { value, generation, .. }) if *generation == id.generation => {
+                Some(value)
+            },
+            _ => None
+        }
+    }
+
+    pub fn get_mut(&mut self, id: TimerId) -> Option<&mut V> {
+        match self.slab.get_mut(id.index) {
+            Some(SlabEntry::Occupied { value, generation, .. }) if *generation == id.generation => {
+                Some(value)
+            },
+            _ => None
+        }
+    }
+
+    /// The time left until a timer expires, rounded up to a whole number of ticks
+    pub fn remaining(&self, id: TimerId) -> Option<Duration> {
+        match self.slab.get(id.index) {
+            Some(SlabEntry::Occupied { deadline, generation, .. })
+                if *generation == id.generation =>
+            {
+                Some(remaining(self.tick, *deadline, self.tick_duration()))
+            },
+            _ => None
+        }
+    }
+
+    /// Return the values of the timers that expired
+    pub fn expire(&mut self) -> Vec<V> {
+        self.tick += 1;
+        let now = self.tick;
+        let mut due = Vec::new();
+        for (wheel, slot_index) in self.wheels.iter_mut().zip(&mut self.slot_indexes) {
+            *slot_index = (*slot_index + 1) % wheel.slots.len();
+            due.append(&mut wheel.slots[*slot_index].entries);
+
+            // We haven't wrapped around to the next wheel
+            if *slot_index != 0 {
+                break;
+            }
+        }
+        // Skip entries for timers that were cancelled
+        let mut expired = Vec::with_capacity(due.len());
+        for id in due {
+            let running = match self.slab.get(id.index) {
+                Some(SlabEntry::Occupied { deadline, generation, .. }) => {
+                    *generation == id.generation && *deadline == now
+                },
+                _ => false
+            };
+            if running {
+                expired.push(self.release(id.index).0);
+            }
+        }
+        expired
+    }
+
+    /// The number of running timers
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// How often `expire` must be called
+    pub fn tick_duration(&self) -> Duration {
+        resolution_duration(self.resolutions[0])
+    }
+
+    // Free the occupied entry at `index`, returning its value and deadline
+    fn release(&mut self, index: usize) -> (V, u64) {
+        let generation = match self.slab[index] {
+            SlabEntry::Occupied { generation, .. } => generation,
+            SlabEntry::Vacant { .. } => unreachable!("released a free slab entry")
+        };
+        let vacant = SlabEntry::Vacant { next_free: self.free, generation: generation + 1 };
+        self.free = Some(index);
+        self.len -= 1;
+        match mem::replace(&mut self.slab[index], vacant) {
+            SlabEntry::Occupied { value, deadline, .. } => (value, deadline),
+            SlabEntry::Vacant { .. } => unreachable!()
+        }
+    }
+
+    // Put `id` in a slot of the coarsest wheel it fits, returning its deadline
+    fn schedule(&mut self, id: TimerId, time: Duration) -> u64 {
+        for wheel_index in (0..self.resolutions.len()).rev() {
+            let coarsest = wheel_index == self.resolutions.len() - 1;
+            let slot = slots_ahead(self.resolutions[wheel_index], time, coarsest);
+            if slot == 1 && wheel_index > 0 {
+                continue;
+            }
+            let max_slot = self.wheels[wheel_index].slots.len();
+            let slot = slot.max(2).min(max_slot);
+            let slot_index = (self.slot_indexes[wheel_index] + slot) % max_slot;
+            self.wheels[wheel_index].slots[slot_index].entries.push(id);
+            return deadline(self.tick, &self.wheels[..wheel_index], slot);
+        }
+        unreachable!("a wheel has no resolutions")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    #[test]
+    fn ids_find_their_timers() {
+        let mut wheel = SlabWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        let a = wheel.start(Duration::from_millis(10), "a");
+        wheel.start(Duration::from_millis(10), "b");
+        assert_eq!(Some("a"), wheel.cancel(a));
+        assert_eq!(None, wheel.cancel(a));
+        // The cancelled timer's slab entry is reused, but its id stays dead
+        let c = wheel.start(Duration::from_millis(20), "c");
+        assert!(!wheel.contains(a) && wheel.contains(c));
+        assert_eq!(None, wheel.get(a));
+        *wheel.get_mut(c).unwrap() = "c2";
+        assert_eq!(2, wheel.len());
+
+        assert!(wheel.expire().is_empty());
+        assert_eq!(vec!["b"], wheel.expire());
+        assert_eq!(Some(Duration::from_millis(10)), wheel.remaining(c));
+        assert_eq!(vec!["c2"], wheel.expire());
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn short_timers_round_up_to_a_tick() {
+        let mut wheel = SlabWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start(Duration::from_millis(1), 1);
+        wheel.start(Duration::from_millis(1500), 2);
+        assert!(wheel.expire().is_empty());
+        assert_eq!(vec![1], wheel.expire());
+        let mut ticks = 2;
+        while wheel.expire().is_empty() {
+            ticks += 1;
+        }
+        assert_eq!(199, ticks);
+    }
+}
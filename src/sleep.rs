@@ -0,0 +1,248 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::time::Duration;
+use collections::HashMap;
+use super::{Clock, CopyWheel, Resolution, SystemClock, Ticks, Wheel};
+
+enum State {
+    Waiting(Option<Waker>),
+    Fired
+}
+
+struct Shared {
+    wheel: CopyWheel<u64>,
+    sleeps: HashMap<u64, State>,
+    next_id: u64
+}
+
+/// A source of `Sleep` futures backed by a shared wheel rather than an async runtime's timer.
+///
+/// Starting and cancelling a sleep costs the same as a wheel timer and expiring them is done a
+/// slot at a time, which suits programs with many coarse timeouts. The futures don't depend on a
+/// particular runtime, but something has to call `tick` once per `tick_duration`. With tokio that
+/// is a task looping over `tokio::time::interval(timer.tick_duration())`, and without a runtime it
+/// can be a thread running `drive`.
+///
+/// Cloning a `Timer` gives another handle to the same wheel.
+#[derive(Clone)]
+pub struct Timer {
+    shared: Arc<Mutex<Shared>>
+}
+
+impl Timer {
+    /// Create a timer with the given resolutions. See `CopyWheel::new`.
+    pub fn new(resolutions: Vec<Resolution>) -> Timer {
+        Timer {
+            shared: Arc::new(Mutex::new(Shared {
+                wheel: CopyWheel::new(resolutions),
+                sleeps: HashMap::new(),
+                next_id: 0
+            }))
+        }
+    }
+
+    /// Return a future that completes once `time` has passed, rounded like a wheel timer.
+    /// Durations shorter than a tick complete straight away.
+    pub fn sleep(&self, time: Duration) -> Sleep {
+        let mut shared = self.lock();
+        let id = shared.next_id;
+        shared.next_id += 1;
+        shared.wheel.start(id, time);
+        let state = if shared.wheel.contains(&id) { State::Waiting(None) } else { State::Fired };
+        shared.sleeps.insert(id, state);
+        Sleep {
+            shared: self.shared.clone(),
+            id
+        }
+    }
+
+    /// Return a future for a timeout on `key` that completes once `time` has passed, like
+    /// `sleep`. Dropping the handle before then cancels the timer, so a timeout that loses a
+    /// `select!` costs nothing more.
+    pub fn timeout<K>(&self, key: K, time: Duration) -> TimerHandle<K> {
+        TimerHandle {
+            key,
+            sleep: self.sleep(time)
+        }
+    }
+
+    /// Expire the sleeps due at the next tick and wake their tasks
+    pub fn tick(&self) {
+        let mut wakers = Vec::new();
+        {
+            let mut shared = self.lock();
+            let Shared { ref mut wheel, ref mut sleeps, .. } = *shared;
+            for id in wheel.expire_iter() {
+                if let Some(State::Waiting(waker)) = sleeps.insert(id, State::Fired) {
+                    wakers.extend(waker);
+                }
+            }
+        }
+        // Wake outside the lock, as a woken task may poll on another thread straight away
+        for waker in wakers {
+            waker.wake();
+        }
+    }
+
+    /// Tick the timer from the current thread until every other handle to it and every sleep has
+    /// been dropped. Ticks are kept on schedule as `run_blocking` keeps them.
+    pub fn drive(&self) {
+        self.drive_with(SystemClock);
+    }
+
+    /// Tick the timer like `drive`, reading the time from `clock` and sleeping with it
+    pub fn drive_with<C: Clock>(&self, clock: C) {
+        let tick = self.tick_duration();
+        let start = clock.now();
+        let mut ticks = 0;
+        while Arc::strong_count(&self.shared) > 1 {
+            ticks += 1;
+            clock.sleep_until(start + Ticks(ticks).to_duration(tick));
+            self.tick();
+        }
+    }
+
+    /// How often `tick` must be called
+    pub fn tick_duration(&self) -> Duration {
+        self.lock().wheel.tick_duration()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Shared> {
+        self.shared.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// A future returned by `Timer::sleep`. Dropping it cancels the timer.
+pub struct Sleep {
+    shared: Arc<Mutex<Shared>>,
+    id: u64
+}
+
+impl Future for Sleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        match shared.sleeps.get_mut(&self.id) {
+            Some(State::Waiting(waker)) => {
+                *waker = Some(cx.waker().clone());
+                Poll::Pending
+            },
+            _ => Poll::Ready(())
+        }
+    }
+}
+
+impl Sleep {
+    /// Whether the sleep's time has passed
+    pub fn is_elapsed(&self) -> bool {
+        let shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        !matches!(shared.sleeps.get(&self.id), Some(State::Waiting(_)))
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        let mut shared = self.shared.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(State::Waiting(_)) = shared.sleeps.remove(&self.id) {
+            shared.wheel.cancel(&self.id);
+        }
+    }
+}
+
+/// A future returned by `Timer::timeout`, carrying the key it was started for. Dropping it
+/// cancels the timer.
+pub struct TimerHandle<K> {
+    key: K,
+    sleep: Sleep
+}
+
+impl<K> TimerHandle<K> {
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Whether the timeout's time has passed
+    pub fn is_elapsed(&self) -> bool {
+        self.sleep.is_elapsed()
+    }
+
+    /// Cancel the timeout if it is still running and return its key
+    pub fn into_key(self) -> K {
+        self.key
+    }
+}
+
+impl<K: Unpin> Future for TimerHandle<K> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        Pin::new(&mut self.get_mut().sleep).poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    struct CountWakes(AtomicUsize);
+
+    impl Wake for CountWakes {
+        fn wake(self: Arc<Self>) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    #[test]
+    fn sleeps_complete_when_their_tick_comes() {
+        let timer = Timer::new(vec![Resolution::TenMs, Resolution::Sec]);
+        let wakes = Arc::new(CountWakes(AtomicUsize::new(0)));
+        let waker = Waker::from(wakes.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        let mut short = Box::pin(timer.sleep(Duration::from_millis(10)));
+        let mut long = Box::pin(timer.sleep(Duration::from_millis(50)));
+        let mut instant = Box::pin(timer.sleep(Duration::from_micros(1)));
+        assert_eq!(Poll::Ready(()), instant.as_mut().poll(&mut cx));
+        assert_eq!(Poll::Pending, short.as_mut().poll(&mut cx));
+        assert_eq!(Poll::Pending, long.as_mut().poll(&mut cx));
+
+        timer.tick();
+        assert_eq!(Poll::Pending, short.as_mut().poll(&mut cx));
+        timer.tick();
+        assert_eq!(1, wakes.0.load(Ordering::SeqCst));
+        assert_eq!(Poll::Ready(()), short.as_mut().poll(&mut cx));
+
+        drop(long);
+        drop(short);
+        drop(instant);
+        assert!(timer.lock().wheel.is_empty());
+        assert!(timer.lock().sleeps.is_empty());
+    }
+
+    #[test]
+    fn dropped_timeouts_are_cancelled() {
+        let timer = Timer::new(vec![Resolution::TenMs, Resolution::Sec]);
+        let waker = Waker::from(Arc::new(CountWakes(AtomicUsize::new(0))));
+        let mut cx = Context::from_waker(&waker);
+
+        let mut request = timer.timeout("request 7", Duration::from_millis(10));
+        let lost = timer.timeout("request 8", Duration::from_millis(10));
+        assert_eq!(&"request 8", lost.key());
+        assert_eq!("request 8", lost.into_key());
+        assert_eq!(1, timer.lock().wheel.len());
+
+        assert_eq!(Poll::Pending, Pin::new(&mut request).poll(&mut cx));
+        timer.tick();
+        timer.tick();
+        assert!(request.is_elapsed());
+        assert_eq!(Poll::Ready(()), Pin::new(&mut request).poll(&mut cx));
+    }
+}
@@ -0,0 +1,357 @@
+//! A minimal open addressed hash map, used for builds without `std` and for `U64Wheel`.
+//!
+//! Only the subset of the `std::collections::HashMap` API used by the wheels is provided, with the
+//! same semantics, so the wheels can use either map interchangeably. Entries are found by linear
+//! probing and removal uses backward shifting, so there are no tombstones to clean up.
+//!
+//! Keys are hashed with FNV-1a by default, which is fast but not resistant to collision attacks.
+//! Don't use keys that an attacker can choose freely in a `no_std` build.
+
+// The API mirrors std's rather than only what the wheels happen to call today
+#![allow(dead_code)]
+
+use core::borrow::Borrow;
+use core::hash::{Hash, Hasher};
+use core::iter::FromIterator;
+use core::marker::PhantomData;
+use core::mem;
+use alloc::vec::{self, Vec};
+
+/// A 64 bit FNV-1a hasher
+pub struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> FnvHasher {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for FnvHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// A hasher that uses an integer key as its own hash.
+///
+/// This is ideal for small, dense keys like file descriptors or sequential ids, which then land in
+/// consecutive buckets. Keys that only differ in their high bits all collide.
+#[derive(Default)]
+pub struct IdentityHasher(u64);
+
+impl Hasher for IdentityHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        // Only integers are expected, but fold anything else in rather than lose it
+        for byte in bytes {
+            self.0 = self.0.rotate_left(8) ^ u64::from(*byte);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.0 = value;
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+fn hash<H: Hasher + Default, Q: Hash + ?Sized>(key: &Q) -> u64 {
+    let mut hasher = H::default();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+struct Bucket<K, V> {
+    hash: u64,
+    key: K,
+    value: V
+}
+
+pub struct HashMap<K, V, H = FnvHasher> {
+    buckets: Vec<Option<Bucket<K, V>>>,
+    len: usize,
+    _hasher: PhantomData<H>
+}
+
+impl<K: Eq + Hash, V, H: Hasher + Default> Default for HashMap<K, V, H> {
+    fn default() -> HashMap<K, V, H> {
+        HashMap::new()
+    }
+}
+
+impl<K: Eq + Hash, V, H: Hasher + Default> HashMap<K, V, H> {
+    pub fn new() -> HashMap<K, V, H> {
+        HashMap {
+            buckets: Vec::new(),
+            len: 0,
+            _hasher: PhantomData
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> HashMap<K, V, H> {
+        let mut map = HashMap::new();
+        map.reserve(capacity);
+        map
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        for bucket in self.buckets.iter_mut() {
+            *bucket = None;
+        }
+        self.len = 0;
+    }
+
+    /// Make room for at least `additional` more entries without growing
+    pub fn reserve(&mut self, additional: usize) {
+        let needed = self.len + additional;
+        // Keep the load factor at or below 7/8
+        if needed * 8 <= self.buckets.len() * 7 {
+            return;
+        }
+        let mut size = self.buckets.len().max(8);
+        while needed * 8 > size * 7 {
+            size *= 2;
+        }
+        let mut buckets = Vec::with_capacity(size);
+        buckets.resize_with(size, || None);
+        let old = mem::replace(&mut self.buckets, buckets);
+        for bucket in old.into_iter().flatten() {
+            let index = self.probe_vacant(bucket.hash);
+            self.buckets[index] = Some(bucket);
+        }
+    }
+
+    /// Insert a value, returning the old value if the key was already present. As with std, the
+    /// existing key is kept.
+    pub fn insert(&mut self, key: K, value: V) -> Option<V> {
+        if let Some(index) = self.find(&key) {
+            let bucket = self.buckets[index].as_mut().unwrap();
+            return Some(mem::replace(&mut bucket.value, value));
+        }
+        self.reserve(1);
+        let hash = hash::<H, K>(&key);
+        let index = self.probe_vacant(hash);
+        self.buckets[index] = Some(Bucket { hash, key, value });
+        self.len += 1;
+        None
+    }
+
+    pub fn get<Q>(&self, key: &Q) -> Option<&V>
+        where K: Borrow<Q>,
+              Q: Eq + Hash + ?Sized
+    {
+        self.find(key).map(|index| &self.buckets[index].as_ref().unwrap().value)
+    }
+
+    pub fn get_key_value<Q>(&self, key: &Q) -> Option<(&K, &V)>
+        where K: Borrow<Q>,
+              Q: Eq + Hash + ?Sized
+    {
+        self.find(key).map(|index| {
+            let bucket = self.buckets[index].as_ref().unwrap();
+            (&bucket.key, &bucket.value)
+        })
+    }
+
+    pub fn get_mut<Q>(&mut self, key: &Q) -> Option<&mut V>
+        where K: Borrow<Q>,
+              Q: Eq + Hash + ?Sized
+    {
+        match self.find(key) {
+            Some(index) => Some(&mut self.buckets[index].as_mut().unwrap().value),
+            None => None
+        }
+    }
+
+    pub fn contains_key<Q>(&self, key: &Q) -> bool
+        where K: Borrow<Q>,
+              Q: Eq + Hash + ?Sized
+    {
+        self.find(key).is_some()
+    }
+
+    pub fn remove<Q>(&mut self, key: &Q) -> Option<V>
+        where K: Borrow<Q>,
+              Q: Eq + Hash + ?Sized
+    {
+        self.remove_entry(key).map(|(_, value)| value)
+    }
+
+    pub fn remove_entry<Q>(&mut self, key: &Q) -> Option<(K, V)>
+        where K: Borrow<Q>,
+              Q: Eq + Hash + ?Sized
+    {
+        let mut hole = self.find(key)?;
+        let removed = self.buckets[hole].take().unwrap();
+        self.len -= 1;
+
+        // Shift back any following entries that were displaced past the hole
+        let mask = self.buckets.len() - 1;
+        let mut index = hole;
+        loop {
+            index = (index + 1) & mask;
+            let ideal = match self.buckets[index] {
+                Some(ref bucket) => bucket.hash as usize & mask,
+                None => break
+            };
+            // Distance from the ideal bucket to the hole and to the current bucket
+            if (hole.wrapping_sub(ideal) & mask) < (index.wrapping_sub(ideal) & mask) {
+                self.buckets[hole] = self.buckets[index].take();
+                hole = index;
+            }
+        }
+        Some((removed.key, removed.value))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.buckets.iter().flatten().map(|bucket| (&bucket.key, &bucket.value))
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = &K> {
+        self.iter().map(|(key, _)| key)
+    }
+
+    /// Keep only the entries for which `f` returns true
+    pub fn retain<F: FnMut(&K, &mut V) -> bool>(&mut self, mut f: F) {
+        let old = mem::take(&mut self.buckets);
+        let capacity = old.len();
+        self.buckets.resize_with(capacity, || None);
+        self.len = 0;
+        for mut bucket in old.into_iter().flatten() {
+            if f(&bucket.key, &mut bucket.value) {
+                let index = self.probe_vacant(bucket.hash);
+                self.buckets[index] = Some(bucket);
+                self.len += 1;
+            }
+        }
+    }
+
+    fn find<Q>(&self, key: &Q) -> Option<usize>
+        where K: Borrow<Q>,
+              Q: Eq + Hash + ?Sized
+    {
+        if self.len == 0 {
+            return None;
+        }
+        let mask = self.buckets.len() - 1;
+        let hash = hash::<H, Q>(key);
+        let mut index = hash as usize & mask;
+        while let Some(ref bucket) = self.buckets[index] {
+            if bucket.hash == hash && bucket.key.borrow() == key {
+                return Some(index);
+            }
+            index = (index + 1) & mask;
+        }
+        None
+    }
+
+    fn probe_vacant(&self, hash: u64) -> usize {
+        let mask = self.buckets.len() - 1;
+        let mut index = hash as usize & mask;
+        while self.buckets[index].is_some() {
+            index = (index + 1) & mask;
+        }
+        index
+    }
+}
+
+impl<K: Eq + Hash, V, H: Hasher + Default> FromIterator<(K, V)> for HashMap<K, V, H> {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> HashMap<K, V, H> {
+        let mut map = HashMap::new();
+        for (key, value) in iter {
+            map.insert(key, value);
+        }
+        map
+    }
+}
+
+pub struct IntoIter<K, V> {
+    buckets: vec::IntoIter<Option<Bucket<K, V>>>,
+    len: usize
+}
+
+impl<K, V> Iterator for IntoIter<K, V> {
+    type Item = (K, V);
+
+    fn next(&mut self) -> Option<(K, V)> {
+        let bucket = self.buckets.by_ref().flatten().next()?;
+        self.len -= 1;
+        Some((bucket.key, bucket.value))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<K, V, H> IntoIterator for HashMap<K, V, H> {
+    type Item = (K, V);
+    type IntoIter = IntoIter<K, V>;
+
+    fn into_iter(self) -> IntoIter<K, V> {
+        IntoIter {
+            buckets: self.buckets.into_iter(),
+            len: self.len
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_get_remove() {
+        let mut map: HashMap<u64, u64> = HashMap::new();
+        for i in 0..1000u64 {
+            assert_eq!(None, map.insert(i, i * 2));
+        }
+        assert_eq!(Some(2), map.insert(1, 3));
+        assert_eq!(1000, map.len());
+        for i in (0..1000u64).filter(|i| i % 3 == 0) {
+            assert_eq!(Some(i * 2), map.remove(&i));
+        }
+        for i in 0..1000u64 {
+            let expected = match i {
+                1 => Some(3),
+                i if i % 3 == 0 => None,
+                i => Some(i * 2)
+            };
+            assert_eq!(expected.as_ref(), map.get(&i));
+        }
+        assert_eq!(666, map.len());
+        *map.get_mut(&2).unwrap() = 7;
+        assert_eq!(Some((2, 7)), map.remove_entry(&2));
+        assert!(!map.contains_key(&2));
+    }
+
+    #[test]
+    fn retain_and_into_iter() {
+        let mut map: HashMap<u64, u64, IdentityHasher> = HashMap::new();
+        for i in 0..100u64 {
+            map.insert(i, i);
+        }
+        map.retain(|k, _| k % 2 == 0);
+        assert_eq!(50, map.len());
+        assert_eq!(50, map.keys().count());
+        let mut entries: Vec<(u64, u64)> = map.into_iter().collect();
+        entries.sort();
+        assert_eq!((0..100).filter(|i| i % 2 == 0).map(|i| (i, i)).collect::<Vec<_>>(), entries);
+    }
+}
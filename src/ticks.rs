@@ -0,0 +1,121 @@
+use core::ops::{Add, AddAssign, Sub, SubAssign};
+use core::time::Duration;
+
+/// A number of wheel ticks.
+///
+/// A tick lasts for the wheel's highest resolution, so converting to and from a `Duration` takes
+/// the tick duration of the wheel in question. Keeping counts of ticks in their own type stops
+/// them from being mixed up with milliseconds or other raw integers in driver code.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Ticks(pub u64);
+
+impl Ticks {
+    /// The number of whole ticks in `duration`, rounding down
+    pub fn from_duration(duration: Duration, tick: Duration) -> Ticks {
+        Ticks(saturate(duration.as_nanos() / tick_nanos(tick)))
+    }
+
+    /// The number of ticks needed to cover `duration`, rounding up
+    pub fn from_duration_ceil(duration: Duration, tick: Duration) -> Ticks {
+        let tick = tick_nanos(tick);
+        Ticks(saturate(duration.as_nanos().div_ceil(tick)))
+    }
+
+    /// The time taken by this many ticks, saturating at the maximum `Duration`
+    pub fn to_duration(self, tick: Duration) -> Duration {
+        let nanos = u128::from(self.0) * tick.as_nanos();
+        let secs = nanos / 1_000_000_000;
+        if secs > u128::from(u64::MAX) {
+            return Duration::MAX;
+        }
+        Duration::new(secs as u64, (nanos % 1_000_000_000) as u32)
+    }
+
+    pub fn saturating_sub(self, other: Ticks) -> Ticks {
+        Ticks(self.0.saturating_sub(other.0))
+    }
+
+    pub fn checked_sub(self, other: Ticks) -> Option<Ticks> {
+        self.0.checked_sub(other.0).map(Ticks)
+    }
+}
+
+fn tick_nanos(tick: Duration) -> u128 {
+    assert!(tick > Duration::from_secs(0), "tick duration must be non-zero");
+    tick.as_nanos()
+}
+
+fn saturate(ticks: u128) -> u64 {
+    if ticks > u128::from(u64::MAX) {
+        u64::MAX
+    } else {
+        ticks as u64
+    }
+}
+
+impl From<u64> for Ticks {
+    fn from(ticks: u64) -> Ticks {
+        Ticks(ticks)
+    }
+}
+
+impl From<Ticks> for u64 {
+    fn from(ticks: Ticks) -> u64 {
+        ticks.0
+    }
+}
+
+impl Add for Ticks {
+    type Output = Ticks;
+
+    fn add(self, other: Ticks) -> Ticks {
+        Ticks(self.0 + other.0)
+    }
+}
+
+impl AddAssign for Ticks {
+    fn add_assign(&mut self, other: Ticks) {
+        self.0 += other.0;
+    }
+}
+
+impl Sub for Ticks {
+    type Output = Ticks;
+
+    fn sub(self, other: Ticks) -> Ticks {
+        Ticks(self.0 - other.0)
+    }
+}
+
+impl SubAssign for Ticks {
+    fn sub_assign(&mut self, other: Ticks) {
+        self.0 -= other.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn duration_conversions() {
+        let tick = Duration::from_millis(10);
+        assert_eq!(Ticks(2), Ticks::from_duration(Duration::from_millis(25), tick));
+        assert_eq!(Ticks(3), Ticks::from_duration_ceil(Duration::from_millis(25), tick));
+        assert_eq!(Ticks(3), Ticks::from_duration_ceil(Duration::from_millis(30), tick));
+        assert_eq!(Duration::from_millis(30), Ticks(3).to_duration(tick));
+        assert_eq!(Duration::MAX, Ticks(u64::MAX).to_duration(Duration::from_secs(3600)));
+        assert_eq!(Ticks(u64::MAX), Ticks::from_duration(Duration::MAX, Duration::from_nanos(1)));
+    }
+
+    #[test]
+    fn arithmetic() {
+        let mut ticks = Ticks(5) + Ticks(3);
+        ticks -= Ticks(2);
+        assert_eq!(Ticks(6), ticks);
+        assert_eq!(Ticks(0), Ticks(2).saturating_sub(ticks));
+        assert_eq!(None, Ticks(2).checked_sub(ticks));
+        assert_eq!(6u64, ticks.into());
+    }
+}
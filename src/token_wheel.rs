@@ -0,0 +1,211 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::time::Duration;
+use alloc::vec::Vec;
+use collections::HashMap;
+use super::Wheel;
+
+/// Identifies one run of a timer started on a `TokenWheel`: its key, and the generation the
+/// wheel gave it when it was started.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct TimerToken<T> {
+    key: T,
+    generation: u64
+}
+
+impl<T> TimerToken<T> {
+    pub fn key(&self) -> &T {
+        &self.key
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn into_key(self) -> T {
+        self.key
+    }
+}
+
+/// A wheel whose `start` returns a `TimerToken`, so that stopping a timer can't cancel a later
+/// timer that reused its key.
+///
+/// When keys are reused, such as connection ids, a late stop for an old connection would
+/// otherwise cancel the timer of the new one. Here every start gives the key a new generation,
+/// and `stop` and `restart` only act on the timer if the token's generation is still current.
+/// As with the other wheels there is one timer per key, so starting a key again replaces its
+/// running timer.
+pub struct TokenWheel<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<TimerToken<T>>
+{
+    wheel: W,
+    // The generation of each running timer, and whether it is an interval timer
+    current: HashMap<T, (u64, bool)>,
+    next_generation: u64
+}
+
+impl<T, W> TokenWheel<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<TimerToken<T>>
+{
+    pub fn new(wheel: W) -> TokenWheel<T, W> {
+        TokenWheel {
+            wheel,
+            current: HashMap::new(),
+            next_generation: 0
+        }
+    }
+
+    /// Start a timer for `key`, replacing any that is running, and return its token.
+    ///
+    /// As with the wheels' `start`, durations shorter than a tick are ignored. The token returned
+    /// for one is never running, and any timer already running for the key is left alone.
+    pub fn start(&mut self, key: T, time: Duration) -> TimerToken<T> {
+        if time < self.wheel.tick_duration() {
+            return self.next_token(key);
+        }
+        let token = self.replace(key, false);
+        self.wheel.start(token.clone(), time);
+        token
+    }
+
+    /// Start a timer for `key` that expires every `period` until it is stopped, replacing any
+    /// that is running. The token stays current for every expiry.
+    pub fn start_interval(&mut self, key: T, period: Duration) -> TimerToken<T> {
+        if period < self.wheel.tick_duration() {
+            return self.next_token(key);
+        }
+        let token = self.replace(key, true);
+        self.wheel.start_interval(token.clone(), period);
+        token
+    }
+
+    /// Restart the timer `token` was issued for, returning false if it is no longer running.
+    /// See `Wheel::restart`.
+    pub fn restart(&mut self, token: &TimerToken<T>, time: Duration) -> bool {
+        self.is_running(token) && self.wheel.restart(token.clone(), time)
+    }
+
+    /// Cancel the timer `token` was issued for, returning the time it had left, or `None` if it
+    /// already expired or the key has been started again since.
+    pub fn stop(&mut self, token: &TimerToken<T>) -> Option<Duration> {
+        if !self.is_running(token) {
+            return None;
+        }
+        self.current.remove(&token.key);
+        self.wheel.stop(token.clone())
+    }
+
+    /// Whether the timer `token` was issued for is still running
+    pub fn is_running(&self, token: &TimerToken<T>) -> bool {
+        self.current.get(&token.key).is_some_and(|&(generation, _)| generation == token.generation)
+    }
+
+    /// The token of the timer running for `key`, if any
+    pub fn token(&self, key: &T) -> Option<TimerToken<T>> {
+        self.current.get(key).map(|&(generation, _)| TimerToken {
+            key: key.clone(),
+            generation
+        })
+    }
+
+    /// Advance the wheel by one tick and return the tokens of the timers that expired
+    pub fn expire(&mut self) -> Vec<TimerToken<T>> {
+        let mut expired = Vec::new();
+        for token in self.wheel.expire() {
+            match self.current.get(&token.key).cloned() {
+                Some((generation, interval)) if generation == token.generation => {
+                    if !interval {
+                        self.current.remove(&token.key);
+                    }
+                    expired.push(token);
+                },
+                _ => ()
+            }
+        }
+        expired
+    }
+
+    /// The number of running timers
+    pub fn len(&self) -> usize {
+        self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.current.is_empty()
+    }
+
+    /// How often `expire` must be called
+    pub fn tick_duration(&self) -> Duration {
+        self.wheel.tick_duration()
+    }
+
+    pub fn wheel(&self) -> &W {
+        &self.wheel
+    }
+
+    pub fn into_inner(self) -> W {
+        self.wheel
+    }
+
+    fn next_token(&mut self, key: T) -> TimerToken<T> {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        TimerToken {
+            key,
+            generation
+        }
+    }
+
+    // Stop the timer running for `key` and return a token with a new generation
+    fn replace(&mut self, key: T, interval: bool) -> TimerToken<T> {
+        let token = self.next_token(key.clone());
+        if let Some((old, _)) = self.current.insert(key.clone(), (token.generation, interval)) {
+            self.wheel.stop(TimerToken {
+                key,
+                generation: old
+            });
+        }
+        token
+    }
+}
+
+#[cfg(all(test, feature = "copy-wheel"))]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{CopyWheel, Resolution};
+
+    #[test]
+    fn stale_tokens_leave_new_timers_alone() {
+        let mut wheel = TokenWheel::new(CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]));
+        let old = wheel.start(7, Duration::from_millis(10));
+        let new = wheel.start(7, Duration::from_millis(20));
+        assert_eq!(None, wheel.stop(&old));
+        assert!(!wheel.restart(&old, Duration::from_secs(1)));
+        assert!(wheel.is_running(&new) && !wheel.is_running(&old));
+        assert_eq!(Some(new.clone()), wheel.token(&7));
+        assert_eq!(1, wheel.len());
+        let short = wheel.start(7, Duration::from_millis(1));
+        assert!(!wheel.is_running(&short) && wheel.is_running(&new));
+
+        assert!(wheel.expire().is_empty());
+        assert!(wheel.expire().is_empty());
+        assert_eq!(vec![new.clone()], wheel.expire());
+        assert_eq!(None, wheel.stop(&new));
+        assert!(wheel.is_empty());
+    }
+
+    #[test]
+    fn interval_tokens_stay_current() {
+        let mut wheel = TokenWheel::new(CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]));
+        let token = wheel.start_interval("tick", Duration::from_millis(10));
+        for _ in 0..3 {
+            assert!(wheel.expire().is_empty());
+            assert_eq!(vec![token.clone()], wheel.expire());
+        }
+        assert_eq!(Some(Duration::from_millis(20)), wheel.stop(&token));
+        assert!(wheel.expire().is_empty() && wheel.is_empty());
+    }
+}
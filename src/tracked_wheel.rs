@@ -0,0 +1,153 @@
+use core::hash::Hash;
+use core::fmt::Debug;
+use core::time::Duration;
+use alloc::vec::Vec;
+use collections::HashMap;
+use super::{Ticks, Wheel};
+
+/// A timer returned by `TrackedWheel::expire_detailed`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ExpiredTimer<T> {
+    pub key: T,
+    /// The tick the timer asked for, which is when its duration ran out rounded up to a whole tick
+    pub scheduled_tick: u64,
+    /// The tick it expired on
+    pub fired_tick: u64
+}
+
+impl<T> ExpiredTimer<T> {
+    /// The number of ticks between when the timer asked to expire and when it did
+    pub fn late_ticks(&self) -> u64 {
+        self.fired_tick.saturating_sub(self.scheduled_tick)
+    }
+}
+
+/// A wheel that remembers when each timer asked to expire, to measure how far rounding to slots
+/// skews them.
+///
+/// Ticks are counted from when the `TrackedWheel` was created. A timer fires when its slot is
+/// visited, which is never before it asked to but can be up to a slot of the timer's resolution
+/// later, so large skews are a sign that a finer `Resolution` would help.
+pub struct TrackedWheel<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    wheel: W,
+    tick: u64,
+    // The scheduled tick of each running timer, and the period in ticks of interval timers
+    scheduled: HashMap<T, (u64, Option<u64>)>
+}
+
+impl<T, W> TrackedWheel<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    pub fn new(wheel: W) -> TrackedWheel<T, W> {
+        TrackedWheel {
+            wheel,
+            tick: 0,
+            scheduled: HashMap::new()
+        }
+    }
+
+    /// Expire the timers due at the next tick, with the tick each one asked for
+    pub fn expire_detailed(&mut self) -> Vec<ExpiredTimer<T>> {
+        self.tick += 1;
+        let fired_tick = self.tick;
+        let mut expired = Vec::new();
+        for key in self.wheel.expire() {
+            let scheduled_tick = match self.scheduled.remove(&key) {
+                Some((scheduled_tick, Some(period))) => {
+                    // Interval timers are rearmed from the tick they fire on
+                    self.scheduled.insert(key.clone(), (fired_tick + period, Some(period)));
+                    scheduled_tick
+                },
+                Some((scheduled_tick, None)) => scheduled_tick,
+                None => fired_tick
+            };
+            expired.push(ExpiredTimer { key, scheduled_tick, fired_tick });
+        }
+        expired
+    }
+
+    pub fn wheel(&self) -> &W {
+        &self.wheel
+    }
+
+    pub fn into_inner(self) -> W {
+        self.wheel
+    }
+
+    fn ticks(&self, time: Duration) -> u64 {
+        Ticks::from_duration_ceil(time, self.wheel.tick_duration()).0
+    }
+}
+
+impl<T, W> Wheel<T> for TrackedWheel<T, W>
+    where T: Eq + Hash + Debug + Clone,
+          W: Wheel<T>
+{
+    type Expired = Vec<T>;
+
+    fn start(&mut self, key: T, time: Duration) {
+        let scheduled_tick = self.tick + self.ticks(time);
+        self.scheduled.insert(key.clone(), (scheduled_tick, None));
+        self.wheel.start(key, time);
+    }
+
+    fn stop(&mut self, key: T) -> Option<Duration> {
+        self.scheduled.remove(&key);
+        self.wheel.stop(key)
+    }
+
+    fn expire(&mut self) -> Vec<T> {
+        self.expire_detailed().into_iter().map(|expired| expired.key).collect()
+    }
+
+    fn start_with_delay(&mut self, key: T, delay: Duration, period: Duration) {
+        let scheduled_tick = self.tick + self.ticks(delay);
+        let period_ticks = self.ticks(period);
+        self.scheduled.insert(key.clone(), (scheduled_tick, Some(period_ticks)));
+        self.wheel.start_with_delay(key, delay, period);
+    }
+
+    fn restart(&mut self, key: T, time: Duration) -> bool {
+        let scheduled_tick = self.tick + self.ticks(time);
+        if let Some(scheduled) = self.scheduled.get_mut(&key) {
+            scheduled.0 = scheduled_tick;
+        }
+        self.wheel.restart(key, time)
+    }
+
+    fn tick_duration(&self) -> Duration {
+        self.wheel.tick_duration()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{CopyWheel, Resolution, Wheel};
+
+    #[test]
+    fn expire_detailed_reports_skew() {
+        let mut wheel = TrackedWheel::new(CopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]));
+        wheel.start("fine", Duration::from_millis(25));
+        wheel.start("coarse", Duration::from_millis(1010));
+        for _ in 0..2 {
+            assert!(wheel.expire_detailed().is_empty());
+        }
+        let expired = wheel.expire_detailed();
+        assert_eq!(vec![ExpiredTimer { key: "fine", scheduled_tick: 3, fired_tick: 3 }], expired);
+        assert_eq!(0, expired[0].late_ticks());
+
+        let mut expired = Vec::new();
+        while expired.is_empty() {
+            expired = wheel.expire_detailed();
+        }
+        assert_eq!(vec![ExpiredTimer { key: "coarse", scheduled_tick: 101, fired_tick: 200 }],
+                   expired);
+        assert_eq!(99, expired[0].late_ticks());
+    }
+}
@@ -0,0 +1,500 @@
+use core::mem;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::time::Instant;
+use alloc::vec::{Drain, Vec};
+use table::{HashMap, IdentityHasher};
+use super::{advance_slots, next_deadline, remove_slot_entry, DuplicatePolicy, InnerWheel, Resolution, Ticks, Wheel};
+#[cfg(feature = "std")]
+use super::{start_instant, time_until};
+use super::{StartError, check_span, wheel_sizes, deadline, remaining, resolution_duration, slots_ahead};
+
+/// A wheel specialized for `u64` keys such as file descriptors and connection ids.
+///
+/// It works like a `CopyWheel<u64>`, but keys are tracked in an open addressed table that uses
+/// each key as its own hash, so starting, stopping and expiring timers never runs a general
+/// purpose hasher. Dense keys that count up from zero are the best case. Keys that only differ in
+/// their high bits collide, so don't use this wheel for random ids.
+///
+/// This is the minimal variant, kept lean for the hot path. It has no overflow list, so
+/// `try_start` reports timers past the span, and it leaves out pausing, precise timers, slack,
+/// a timer limit, rounding and tick policies. Use a `CopyWheel<u64>` for those.
+pub struct U64Wheel {
+    resolutions: Vec<Resolution>,
+    keys: HashMap<u64, u64, IdentityHasher>,
+    periods: HashMap<u64, Duration, IdentityHasher>,
+    wheels: Vec<InnerWheel<u64>>,
+    slot_indexes: Vec<usize>,
+    tick: u64,
+    expired: Vec<u64>,
+    duplicate_policy: DuplicatePolicy,
+    // Time passed to `expire_elapsed` that didn't make up a whole tick
+    leftover: Duration,
+    // The instant of tick 0, for `expire_until`, once known
+    #[cfg(feature = "std")]
+    origin: Option<Instant>
+}
+
+impl U64Wheel {
+    /// Create a set of hierarchical inner wheels. See `CopyWheel::new`.
+    pub fn new(resolutions: Vec<Resolution>) -> U64Wheel {
+        U64Wheel::with_capacity(resolutions, 0)
+    }
+
+    /// Create a set of hierarchical inner wheels with room for `capacity` timers before the key
+    /// table needs to grow
+    pub fn with_capacity(mut resolutions: Vec<Resolution>, capacity: usize) -> U64Wheel {
+        let sizes = wheel_sizes(&mut resolutions);
+        let indexes = vec![0; sizes.len()];
+        U64Wheel {
+            resolutions,
+            keys: HashMap::with_capacity(capacity),
+            periods: HashMap::new(),
+            wheels: sizes.iter().map(|size| InnerWheel::new(*size)).collect(),
+            slot_indexes: indexes,
+            tick: 0,
+            expired: Vec::new(),
+            duplicate_policy: DuplicatePolicy::default(),
+            leftover: Duration::from_secs(0),
+            #[cfg(feature = "std")]
+            origin: start_instant()
+        }
+    }
+
+    /// The number of running timers
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// What `start` does with a timer that is already running
+    pub fn duplicate_policy(&self) -> DuplicatePolicy {
+        self.duplicate_policy
+    }
+
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+
+    /// The number of calls to `expire` until the next timer expires, or `None` if no timers are
+    /// running.
+    ///
+    /// The calls before then return nothing, so an event loop can sleep through them and catch up
+    /// afterwards. The answer comes from scanning the slots ahead rather than every key.
+    pub fn ticks_until_next_expiry(&self) -> Option<u64> {
+        let keys = &self.keys;
+        let next = next_deadline(self.tick, &self.wheels, &self.slot_indexes, |key, deadline| {
+            keys.get(key) == Some(&deadline)
+        })?;
+        Some(next - self.tick)
+    }
+
+    /// The time until the next timer expires, or `None` if no timers are running
+    pub fn next_expiry_in(&self) -> Option<Duration> {
+        let ticks = self.ticks_until_next_expiry()?;
+        Some(Ticks(ticks).to_duration(self.tick_duration()))
+    }
+
+    /// Whether a timer is running for `key`
+    pub fn contains(&self, key: &u64) -> bool {
+        self.keys.contains_key(key)
+    }
+
+    /// The time left until the timer for `key` expires, or `None` if it isn't running.
+    ///
+    /// The time left is rounded up to a whole number of ticks.
+    pub fn remaining(&self, key: &u64) -> Option<Duration> {
+        let deadline = *self.keys.get(key)?;
+        Some(remaining(self.tick, deadline, resolution_duration(self.resolutions[0])))
+    }
+
+    /// Start a timer with the given duration, restarting it if it is already running
+    pub fn start_u64(&mut self, key: u64, time: Duration) {
+        let old_deadline = self.keys.get(&key).cloned();
+        if !self.schedule(key, time) {
+            return;
+        }
+        if let (Some(old), Some(deadline)) = (old_deadline, self.keys.get_mut(&key)) {
+            if self.duplicate_policy.keeps_running(old, *deadline) {
+                *deadline = old;
+                return;
+            }
+        }
+        self.periods.remove(&key);
+    }
+
+    /// Cancel a timer, returning the time that was left before it would have expired
+    pub fn stop_u64(&mut self, key: u64) -> Option<Duration> {
+        self.cancel(key)
+    }
+
+    /// Start a timer for each key and duration. See `CopyWheel::start_all`.
+    pub fn start_all<I: IntoIterator<Item = (u64, Duration)>>(&mut self, timers: I) {
+        let timers = timers.into_iter();
+        self.keys.reserve(timers.size_hint().0);
+        for (key, time) in timers {
+            self.start_u64(key, time);
+        }
+    }
+
+    /// Cancel every timer. See `CopyWheel::clear`.
+    pub fn clear(&mut self) {
+        self.keys.clear();
+        self.periods.clear();
+        for wheel in &mut self.wheels {
+            for slot in &mut wheel.slots {
+                slot.entries.clear();
+            }
+        }
+    }
+
+    /// Cancel every timer, returning each key with the time that was left. See
+    /// `CopyWheel::drain`.
+    pub fn drain(&mut self) -> Vec<(u64, Duration)> {
+        let keys = mem::take(&mut self.keys);
+        self.clear();
+        let tick = resolution_duration(self.resolutions[0]);
+        keys.into_iter().map(|(key, deadline)| (key, remaining(self.tick, deadline, tick))).collect()
+    }
+
+    /// Keep only the timers whose keys `f` returns true for, and cancel the rest
+    pub fn retain<F: FnMut(&u64) -> bool>(&mut self, mut f: F) {
+        self.keys.retain(|key, _| f(key));
+        let keys = &self.keys;
+        self.periods.retain(|key, _| keys.contains_key(key));
+    }
+
+    /// Cancel the timers whose keys `f` returns true for, returning how many were cancelled
+    pub fn cancel_where<F: FnMut(&u64) -> bool>(&mut self, mut f: F) -> usize {
+        let len = self.keys.len();
+        self.retain(|key| !f(key));
+        len - self.keys.len()
+    }
+
+    /// Cancel a timer, returning the time that was left before it would have expired, or `None`
+    /// if it wasn't running
+    pub fn cancel(&mut self, key: u64) -> Option<Duration> {
+        self.periods.remove(&key);
+        let deadline = self.keys.remove(&key)?;
+        Some(remaining(self.tick, deadline, resolution_duration(self.resolutions[0])))
+    }
+
+    /// Move a running timer to a new deadline, removing its old slot entry. See
+    /// `CopyWheel::reschedule`.
+    pub fn reschedule(&mut self, key: u64, time: Duration) -> bool {
+        let old_deadline = match self.keys.get(&key) {
+            Some(&deadline) => deadline,
+            None => return false
+        };
+        if !self.schedule(key, time) {
+            return false;
+        }
+        remove_slot_entry(self.tick, &mut self.wheels, &self.slot_indexes, old_deadline,
+                          |entry| *entry == key);
+        true
+    }
+
+    /// Push a running timer's deadline out by `extra`. See `CopyWheel::extend_by`.
+    pub fn extend_by(&mut self, key: u64, extra: Duration) -> bool {
+        let left = match self.remaining(&key) {
+            Some(left) => left,
+            None => return false
+        };
+        // A timer with one tick left fires on the next call to `expire`, like one started for a
+        // tick, so the time to schedule counts from there
+        let tick = self.tick_duration();
+        let time = left.saturating_sub(tick) + extra;
+        time < tick || self.reschedule(key, time)
+    }
+
+    /// Start a timer that isn't already running, without adjusting the duration to fit. See
+    /// `CopyWheel::try_start`.
+    pub fn try_start(&mut self, key: u64, time: Duration) -> Result<(), StartError> {
+        if self.keys.contains_key(&key) {
+            return Err(StartError::DuplicateKey);
+        }
+        check_span(&self.resolutions, &self.wheels, time)?;
+        if !self.schedule(key, time) {
+            // Resolutions with gaps between them can't represent every duration in the span
+            return Err(StartError::TooShort(resolution_duration(self.resolutions[0])));
+        }
+        Ok(())
+    }
+
+    /// Expire the timers due at the next tick into a buffer kept by the wheel. See
+    /// `CopyWheel::expire_iter`.
+    pub fn expire_iter(&mut self) -> Drain<'_, u64> {
+        let mut expired = mem::take(&mut self.expired);
+        self.expire_into(&mut expired);
+        self.expired = expired;
+        self.expired.drain(..)
+    }
+
+    /// Expire the timers due at the next tick, like `expire`, but append them to `expired`. See
+    /// `CopyWheel::expire_into`.
+    pub fn expire_into(&mut self, expired: &mut Vec<u64>) {
+        let mut keys = mem::take(&mut self.keys);
+        self.tick += 1;
+        let now = self.tick;
+        let start = expired.len();
+        for (wheel, slot_index) in self.wheels.iter_mut().zip(&mut self.slot_indexes) {
+            *slot_index = (*slot_index + 1) % wheel.slots.len();
+            for key in wheel.slots[*slot_index].entries.drain(..) {
+                // Skip entries for timers that were cancelled or restarted
+                if keys.get(&key) == Some(&now) {
+                    keys.remove(&key);
+                    expired.push(key);
+                }
+            }
+
+            // We haven't wrapped around to the next wheel
+            if *slot_index != 0 {
+                break;
+            }
+        }
+        self.keys = keys;
+
+        for key in &expired[start..] {
+            if let Some(period) = self.periods.get(key).cloned() {
+                self.schedule(*key, period);
+            }
+        }
+    }
+
+    /// Move the wheel on by the ticks that fit in `elapsed`, carrying over the rest. See
+    /// `CopyWheel::expire_elapsed`.
+    pub fn expire_elapsed(&mut self, elapsed: Duration) -> Vec<u64> {
+        let tick = self.tick_duration();
+        let elapsed = self.leftover + elapsed;
+        let ticks = Ticks::from_duration(elapsed, tick);
+        self.leftover = elapsed - ticks.to_duration(tick);
+        self.advance(ticks.0)
+    }
+
+    /// Move the wheel on by `ticks` at once, returning every timer that came due along the way.
+    /// See `CopyWheel::advance`.
+    pub fn advance(&mut self, ticks: u64) -> Vec<u64> {
+        let end = self.tick + ticks;
+        let mut expired = Vec::new();
+        {
+            let keys = &mut self.keys;
+            advance_slots(self.tick, ticks, &mut self.wheels, &mut self.slot_indexes, |entries| {
+                entries.retain(|key| match keys.get(key).cloned() {
+                    Some(deadline) if deadline <= end => {
+                        keys.remove(key);
+                        expired.push(*key);
+                        false
+                    },
+                    Some(_) => true,
+                    None => false
+                });
+            });
+        }
+        self.tick = end;
+
+        for key in &expired {
+            if let Some(period) = self.periods.get(key).cloned() {
+                self.schedule(*key, period);
+            }
+        }
+        expired
+    }
+
+    /// The instant of the wheel's last tick, counting from when it was created
+    #[cfg(feature = "std")]
+    pub fn last_tick_at(&self) -> Instant {
+        let origin = self.origin.expect("the wheel needs the time from sync_clock first");
+        origin + Ticks(self.tick).to_duration(self.tick_duration())
+    }
+
+    /// Make `now` the instant of the wheel's last tick. See `CopyWheel::sync_clock`.
+    #[cfg(feature = "std")]
+    pub fn sync_clock(&mut self, now: Instant) {
+        let since = Ticks(self.tick).to_duration(self.tick_duration());
+        if let Some(origin) = now.checked_sub(since) {
+            self.origin = Some(origin);
+        }
+    }
+
+    /// Start a timer that expires at `deadline` rather than after a duration. See
+    /// `CopyWheel::start_at`.
+    #[cfg(feature = "std")]
+    pub fn start_at(&mut self, key: u64, deadline: Instant) {
+        let wait = deadline.saturating_duration_since(self.last_tick_at());
+        let time = time_until(wait, self.tick_duration());
+        self.start(key, time);
+    }
+
+    /// Restart a running timer so that it expires at `deadline`. See `CopyWheel::restart_at`.
+    #[cfg(feature = "std")]
+    pub fn restart_at(&mut self, key: u64, deadline: Instant) -> bool {
+        let wait = deadline.saturating_duration_since(self.last_tick_at());
+        let time = time_until(wait, self.tick_duration());
+        self.restart(key, time)
+    }
+
+    /// The instant a running timer is due to expire
+    #[cfg(feature = "std")]
+    pub fn expires_at(&self, key: &u64) -> Option<Instant> {
+        Some(self.last_tick_at() + self.remaining(key)?)
+    }
+
+    /// Expire every tick that has passed by `now`, counting from when the wheel was created. See
+    /// `CopyWheel::expire_until`.
+    #[cfg(feature = "std")]
+    pub fn expire_until(&mut self, now: Instant) -> Vec<u64> {
+        let origin = match self.origin {
+            Some(origin) => origin,
+            None => {
+                self.sync_clock(now);
+                return Vec::new();
+            }
+        };
+        let elapsed = now.saturating_duration_since(origin);
+        let due = Ticks::from_duration(elapsed, self.tick_duration());
+        let ticks = due.saturating_sub(Ticks(self.tick));
+        self.advance(ticks.0)
+    }
+
+    /// Expire every tick that has passed by `now`. See `CopyWheel::expire_at`.
+    #[cfg(feature = "std")]
+    pub fn expire_at(&mut self, now: Instant) -> Vec<u64> {
+        self.expire_until(now)
+    }
+
+    /// How long an event loop using `expire_until` can wait at `now` before the next timer is
+    /// due. See `CopyWheel::poll_timeout`.
+    #[cfg(feature = "std")]
+    pub fn poll_timeout(&self, now: Instant) -> Option<Duration> {
+        let due = self.last_tick_at() + self.next_expiry_in()?;
+        Some(due.saturating_duration_since(now))
+    }
+
+    // Schedule a timer without changing its period, returning false if the duration is too short
+    // for the wheel
+    fn schedule(&mut self, key: u64, time: Duration) -> bool {
+        // Try the lowest resolution first, as the other wheels do
+        for resolution in self.resolutions.clone().iter().rev() {
+            if let Some(deadline) = self.insert(key, time, *resolution) {
+                self.keys.insert(key, deadline);
+                return true;
+            }
+        }
+        false
+    }
+
+    fn insert(&mut self, key: u64, time: Duration, resolution: Resolution) -> Option<u64> {
+        let slot = slots_ahead(resolution, time, Some(&resolution) == self.resolutions.last());
+        // The slot must be at least 2 ahead of the current, or the timer belongs in a finer wheel
+        if slot == 1 {
+            return None;
+        }
+        let wheel_index = self.resolutions.iter().position(|r| *r == resolution)?;
+        let max_slot = self.wheels[wheel_index].slots.len();
+        let slot = slot.min(max_slot);
+        let slot_index = (self.slot_indexes[wheel_index] + slot) % max_slot;
+        self.wheels[wheel_index].slots[slot_index].entries.push(key);
+        Some(deadline(self.tick, &self.wheels[..wheel_index], slot))
+    }
+}
+
+impl Wheel<u64> for U64Wheel {
+    type Expired = Vec<u64>;
+
+    fn start(&mut self, key: u64, time: Duration) {
+        self.start_u64(key, time);
+    }
+
+    fn stop(&mut self, key: u64) -> Option<Duration> {
+        self.stop_u64(key)
+    }
+
+    /// Return any expired timer keys
+    fn expire(&mut self) -> Vec<u64> {
+        let mut expired = Vec::new();
+        self.expire_into(&mut expired);
+        expired
+    }
+
+    fn start_with_delay(&mut self, key: u64, delay: Duration, period: Duration) {
+        if self.schedule(key, delay) {
+            let period = period.max(self.tick_duration());
+            self.periods.insert(key, period);
+        }
+    }
+
+    fn restart(&mut self, key: u64, time: Duration) -> bool {
+        self.keys.contains_key(&key) && self.schedule(key, time)
+    }
+
+    fn tick_duration(&self) -> Duration {
+        resolution_duration(self.resolutions[0])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{CopyWheel, Resolution, Wheel};
+
+    #[test]
+    fn matches_copy_wheel() {
+        let resolutions = vec![Resolution::Ms, Resolution::TenMs, Resolution::Sec, Resolution::Min];
+        let mut wheel = U64Wheel::new(resolutions.clone());
+        let mut copy = CopyWheel::new(resolutions);
+        for key in 0..2000u64 {
+            let time = Duration::from_millis(key * 37 % 50_000);
+            wheel.start_u64(key, time);
+            copy.start(key, time);
+        }
+        for key in (0..2000u64).filter(|key| key % 3 == 0) {
+            wheel.stop_u64(key);
+            copy.stop(key);
+        }
+        for key in (0..2000u64).filter(|key| key % 5 == 0) {
+            wheel.start_u64(key, Duration::from_millis(15));
+            copy.start(key, Duration::from_millis(15));
+        }
+        for _ in 0..60_000 {
+            let mut expired = wheel.expire();
+            let mut expected = copy.expire();
+            expired.sort();
+            expected.sort();
+            assert_eq!(expected, expired);
+        }
+        assert!(wheel.keys.is_empty());
+    }
+
+    #[test]
+    fn elapsed_time_carries_over() {
+        let mut wheel = U64Wheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start_u64(1, Duration::from_millis(50));
+        let mut expired = Vec::new();
+        for frame in 1..7 {
+            expired.extend(wheel.expire_elapsed(Duration::from_millis(16)).into_iter().map(|key| (frame, key)));
+        }
+        assert_eq!(vec![(4, 1)], expired);
+        assert_eq!(Duration::from_millis(6), wheel.leftover);
+    }
+
+    #[test]
+    fn try_start_reports_timers_past_the_span() {
+        let mut wheel = U64Wheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        assert_eq!(Err(StartError::TooLong(Duration::from_secs(60))),
+                   wheel.try_start(1, Duration::from_secs(60)));
+        assert_eq!(Ok(()), wheel.try_start(1, Duration::from_secs(59)));
+    }
+
+    #[test]
+    fn cancel_returns_remaining_time() {
+        let mut wheel = U64Wheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start(7, Duration::from_millis(50));
+        wheel.expire();
+        assert_eq!(Some(Duration::from_millis(50)), wheel.cancel(7));
+        assert_eq!(None, wheel.cancel(7));
+    }
+}
@@ -0,0 +1,83 @@
+use core::hash::{Hash, Hasher};
+#[cfg(feature = "alloc-wheel")]
+use super::AllocWheel;
+#[cfg(feature = "copy-wheel")]
+use super::CopyWheel;
+
+/// A 16 byte UUID used as a timer key.
+///
+/// The derived `Hash` for a byte array feeds the length and all 16 bytes through the hasher, which
+/// dominates the cost of starting and stopping timers keyed by UUIDs. UUIDs are already close to
+/// uniformly random, so this key hashes a single `u64` made by folding the two halves together.
+///
+/// Convert with `UuidKey::from_u128(uuid.as_u128())` or `UuidKey::from_bytes(*uuid.as_bytes())`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Default)]
+pub struct UuidKey([u8; 16]);
+
+impl UuidKey {
+    pub fn from_bytes(bytes: [u8; 16]) -> UuidKey {
+        UuidKey(bytes)
+    }
+
+    pub fn from_u128(value: u128) -> UuidKey {
+        UuidKey(value.to_be_bytes())
+    }
+
+    pub fn as_bytes(&self) -> &[u8; 16] {
+        &self.0
+    }
+
+    pub fn as_u128(&self) -> u128 {
+        u128::from_be_bytes(self.0)
+    }
+}
+
+impl Hash for UuidKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        let value = self.as_u128();
+        state.write_u64((value >> 64) as u64 ^ value as u64);
+    }
+}
+
+impl From<[u8; 16]> for UuidKey {
+    fn from(bytes: [u8; 16]) -> UuidKey {
+        UuidKey(bytes)
+    }
+}
+
+impl From<u128> for UuidKey {
+    fn from(value: u128) -> UuidKey {
+        UuidKey::from_u128(value)
+    }
+}
+
+/// A `CopyWheel` keyed by UUIDs. The keys are small and `Copy`, so this is usually the better fit.
+#[cfg(feature = "copy-wheel")]
+pub type UuidCopyWheel = CopyWheel<UuidKey>;
+
+/// An `AllocWheel` keyed by UUIDs
+#[cfg(feature = "alloc-wheel")]
+pub type UuidAllocWheel = AllocWheel<UuidKey>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::{Resolution, Wheel};
+
+    #[test]
+    fn uuid_keys_round_trip_and_expire() {
+        let value = 0x67e5_5044_10b1_426f_9247_bb68_0e5f_e0c8u128;
+        let key = UuidKey::from(value);
+        assert_eq!(value, key.as_u128());
+        assert_eq!(0x67, key.as_bytes()[0]);
+        assert_eq!(key, UuidKey::from_bytes(*key.as_bytes()));
+
+        let mut wheel = UuidCopyWheel::new(vec![Resolution::TenMs, Resolution::Sec]);
+        wheel.start(key, Duration::from_millis(10));
+        wheel.start(UuidKey::from(1), Duration::from_millis(10));
+        wheel.stop(UuidKey::from(1));
+        assert!(wheel.expire().is_empty());
+        assert_eq!(vec![key], wheel.expire());
+    }
+}
@@ -0,0 +1,177 @@
+use std::hash::Hash;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::time::Duration;
+use super::{CopyWheel, Wheel, Resolution};
+
+/// A stalled component reported by `Watchdog::expire`
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Stall<C> {
+    pub component: C,
+    /// 0 for the initial stall, then the index of each escalation tier reached, starting at 1
+    pub tier: usize
+}
+
+struct Armed {
+    timeout: Duration,
+    next_tier: usize,
+    generation: u64
+}
+
+/// A watchdog for a set of components that must periodically prove they are making progress.
+///
+/// A component is armed with a timeout and must `feed` the watchdog before it elapses, otherwise
+/// it is reported as stalled at tier 0. If escalation tiers are configured, a component that stays
+/// stalled is reported again at tier 1 after the first escalation delay, tier 2 after the second,
+/// and so on, so supervisors can go from logging to restarting to aborting. Feeding a stalled
+/// component clears the stall and starts again from its timeout. Timeouts and escalation delays
+/// shorter than a tick are rounded up to a tick.
+pub struct Watchdog<C: Eq + Hash + Debug + Clone> {
+    escalation: Vec<Duration>,
+    components: HashMap<C, Armed>,
+    wheel: CopyWheel<(C, u64)>,
+    next_generation: u64
+}
+
+impl<C: Eq + Hash + Debug + Clone> Watchdog<C> {
+    /// Create a watchdog that reports each stall once
+    pub fn new(resolutions: Vec<Resolution>) -> Watchdog<C> {
+        Watchdog::with_escalation(resolutions, Vec::new())
+    }
+
+    /// Create a watchdog that re-reports stalled components after each escalation delay
+    pub fn with_escalation(resolutions: Vec<Resolution>, escalation: Vec<Duration>) -> Watchdog<C> {
+        Watchdog {
+            escalation,
+            components: HashMap::new(),
+            wheel: CopyWheel::new(resolutions),
+            next_generation: 0
+        }
+    }
+
+    /// Start watching a component. Arming a component that is already armed replaces its timeout.
+    pub fn arm(&mut self, component: C, timeout: Duration) {
+        self.disarm(&component);
+        let generation = self.start(component.clone(), timeout);
+        self.components.insert(component, Armed {
+            timeout,
+            next_tier: 0,
+            generation
+        });
+    }
+
+    /// Record progress for a component, restarting its timeout and clearing any stall.
+    /// Returns false if the component isn't armed.
+    pub fn feed(&mut self, component: &C) -> bool {
+        let timeout = match self.components.get(component) {
+            Some(armed) => armed.timeout,
+            None => return false
+        };
+        self.arm(component.clone(), timeout);
+        true
+    }
+
+    /// Stop watching a component. Returns false if the component isn't armed.
+    pub fn disarm(&mut self, component: &C) -> bool {
+        match self.components.remove(component) {
+            Some(armed) => {
+                self.wheel.stop((component.clone(), armed.generation));
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn is_armed(&self, component: &C) -> bool {
+        self.components.contains_key(component)
+    }
+
+    /// Return true if the component has been reported stalled and not fed since
+    pub fn is_stalled(&self, component: &C) -> bool {
+        self.components.get(component).is_some_and(|armed| armed.next_tier > 0)
+    }
+
+    /// Advance the wheel by one tick and return any newly stalled or escalated components
+    pub fn expire(&mut self) -> Vec<Stall<C>> {
+        let mut stalls = Vec::new();
+        for (component, generation) in self.wheel.expire() {
+            let tier = match self.components.get(&component) {
+                Some(armed) if armed.generation == generation => armed.next_tier,
+                _ => continue
+            };
+            // Escalation delay i schedules tier i + 1
+            let next = self.escalation.get(tier).cloned();
+            let generation = match next {
+                Some(delay) => self.start(component.clone(), delay),
+                None => generation
+            };
+            let armed = self.components.get_mut(&component).unwrap();
+            armed.next_tier = tier + 1;
+            armed.generation = generation;
+            stalls.push(Stall { component, tier });
+        }
+        stalls
+    }
+
+    fn start(&mut self, component: C, duration: Duration) -> u64 {
+        let generation = self.next_generation;
+        self.next_generation += 1;
+        let duration = duration.max(self.wheel.tick_duration());
+        self.wheel.start((component, generation), duration);
+        generation
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use super::super::Resolution;
+
+    fn run(watchdog: &mut Watchdog<&'static str>, ticks: usize) -> Vec<(usize, Stall<&'static str>)> {
+        let mut stalls = Vec::new();
+        for tick in 1..ticks + 1 {
+            stalls.extend(watchdog.expire().into_iter().map(|stall| (tick, stall)));
+        }
+        stalls
+    }
+
+    #[test]
+    fn fed_components_never_stall() {
+        let mut watchdog = Watchdog::new(vec![Resolution::TenMs, Resolution::Sec]);
+        watchdog.arm("db", Duration::from_millis(50));
+        for _ in 0..100 {
+            assert!(watchdog.expire().is_empty());
+            assert!(watchdog.feed(&"db"));
+        }
+        assert!(watchdog.disarm(&"db"));
+        assert!(!watchdog.feed(&"db"));
+    }
+
+    #[test]
+    fn timeouts_shorter_than_a_tick_still_stall() {
+        let mut watchdog = Watchdog::with_escalation(vec![Resolution::TenMs, Resolution::Sec],
+                                                     vec![Duration::from_millis(5)]);
+        watchdog.arm("db", Duration::from_millis(5));
+        assert_eq!(vec![(2, Stall { component: "db", tier: 0 }),
+                        (4, Stall { component: "db", tier: 1 })],
+                   run(&mut watchdog, 10));
+    }
+
+    #[test]
+    fn stalls_escalate_until_fed() {
+        let mut watchdog = Watchdog::with_escalation(vec![Resolution::TenMs, Resolution::Sec],
+                                                     vec![Duration::from_millis(20),
+                                                          Duration::from_millis(40)]);
+        watchdog.arm("db", Duration::from_millis(50));
+        assert_eq!(vec![(6, Stall { component: "db", tier: 0 }),
+                        (9, Stall { component: "db", tier: 1 }),
+                        (14, Stall { component: "db", tier: 2 })],
+                   run(&mut watchdog, 50));
+        assert!(watchdog.is_stalled(&"db"));
+
+        watchdog.feed(&"db");
+        assert!(!watchdog.is_stalled(&"db"));
+        assert_eq!(vec![(6, Stall { component: "db", tier: 0 })], run(&mut watchdog, 8));
+    }
+}